@@ -0,0 +1 @@
+DROP TABLE presenter_accounts;
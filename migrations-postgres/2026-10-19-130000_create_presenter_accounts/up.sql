@@ -0,0 +1,7 @@
+CREATE TABLE presenter_accounts (
+  user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+  password_hash VARCHAR(255) NOT NULL,
+  failed_logins INTEGER NOT NULL DEFAULT 0,
+  locked_until TIMESTAMP,
+  created TIMESTAMP NOT NULL
+);
@@ -0,0 +1,4 @@
+DELETE FROM answers WHERE user_id IS NULL;
+ALTER TABLE answers DROP COLUMN participant_id;
+ALTER TABLE answers ALTER COLUMN user_id SET NOT NULL;
+DROP TABLE participants;
@@ -0,0 +1,11 @@
+CREATE TABLE participants (
+  id SERIAL PRIMARY KEY,
+  presentation_id INTEGER NOT NULL REFERENCES presentations (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL
+);
+CREATE INDEX participants_presentation_id_idx ON participants (presentation_id);
+
+-- Answers are given either by a user or by an anonymous participant.
+ALTER TABLE answers ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE answers ADD COLUMN participant_id INTEGER REFERENCES participants (id) ON DELETE CASCADE;
+CREATE INDEX answers_participant_id_idx ON answers (participant_id);
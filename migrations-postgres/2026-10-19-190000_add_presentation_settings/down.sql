@@ -0,0 +1 @@
+ALTER TABLE presentations DROP COLUMN voting_duration;
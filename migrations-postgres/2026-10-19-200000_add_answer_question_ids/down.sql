@@ -0,0 +1,2 @@
+DROP INDEX answers_participant_id_question_id_idx;
+ALTER TABLE answers DROP COLUMN question_id;
@@ -0,0 +1,14 @@
+-- Answers carry the question of their option, so the database refuses a second answer of a
+-- participant to the same question, even from concurrent requests.
+ALTER TABLE answers ADD COLUMN question_id INTEGER REFERENCES questions (id) ON DELETE CASCADE;
+UPDATE answers SET question_id = options.question_id
+  FROM options WHERE options.id = answers.option_id;
+
+-- Only the first answer of a participant to a question is kept.
+DELETE FROM answers AS later USING answers AS earlier
+  WHERE earlier.participant_id = later.participant_id
+  AND earlier.question_id = later.question_id
+  AND earlier.id < later.id;
+
+ALTER TABLE answers ALTER COLUMN question_id SET NOT NULL;
+CREATE UNIQUE INDEX answers_participant_id_question_id_idx ON answers (participant_id, question_id);
@@ -0,0 +1,5 @@
+DROP TABLE answers;
+DROP TABLE options;
+DROP TABLE questions;
+DROP TABLE presentations;
+DROP TABLE users;
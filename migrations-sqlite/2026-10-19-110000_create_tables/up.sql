@@ -0,0 +1,44 @@
+CREATE TABLE users (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  created TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX users_name_unique ON users (name);
+
+CREATE TABLE presentations (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  title VARCHAR(255) NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL
+);
+CREATE INDEX presentations_user_id_idx ON presentations (user_id);
+
+CREATE TABLE questions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  title VARCHAR(255) NOT NULL,
+  created TIMESTAMP NOT NULL,
+  presentation_id INTEGER NOT NULL REFERENCES presentations (id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE
+);
+CREATE INDEX questions_presentation_id_created_idx ON questions (presentation_id, created);
+CREATE INDEX questions_user_id_idx ON questions (user_id);
+
+CREATE TABLE options (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  data VARCHAR(255) NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  question_id INTEGER NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL,
+  is_correct BOOLEAN NOT NULL DEFAULT 0
+);
+CREATE INDEX options_question_id_idx ON options (question_id);
+CREATE INDEX options_user_id_idx ON options (user_id);
+
+CREATE TABLE answers (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL,
+  option_id INTEGER NOT NULL REFERENCES options (id) ON DELETE CASCADE
+);
+CREATE INDEX answers_option_id_idx ON answers (option_id);
+CREATE INDEX answers_user_id_idx ON answers (user_id);
@@ -0,0 +1,9 @@
+CREATE TABLE user_identities (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  provider VARCHAR(32) NOT NULL,
+  subject VARCHAR(255) NOT NULL,
+  created TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX user_identities_provider_subject_unique ON user_identities (provider, subject);
+CREATE INDEX user_identities_user_id_idx ON user_identities (user_id);
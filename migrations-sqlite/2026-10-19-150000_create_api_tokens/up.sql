@@ -0,0 +1,11 @@
+CREATE TABLE api_tokens (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  name VARCHAR(255) NOT NULL,
+  token_hash VARCHAR(64) NOT NULL,
+  scopes VARCHAR(32) NOT NULL,
+  created TIMESTAMP NOT NULL,
+  last_used TIMESTAMP
+);
+CREATE UNIQUE INDEX api_tokens_token_hash_unique ON api_tokens (token_hash);
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
@@ -0,0 +1,13 @@
+CREATE TABLE answers_old (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL,
+  option_id INTEGER NOT NULL REFERENCES options (id) ON DELETE CASCADE
+);
+INSERT INTO answers_old (id, user_id, created, option_id)
+  SELECT id, user_id, created, option_id FROM answers WHERE user_id IS NOT NULL;
+DROP TABLE answers;
+ALTER TABLE answers_old RENAME TO answers;
+CREATE INDEX answers_option_id_idx ON answers (option_id);
+CREATE INDEX answers_user_id_idx ON answers (user_id);
+DROP TABLE participants;
@@ -0,0 +1,23 @@
+CREATE TABLE participants (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  presentation_id INTEGER NOT NULL REFERENCES presentations (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL
+);
+CREATE INDEX participants_presentation_id_idx ON participants (presentation_id);
+
+-- Answers are given either by a user or by an anonymous participant. SQLite can't drop the
+-- NOT NULL constraint of a column, so the table is rebuilt.
+CREATE TABLE answers_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER REFERENCES users (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL,
+  option_id INTEGER NOT NULL REFERENCES options (id) ON DELETE CASCADE,
+  participant_id INTEGER REFERENCES participants (id) ON DELETE CASCADE
+);
+INSERT INTO answers_new (id, user_id, created, option_id)
+  SELECT id, user_id, created, option_id FROM answers;
+DROP TABLE answers;
+ALTER TABLE answers_new RENAME TO answers;
+CREATE INDEX answers_option_id_idx ON answers (option_id);
+CREATE INDEX answers_user_id_idx ON answers (user_id);
+CREATE INDEX answers_participant_id_idx ON answers (participant_id);
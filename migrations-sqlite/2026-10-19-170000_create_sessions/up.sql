@@ -0,0 +1,9 @@
+CREATE TABLE sessions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  token_hash VARCHAR(64) NOT NULL,
+  created TIMESTAMP NOT NULL,
+  last_seen TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX sessions_token_hash_unique ON sessions (token_hash);
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
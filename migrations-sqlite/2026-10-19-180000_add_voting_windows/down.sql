@@ -0,0 +1,3 @@
+ALTER TABLE questions DROP COLUMN opened_at;
+ALTER TABLE questions DROP COLUMN closes_at;
+ALTER TABLE questions DROP COLUMN closed_at;
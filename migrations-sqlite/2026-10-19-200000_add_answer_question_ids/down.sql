@@ -0,0 +1,14 @@
+CREATE TABLE answers_old (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER REFERENCES users (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL,
+  option_id INTEGER NOT NULL REFERENCES options (id) ON DELETE CASCADE,
+  participant_id INTEGER REFERENCES participants (id) ON DELETE CASCADE
+);
+INSERT INTO answers_old (id, user_id, created, option_id, participant_id)
+  SELECT id, user_id, created, option_id, participant_id FROM answers;
+DROP TABLE answers;
+ALTER TABLE answers_old RENAME TO answers;
+CREATE INDEX answers_option_id_idx ON answers (option_id);
+CREATE INDEX answers_user_id_idx ON answers (user_id);
+CREATE INDEX answers_participant_id_idx ON answers (participant_id);
@@ -0,0 +1,29 @@
+-- Answers carry the question of their option, so the database refuses a second answer of a
+-- participant to the same question, even from concurrent requests. SQLite can't add a NOT NULL
+-- column without a default, so the table is rebuilt.
+CREATE TABLE answers_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER REFERENCES users (id) ON DELETE CASCADE,
+  created TIMESTAMP NOT NULL,
+  option_id INTEGER NOT NULL REFERENCES options (id) ON DELETE CASCADE,
+  participant_id INTEGER REFERENCES participants (id) ON DELETE CASCADE,
+  question_id INTEGER NOT NULL REFERENCES questions (id) ON DELETE CASCADE
+);
+INSERT INTO answers_new (id, user_id, created, option_id, participant_id, question_id)
+  SELECT answers.id, answers.user_id, answers.created, answers.option_id, answers.participant_id,
+    options.question_id
+  FROM answers INNER JOIN options ON options.id = answers.option_id;
+DROP TABLE answers;
+ALTER TABLE answers_new RENAME TO answers;
+
+-- Only the first answer of a participant to a question is kept.
+DELETE FROM answers WHERE EXISTS (
+  SELECT 1 FROM answers AS earlier
+  WHERE earlier.participant_id = answers.participant_id
+  AND earlier.question_id = answers.question_id
+  AND earlier.id < answers.id
+);
+
+CREATE INDEX answers_option_id_idx ON answers (option_id);
+CREATE INDEX answers_user_id_idx ON answers (user_id);
+CREATE UNIQUE INDEX answers_participant_id_question_id_idx ON answers (participant_id, question_id);
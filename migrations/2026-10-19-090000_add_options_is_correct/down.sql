@@ -0,0 +1 @@
+ALTER TABLE options DROP is_correct;
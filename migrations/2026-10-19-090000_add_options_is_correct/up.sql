@@ -0,0 +1 @@
+ALTER TABLE options ADD is_correct BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,15 @@
+ALTER TABLE answers DROP FOREIGN KEY answers_option_id_fk, DROP FOREIGN KEY answers_user_id_fk;
+ALTER TABLE options DROP FOREIGN KEY options_question_id_fk, DROP FOREIGN KEY options_user_id_fk;
+ALTER TABLE questions
+  DROP FOREIGN KEY questions_presentation_id_fk,
+  DROP FOREIGN KEY questions_user_id_fk;
+ALTER TABLE presentations DROP FOREIGN KEY presentations_user_id_fk;
+
+DROP INDEX answers_user_id_idx ON answers;
+DROP INDEX answers_option_id_idx ON answers;
+DROP INDEX options_user_id_idx ON options;
+DROP INDEX options_question_id_idx ON options;
+DROP INDEX questions_user_id_idx ON questions;
+DROP INDEX questions_presentation_id_created_idx ON questions;
+DROP INDEX presentations_user_id_idx ON presentations;
+DROP INDEX users_name_unique ON users;
@@ -0,0 +1,62 @@
+-- Merge users sharing a name into the oldest of them, so that names can be made unique.
+UPDATE presentations p
+  JOIN users u ON u.id = p.user_id
+  JOIN (SELECT name, MIN(id) AS id FROM users GROUP BY name) k ON k.name = u.name
+  SET p.user_id = k.id;
+UPDATE questions q
+  JOIN users u ON u.id = q.user_id
+  JOIN (SELECT name, MIN(id) AS id FROM users GROUP BY name) k ON k.name = u.name
+  SET q.user_id = k.id;
+UPDATE options o
+  JOIN users u ON u.id = o.user_id
+  JOIN (SELECT name, MIN(id) AS id FROM users GROUP BY name) k ON k.name = u.name
+  SET o.user_id = k.id;
+UPDATE answers a
+  JOIN users u ON u.id = a.user_id
+  JOIN (SELECT name, MIN(id) AS id FROM users GROUP BY name) k ON k.name = u.name
+  SET a.user_id = k.id;
+DELETE u FROM users u
+  JOIN (SELECT name, MIN(id) AS id FROM users GROUP BY name) k ON k.name = u.name
+  WHERE u.id <> k.id;
+
+-- Remove the rows whose parent does not exist, from the top of the tree down.
+DELETE FROM presentations WHERE user_id NOT IN (SELECT id FROM users);
+DELETE FROM questions
+  WHERE presentation_id NOT IN (SELECT id FROM presentations)
+  OR user_id NOT IN (SELECT id FROM users);
+DELETE FROM options
+  WHERE question_id NOT IN (SELECT id FROM questions)
+  OR user_id NOT IN (SELECT id FROM users);
+DELETE FROM answers
+  WHERE option_id NOT IN (SELECT id FROM options)
+  OR user_id NOT IN (SELECT id FROM users);
+
+-- Indexes for the lookups done by the application.
+CREATE UNIQUE INDEX users_name_unique ON users (name);
+CREATE INDEX presentations_user_id_idx ON presentations (user_id);
+CREATE INDEX questions_presentation_id_created_idx ON questions (presentation_id, created);
+CREATE INDEX questions_user_id_idx ON questions (user_id);
+CREATE INDEX options_question_id_idx ON options (question_id);
+CREATE INDEX options_user_id_idx ON options (user_id);
+CREATE INDEX answers_option_id_idx ON answers (option_id);
+CREATE INDEX answers_user_id_idx ON answers (user_id);
+
+-- Deleting a row deletes everything that belongs to it.
+ALTER TABLE presentations
+  ADD CONSTRAINT presentations_user_id_fk
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+ALTER TABLE questions
+  ADD CONSTRAINT questions_presentation_id_fk
+  FOREIGN KEY (presentation_id) REFERENCES presentations (id) ON DELETE CASCADE,
+  ADD CONSTRAINT questions_user_id_fk
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+ALTER TABLE options
+  ADD CONSTRAINT options_question_id_fk
+  FOREIGN KEY (question_id) REFERENCES questions (id) ON DELETE CASCADE,
+  ADD CONSTRAINT options_user_id_fk
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+ALTER TABLE answers
+  ADD CONSTRAINT answers_option_id_fk
+  FOREIGN KEY (option_id) REFERENCES options (id) ON DELETE CASCADE,
+  ADD CONSTRAINT answers_user_id_fk
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
@@ -0,0 +1,9 @@
+CREATE TABLE presenter_accounts (
+  user_id INT PRIMARY KEY,
+  password_hash VARCHAR(255) NOT NULL,
+  failed_logins INT NOT NULL DEFAULT 0,
+  locked_until TIMESTAMP NULL,
+  created TIMESTAMP NOT NULL,
+  CONSTRAINT presenter_accounts_user_id_fk
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,13 @@
+CREATE TABLE api_tokens (
+  id INT PRIMARY KEY AUTO_INCREMENT,
+  user_id INT NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  token_hash VARCHAR(64) NOT NULL,
+  scopes VARCHAR(32) NOT NULL,
+  created TIMESTAMP NOT NULL,
+  last_used TIMESTAMP NULL,
+  CONSTRAINT api_tokens_user_id_fk
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+CREATE UNIQUE INDEX api_tokens_token_hash_unique ON api_tokens (token_hash);
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
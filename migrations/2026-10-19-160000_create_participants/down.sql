@@ -0,0 +1,5 @@
+DELETE FROM answers WHERE user_id IS NULL;
+ALTER TABLE answers DROP FOREIGN KEY answers_participant_id_fk;
+ALTER TABLE answers DROP COLUMN participant_id;
+ALTER TABLE answers MODIFY user_id INT NOT NULL;
+DROP TABLE participants;
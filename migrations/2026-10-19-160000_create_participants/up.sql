@@ -0,0 +1,14 @@
+CREATE TABLE participants (
+  id INT PRIMARY KEY AUTO_INCREMENT,
+  presentation_id INT NOT NULL,
+  created TIMESTAMP NOT NULL,
+  CONSTRAINT participants_presentation_id_fk
+  FOREIGN KEY (presentation_id) REFERENCES presentations (id) ON DELETE CASCADE
+);
+
+-- Answers are given either by a user or by an anonymous participant.
+ALTER TABLE answers MODIFY user_id INT NULL;
+ALTER TABLE answers
+  ADD COLUMN participant_id INT NULL,
+  ADD CONSTRAINT answers_participant_id_fk
+  FOREIGN KEY (participant_id) REFERENCES participants (id) ON DELETE CASCADE;
@@ -0,0 +1,11 @@
+CREATE TABLE sessions (
+  id INT PRIMARY KEY AUTO_INCREMENT,
+  user_id INT NOT NULL,
+  token_hash VARCHAR(64) NOT NULL,
+  created TIMESTAMP NOT NULL,
+  last_seen TIMESTAMP NOT NULL,
+  CONSTRAINT sessions_user_id_fk
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+CREATE UNIQUE INDEX sessions_token_hash_unique ON sessions (token_hash);
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
@@ -0,0 +1,4 @@
+ALTER TABLE questions
+  DROP COLUMN opened_at,
+  DROP COLUMN closes_at,
+  DROP COLUMN closed_at;
@@ -0,0 +1,9 @@
+-- A question takes answers from `opened_at` until it is closed, at `closes_at` when opened with a
+-- duration, or at `closed_at` by the presenter.
+ALTER TABLE questions
+  ADD COLUMN opened_at TIMESTAMP NULL,
+  ADD COLUMN closes_at TIMESTAMP NULL,
+  ADD COLUMN closed_at TIMESTAMP NULL;
+
+-- Questions created before voting windows keep taking answers until the presenter closes them.
+UPDATE questions SET opened_at = created;
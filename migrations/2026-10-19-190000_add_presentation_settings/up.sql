@@ -0,0 +1,2 @@
+-- Seconds a question of the presentation stays open for when it is opened without a duration.
+ALTER TABLE presentations ADD COLUMN voting_duration INT NULL;
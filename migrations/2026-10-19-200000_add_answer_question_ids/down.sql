@@ -0,0 +1,3 @@
+ALTER TABLE answers DROP INDEX answers_participant_id_question_id_idx;
+ALTER TABLE answers DROP FOREIGN KEY answers_question_id_fk;
+ALTER TABLE answers DROP COLUMN question_id;
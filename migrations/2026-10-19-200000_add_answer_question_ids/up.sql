@@ -0,0 +1,18 @@
+-- Answers carry the question of their option, so the database refuses a second answer of a
+-- participant to the same question, even from concurrent requests.
+ALTER TABLE answers ADD COLUMN question_id INT NULL;
+UPDATE answers INNER JOIN options ON options.id = answers.option_id
+  SET answers.question_id = options.question_id;
+
+-- Only the first answer of a participant to a question is kept.
+DELETE later FROM answers AS later
+  INNER JOIN answers AS earlier
+  ON earlier.participant_id = later.participant_id
+  AND earlier.question_id = later.question_id
+  AND earlier.id < later.id;
+
+ALTER TABLE answers
+  MODIFY question_id INT NOT NULL,
+  ADD CONSTRAINT answers_question_id_fk
+  FOREIGN KEY (question_id) REFERENCES questions (id) ON DELETE CASCADE,
+  ADD UNIQUE INDEX answers_participant_id_question_id_idx (participant_id, question_id);
@@ -0,0 +1,484 @@
+//! Presenter accounts, which log in with a password or an external identity provider.
+//!
+//! Audience members log in with just a name. Once a name is registered as a presenter account,
+//! or linked to an external identity, logging in with just the name is refused.
+
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::config::Config;
+use crate::models::{
+    ChangePasswordInput, FieldError, FieldErrors, NewPresenterAccount, NewUserIdentity,
+    PresenterAccount, RegisterInput, User, UserIdentity,
+};
+use crate::session::{
+    create_user, find_user_by_name, get_user_by_name, load_user_by_id, start_session,
+};
+use crate::validation::{validate, Validate, SHORT_TEXT};
+use crate::{DbConnection, DbPool};
+
+use actix_identity::Identity;
+use actix_web::error::BlockingError;
+use actix_web::http::header;
+use actix_web::web::{block, Data, Json};
+use actix_web::{post, Error, HttpResponse};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use rand::Rng;
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+const MAX_PASSWORD_LENGTH: usize = 1024;
+
+/// Number of failed logins in a row that lock an account.
+pub const MAX_FAILED_LOGINS: i32 = 5;
+
+/// How long a locked account refuses logins, in seconds.
+pub const LOCKOUT_SECONDS: i64 = 15 * 60;
+
+/// Result of a login attempt.
+pub enum LoginOutcome {
+    LoggedIn(User),
+    InvalidCredentials,
+    /// The account is locked after too many failed logins, until the given time.
+    Locked(NaiveDateTime),
+}
+
+/// Hashes a password with argon2id and a random salt, in the PHC string format.
+pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
+    let salt = rand::thread_rng().gen::<[u8; 16]>();
+    let config = argon2::Config {
+        variant: argon2::Variant::Argon2id,
+        mem_cost: 19 * 1024,
+        time_cost: 2,
+        ..argon2::Config::default()
+    };
+
+    argon2::hash_encoded(password.as_bytes(), &salt, &config)
+}
+
+/// Checks a password against a hash created by `hash_password`.
+pub fn verify_password(password_hash: &str, password: &str) -> bool {
+    argon2::verify_encoded(password_hash, password.as_bytes()).unwrap_or(false)
+}
+
+fn validate_password(path: &str, password: &str, errors: &mut Vec<FieldError>) {
+    let length = password.chars().count();
+
+    if length < MIN_PASSWORD_LENGTH {
+        errors.push(FieldError {
+            path: String::from(path),
+            message: format!("must be at least {} characters long", MIN_PASSWORD_LENGTH),
+        });
+    } else if password.len() > MAX_PASSWORD_LENGTH {
+        errors.push(FieldError {
+            path: String::from(path),
+            message: format!("must be at most {} bytes long", MAX_PASSWORD_LENGTH),
+        });
+    }
+}
+
+impl Validate for RegisterInput {
+    fn check(&self, errors: &mut Vec<FieldError>) {
+        SHORT_TEXT.check("name", &self.name, errors);
+        validate_password("password", &self.password, errors);
+    }
+}
+
+pub fn get_presenter_account(
+    uid: i32,
+    connection: &DbConnection,
+) -> Result<Option<PresenterAccount>, DieselError> {
+    use crate::schema::presenter_accounts::dsl::presenter_accounts;
+
+    presenter_accounts.find(uid).first(connection).optional()
+}
+
+/// Whether the user logs in through an external identity provider.
+pub fn has_linked_identity(uid: i32, connection: &DbConnection) -> Result<bool, DieselError> {
+    use crate::schema::user_identities::dsl::{user_id, user_identities};
+
+    user_identities
+        .filter(user_id.eq(uid))
+        .first::<UserIdentity>(connection)
+        .optional()
+        .map(|identity| identity.is_some())
+}
+
+/// Whether the user is a presenter, with a presenter account or an external identity.
+///
+/// Only presenters create presentations, audience members just answer.
+pub fn is_presenter(uid: i32, connection: &DbConnection) -> Result<bool, DieselError> {
+    if get_presenter_account(uid, connection)?.is_some() {
+        return Ok(true);
+    }
+
+    has_linked_identity(uid, connection)
+}
+
+/// Fails with 403 Forbidden unless the user is a presenter.
+pub async fn require_presenter_account(uid: i32, pool: &DbPool) -> Result<(), Error> {
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    let presenter = block(move || is_presenter(uid, &connection))
+        .await
+        .map_err(|_| HttpResponse::InternalServerError().body("Could not load the user."))?;
+
+    if presenter {
+        Ok(())
+    } else {
+        Err(HttpResponse::Forbidden()
+            .body("Only presenters can create presentations.")
+            .into())
+    }
+}
+
+/// Returns the user linked to an account at an external identity provider.
+///
+/// Without a link yet, the account is linked to `current_user` if that is a presenter, or to a new
+/// user named after `preferred_name` otherwise. A number is added to the name if it is taken.
+///
+/// Audience members log in with just a name, so anyone could log in as one of them. Linking their
+/// user would let that person take over the external account's login, and make them a presenter.
+pub fn link_identity(
+    provider: &str,
+    subject: &str,
+    preferred_name: &str,
+    current_user: Option<i32>,
+    connection: &DbConnection,
+) -> Result<User, DieselError> {
+    use crate::schema::user_identities::dsl::{
+        provider as identity_provider, subject as identity_subject, user_identities,
+    };
+
+    connection.transaction(|| {
+        let identity = user_identities
+            .filter(identity_provider.eq(provider))
+            .filter(identity_subject.eq(subject))
+            .first::<UserIdentity>(connection)
+            .optional()?;
+
+        if let Some(identity) = identity {
+            return load_user_by_id(identity.user_id, connection);
+        }
+
+        let user = match current_user {
+            Some(uid) if is_presenter(uid, connection)? => load_user_by_id(uid, connection)?,
+            _ => {
+                let mut name = preferred_name.to_owned();
+                let mut suffix = 1;
+                while find_user_by_name(&name, connection)?.is_some() {
+                    suffix += 1;
+                    name = format!("{} {}", preferred_name, suffix);
+                }
+
+                create_user(name, connection)?
+            }
+        };
+
+        diesel::insert_into(user_identities)
+            .values(&NewUserIdentity {
+                user_id: user.id,
+                provider: provider.to_owned(),
+                subject: subject.to_owned(),
+                created: Utc::now().naive_utc(),
+            })
+            .execute(connection)?;
+
+        Ok(user)
+    })
+}
+
+/// Creates a user with a presenter account.
+///
+/// Fails with a unique violation if the name is taken.
+pub fn register_presenter(
+    name: String,
+    password_hash: String,
+    connection: &DbConnection,
+) -> Result<User, DieselError> {
+    use crate::schema::presenter_accounts::dsl::presenter_accounts;
+
+    connection.transaction(|| {
+        let user = create_user(name, connection)?;
+        let account = NewPresenterAccount {
+            user_id: user.id,
+            password_hash,
+            created: Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(presenter_accounts)
+            .values(&account)
+            .execute(connection)?;
+
+        Ok(user)
+    })
+}
+
+/// Logs in by name.
+///
+/// Presenter accounts need their password, and are locked for `LOCKOUT_SECONDS` after
+/// `MAX_FAILED_LOGINS` wrong ones in a row. Users linked to an external identity can only log in
+/// through their provider. Any other name logs in as an audience member, who is created if
+/// needed, as long as no password is given.
+pub fn authenticate(
+    name: String,
+    password: Option<String>,
+    now: NaiveDateTime,
+    connection: &DbConnection,
+) -> Result<LoginOutcome, DieselError> {
+    use crate::schema::presenter_accounts::dsl::{failed_logins, locked_until, presenter_accounts};
+
+    let user = find_user_by_name(&name, connection)?;
+    let account = match &user {
+        Some(user) => get_presenter_account(user.id, connection)?,
+        None => None,
+    };
+
+    let (user, account) = match (user, account) {
+        (Some(user), Some(account)) => (user, account),
+        (Some(user), None) if has_linked_identity(user.id, connection)? => {
+            return Ok(LoginOutcome::InvalidCredentials);
+        }
+        _ => {
+            return match password {
+                Some(_) => Ok(LoginOutcome::InvalidCredentials),
+                None => get_user_by_name(name, connection).map(LoginOutcome::LoggedIn),
+            };
+        }
+    };
+
+    if let Some(until) = account.locked_until {
+        if until > now {
+            return Ok(LoginOutcome::Locked(until));
+        }
+    }
+
+    let valid = password.map_or(false, |password| {
+        verify_password(&account.password_hash, &password)
+    });
+
+    if valid {
+        diesel::update(presenter_accounts.find(user.id))
+            .set((failed_logins.eq(0), locked_until.eq(None::<NaiveDateTime>)))
+            .execute(connection)?;
+
+        return Ok(LoginOutcome::LoggedIn(user));
+    }
+
+    if account.failed_logins + 1 >= MAX_FAILED_LOGINS {
+        diesel::update(presenter_accounts.find(user.id))
+            .set((
+                failed_logins.eq(0),
+                locked_until.eq(now + Duration::seconds(LOCKOUT_SECONDS)),
+            ))
+            .execute(connection)?;
+    } else {
+        diesel::update(presenter_accounts.find(user.id))
+            .set(failed_logins.eq(failed_logins + 1))
+            .execute(connection)?;
+    }
+
+    Ok(LoginOutcome::InvalidCredentials)
+}
+
+/// Replaces the password of a presenter account, if `current_password` matches.
+///
+/// Returns whether the password was changed.
+pub fn change_password(
+    uid: i32,
+    current_password: &str,
+    new_password_hash: String,
+    connection: &DbConnection,
+) -> Result<bool, DieselError> {
+    use crate::schema::presenter_accounts::dsl::{password_hash, presenter_accounts};
+
+    let account = presenter_accounts
+        .find(uid)
+        .first::<PresenterAccount>(connection)?;
+
+    if !verify_password(&account.password_hash, current_password) {
+        return Ok(false);
+    }
+
+    diesel::update(presenter_accounts.find(uid))
+        .set(password_hash.eq(new_password_hash))
+        .execute(connection)?;
+
+    Ok(true)
+}
+
+/// Turns a login outcome into the response of `/login`, keeping the session started for a
+/// successful login in the auth cookie.
+pub fn login_response(
+    outcome: LoginOutcome,
+    session_token: Option<String>,
+    id: &Identity,
+) -> HttpResponse {
+    match outcome {
+        LoginOutcome::LoggedIn(user) => {
+            if let Some(token) = session_token {
+                id.remember(token);
+            }
+            HttpResponse::Ok().json(user)
+        }
+        LoginOutcome::InvalidCredentials => {
+            HttpResponse::Unauthorized().body("Invalid name or password.")
+        }
+        LoginOutcome::Locked(until) => {
+            let seconds = (until - Utc::now().naive_utc()).num_seconds().max(1);
+
+            HttpResponse::TooManyRequests()
+                .header(header::RETRY_AFTER, seconds.to_string())
+                .body("Too many failed logins, try again later.")
+        }
+    }
+}
+
+/// Registers a presenter account and logs it in.
+///
+/// `/register` POST
+///
+/// Headers:
+///
+/// Content type: application/json
+///
+/// Body:
+///
+/// ```json
+/// {
+///    "name": "agent 42",
+///    "password": "correct horse battery staple"
+/// }
+/// ```
+///
+/// Response:
+/// ```json
+/// {
+///     "id": 9,
+///     "name": "agent 42",
+///     "created": "2020-11-18T03:30:22"
+/// }
+/// ```
+///
+/// Response (name taken): 409 Conflict
+///
+/// Response (invalid input): 422 Unprocessable Entity
+/// ```json
+/// {
+///    "errors": [
+///        {
+///            "path": "password",
+///            "message": "must be at least 8 characters long"
+///        }
+///    ]
+/// }
+/// ```
+#[post("/register")]
+pub async fn register(
+    data: Json<RegisterInput>,
+    pool: Data<DbPool>,
+    config: Data<Config>,
+    id: Identity,
+) -> Result<HttpResponse, Error> {
+    let input = validate(data.into_inner())?;
+    let timeouts = config.session_timeouts;
+
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    let user = block(move || {
+        if find_user_by_name(&input.name, &connection)?.is_some() {
+            return Ok(None);
+        }
+
+        let password_hash = hash_password(&input.password).expect("Could not hash password.");
+
+        // Registering the same name concurrently fails on the unique index.
+        match register_presenter(input.name, password_hash, &connection) {
+            Ok(user) => {
+                let session_token = start_session(user.id, timeouts, &connection)?;
+                Ok(Some((user, session_token)))
+            }
+            Err(DieselError::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            )) => Ok(None),
+            Err(error) => Err(error),
+        }
+    })
+    .await
+    .map_err(|_: BlockingError<DieselError>| {
+        HttpResponse::InternalServerError().body("Could not register the account.")
+    })?;
+
+    match user {
+        Some((user, session_token)) => {
+            id.remember(session_token);
+            Ok(HttpResponse::Ok().json(user))
+        }
+        None => Ok(HttpResponse::Conflict().body("The name is already taken.")),
+    }
+}
+
+/// Changes the password of the logged in presenter.
+///
+/// `/change-password` POST
+///
+/// Headers:
+///
+/// Content type: application/json
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Body:
+///
+/// ```json
+/// {
+///    "current_password": "correct horse battery staple",
+///    "new_password": "battery staple correct horse"
+/// }
+/// ```
+///
+/// Response: 200 OK
+///
+/// Response (wrong current password): 403 Forbidden
+///
+/// Response (invalid new password): 422 Unprocessable Entity
+#[post("/change-password")]
+pub async fn post_change_password(
+    data: Json<ChangePasswordInput>,
+    pool: Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(Scope::Admin)?;
+
+    let input = data.into_inner();
+    let mut errors = vec![];
+
+    validate_password("new_password", &input.new_password, &mut errors);
+    if !errors.is_empty() {
+        return Ok(HttpResponse::UnprocessableEntity().json(FieldErrors { errors }));
+    }
+
+    let uid = user.id();
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    let changed = block(move || {
+        let password_hash = hash_password(&input.new_password).expect("Could not hash password.");
+
+        change_password(uid, &input.current_password, password_hash, &connection)
+    })
+    .await
+    .map_err(|error| match error {
+        BlockingError::Error(DieselError::NotFound) => {
+            HttpResponse::BadRequest().body("The user is not a presenter.")
+        }
+        _ => HttpResponse::InternalServerError().body("Could not change the password."),
+    })?;
+
+    if changed {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Forbidden().body("The current password is incorrect."))
+    }
+}
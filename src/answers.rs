@@ -1,31 +1,127 @@
-use crate::DbPool;
+use crate::{DbConnection, DbPool};
 use actix_web::Error;
 
-use crate::models::{Answer, AnswerInput, NewAnswer};
-use crate::session::load_user_by_id;
-use actix_identity::Identity;
+use crate::models::{Answer, AnswerInput, NewAnswer, Participant, Questions};
+use crate::parents::{attach_error_response, AttachError, Parent};
+use crate::participants::Voter;
+use crate::questions::is_open;
 use actix_web::web::{block, Data, Json, Path};
 use actix_web::HttpResponse;
 use actix_web::{get, post};
 use chrono::Utc;
 use diesel::prelude::*;
-use diesel::result::Error as DieselError;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 
+/// Fails unless the question of an option takes answers, and returns the id of the question.
+fn require_open_question(option_id: i32, connection: &DbConnection) -> Result<i32, AttachError> {
+    use crate::schema::{options, questions};
+
+    let question: Questions = options::table
+        .inner_join(questions::table)
+        .filter(options::id.eq(option_id))
+        .select(questions::all_columns)
+        .first(connection)?;
+
+    if is_open(&question, Utc::now().naive_utc()) {
+        Ok(question.id)
+    } else {
+        Err(AttachError::Closed(Parent::Question(question.id)))
+    }
+}
+
+/// Saves the answer of a user, who may answer the options of every presentation.
 pub fn new_answer(
     option_id: i32,
     user_id: i32,
-    connection: &MysqlConnection,
-) -> Result<(), DieselError> {
+    connection: &DbConnection,
+) -> Result<(), AttachError> {
     use crate::schema::answers::dsl::answers;
 
-    let new_answer = NewAnswer::new(user_id, Utc::now().naive_utc(), option_id);
+    connection.transaction(|| {
+        Parent::Option(option_id).require_exists(connection)?;
+        let question_id = require_open_question(option_id, connection)?;
+
+        let new_answer = NewAnswer::new(user_id, Utc::now().naive_utc(), option_id, question_id);
+
+        diesel::insert_into(answers)
+            .values(&new_answer)
+            .execute(connection)?;
+
+        Ok(())
+    })
+}
+
+/// Result of an answer given by an anonymous participant.
+#[derive(PartialEq, Debug)]
+pub enum ParticipantAnswer {
+    Created,
+    /// The participant already answered the question of the option.
+    AlreadyAnswered,
+}
+
+/// Saves the answer of an anonymous participant, who answers every question of their
+/// presentation once.
+///
+/// The unique index on the participant and question of answers refuses a second answer, even one
+/// inserted concurrently.
+pub fn new_participant_answer(
+    option_id: i32,
+    participant_id: i32,
+    connection: &DbConnection,
+) -> Result<ParticipantAnswer, AttachError> {
+    use crate::schema::{answers, participants};
+
+    let created = connection.transaction(|| {
+        let parent = Parent::Option(option_id);
+        let presentation_id = parent.require_exists(connection)?;
+
+        let participant = participants::table
+            .find(participant_id)
+            .first::<Participant>(connection)
+            .optional()?;
+        match participant {
+            Some(participant) if participant.presentation_id == presentation_id => (),
+            _ => return Err(AttachError::Forbidden(parent)),
+        }
+        let question_id = require_open_question(option_id, connection)?;
+
+        diesel::insert_into(answers::table)
+            .values(&NewAnswer::from_participant(
+                participant_id,
+                Utc::now().naive_utc(),
+                option_id,
+                question_id,
+            ))
+            .execute(connection)?;
+
+        Ok(())
+    });
+
+    match created {
+        Ok(()) => Ok(ParticipantAnswer::Created),
+        Err(AttachError::Database(DieselError::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            _,
+        ))) => Ok(ParticipantAnswer::AlreadyAnswered),
+        Err(error) => Err(error),
+    }
+}
+
+/// Deletes all answers given to the questions of a presentation and returns their number.
+pub fn delete_answers_by_presentation(
+    presentation_id: i32,
+    connection: &DbConnection,
+) -> Result<usize, DieselError> {
+    use crate::schema::{answers, options, questions};
 
-    diesel::insert_into(answers)
-        .values(&new_answer)
-        .execute(connection)
-        .expect("Error saving an answer");
+    let question_ids = questions::table
+        .filter(questions::presentation_id.eq(presentation_id))
+        .select(questions::id);
+    let option_ids = options::table
+        .filter(options::question_id.eq_any(question_ids))
+        .select(options::id);
 
-    Ok(())
+    diesel::delete(answers::table.filter(answers::option_id.eq_any(option_ids))).execute(connection)
 }
 
 /// `/answers` POST
@@ -38,6 +134,9 @@ pub fn new_answer(
 ///
 /// auth-cookie: <cookie_value>
 ///
+/// Anonymous participants send the `X-Device-Token` header from `/presentations/{id}/join`
+/// instead, and answer every question once.
+///
 /// Body:
 /// ```json
 /// {
@@ -46,40 +145,51 @@ pub fn new_answer(
 /// ```
 ///
 /// Response: 200 OK
+///
+/// Response (invalid device token): 401 Unauthorized
+///
+/// Response (option of another presentation than the participant's): 403 Forbidden
+///
+/// Response (unknown option): 404 Not Found
+///
+/// Response (the participant already answered the question, or the question is closed): 409
+/// Conflict
 #[post("/answers")]
 pub async fn post(
     pool: Data<DbPool>,
     data: Json<AnswerInput>,
-    id: Identity,
+    voter: Voter,
 ) -> Result<HttpResponse, Error> {
-    if let Some(id) = id.identity() {
-        let connection = pool.get().expect("Could not get database connection.");
-
-        let uid = id
-            .parse::<i32>()
-            .expect("Unable to convert user id to i32.");
+    let connection = pool.get().expect("couldn't get db connection from pool");
+    let input = data.into_inner();
 
-        let user = block(move || load_user_by_id(uid, &connection))
-            .await
-            .map_err(|_| {
-                HttpResponse::InternalServerError().body("Unable to retrieve user by id.")
-            })?;
+    match voter {
+        Voter::User(user) => {
+            let uid = user.id();
 
-        // TODO: Try not to retrieve the connection again.
-        let connection = pool.get().expect("couldn't get db connection from pool");
-        let input = data.into_inner();
+            block(move || new_answer(input.option_id, uid, &connection))
+                .await
+                .map_err(attach_error_response)?;
 
-        block(move || new_answer(input.option_id, user.id, &connection))
-            .await
-            .map_err(|_| HttpResponse::InternalServerError().finish())?;
+            Ok(HttpResponse::Ok().finish())
+        }
+        Voter::Participant(participant_id) => {
+            let outcome =
+                block(move || new_participant_answer(input.option_id, participant_id, &connection))
+                    .await
+                    .map_err(attach_error_response)?;
 
-        Ok(HttpResponse::Ok().finish())
-    } else {
-        Ok(HttpResponse::BadRequest().body("Could not identify the user."))
+            Ok(match outcome {
+                ParticipantAnswer::Created => HttpResponse::Ok().finish(),
+                ParticipantAnswer::AlreadyAnswered => {
+                    HttpResponse::Conflict().body("The question is already answered.")
+                }
+            })
+        }
     }
 }
 
-fn get_answer_by_id(answer_id: i32, connection: &MysqlConnection) -> Result<Answer, DieselError> {
+fn get_answer_by_id(answer_id: i32, connection: &DbConnection) -> Result<Answer, DieselError> {
     use crate::schema::answers::dsl::{answers, id};
 
     answers.filter(id.eq(answer_id)).first::<Answer>(connection)
@@ -110,7 +220,7 @@ pub async fn get(pool: Data<DbPool>, data: Path<i32>) -> Result<HttpResponse, Er
 
 fn get_answer_by_option_id(
     option_id: i32,
-    connection: &MysqlConnection,
+    connection: &DbConnection,
 ) -> Result<Vec<Answer>, DieselError> {
     use crate::schema::answers;
     use crate::schema::answers::dsl::option_id as schema_option_id;
@@ -0,0 +1,241 @@
+//! Personal API tokens, for scripts and integrations that can't keep the auth cookie.
+
+use crate::auth::{
+    format_scopes, generate_api_token, hash_api_token, parse_scopes, AuthenticatedUser, Scope,
+};
+use crate::models::{ApiToken, ApiTokenInput, FieldError, NewApiToken};
+use crate::validation::{validate, Validate, SHORT_TEXT};
+use crate::{DbConnection, DbPool};
+
+use actix_web::error::BlockingError;
+use actix_web::web::{block, Data, Json, Path};
+use actix_web::{get, post, Error, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use serde::Serialize;
+
+impl Validate for ApiTokenInput {
+    fn check(&self, errors: &mut Vec<FieldError>) {
+        SHORT_TEXT.check("name", &self.name, errors);
+
+        if self.scopes.is_empty() {
+            errors.push(FieldError {
+                path: String::from("scopes"),
+                message: String::from("must not be empty"),
+            });
+        }
+    }
+}
+
+/// An API token as returned to its owner. The token itself is only included on creation.
+#[derive(Serialize, Debug)]
+pub struct ApiTokenResponse {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created: NaiveDateTime,
+    pub last_used: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<ApiToken> for ApiTokenResponse {
+    fn from(api_token: ApiToken) -> Self {
+        ApiTokenResponse {
+            id: api_token.id,
+            scopes: parse_scopes(&api_token.scopes),
+            name: api_token.name,
+            created: api_token.created,
+            last_used: api_token.last_used,
+            token: None,
+        }
+    }
+}
+
+/// Creates an API token and returns it together with the token, which is not stored.
+pub fn create_api_token(
+    user_id: i32,
+    name: String,
+    scopes: &[Scope],
+    connection: &DbConnection,
+) -> Result<(ApiToken, String), DieselError> {
+    use crate::schema::api_tokens::dsl::api_tokens;
+
+    let token = generate_api_token();
+    let record = NewApiToken {
+        user_id,
+        name,
+        token_hash: hash_api_token(&token),
+        scopes: format_scopes(scopes),
+        created: Utc::now().naive_utc(),
+    };
+
+    let token_id = insert_returning_id!(api_tokens, &record, connection)?;
+    let api_token = api_tokens.find(token_id).first::<ApiToken>(connection)?;
+
+    Ok((api_token, token))
+}
+
+/// Returns the API tokens of a user, ordered by id.
+pub fn get_api_tokens(uid: i32, connection: &DbConnection) -> Result<Vec<ApiToken>, DieselError> {
+    use crate::schema::api_tokens::dsl::{api_tokens, id, user_id};
+
+    api_tokens
+        .filter(user_id.eq(uid))
+        .order(id.asc())
+        .load::<ApiToken>(connection)
+}
+
+/// Deletes an API token of a user, and returns whether it existed.
+pub fn revoke_api_token(
+    uid: i32,
+    token_id: i32,
+    connection: &DbConnection,
+) -> Result<bool, DieselError> {
+    use crate::schema::api_tokens::dsl::{api_tokens, id, user_id};
+
+    let deleted = diesel::delete(api_tokens.filter(id.eq(token_id)).filter(user_id.eq(uid)))
+        .execute(connection)?;
+
+    Ok(deleted > 0)
+}
+
+/// Creates an API token for the logged in user.
+///
+/// The token is only returned here, store it right away. Send it as
+/// `Authorization: Bearer <token>`.
+///
+/// `/api-tokens` POST
+///
+/// Headers:
+///
+/// Content type: application/json
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Body (`scopes` is any of `read`, `write` and `admin`):
+/// ```json
+/// {
+///    "name": "CI import",
+///    "scopes": ["write"]
+/// }
+/// ```
+///
+/// Response:
+/// ```json
+/// {
+///    "id": 3,
+///    "name": "CI import",
+///    "scopes": ["write"],
+///    "created": "2020-11-18T03:30:22",
+///    "last_used": null,
+///    "token": "qat_2bXq0P0tZ7tHc8GQwU3aV1lJmY9nR4sKdE6fB5hT"
+/// }
+/// ```
+///
+/// Response (API token without the `admin` scope): 403 Forbidden
+///
+/// Response (invalid input): 422 Unprocessable Entity
+#[post("/api-tokens")]
+pub async fn post(
+    data: Json<ApiTokenInput>,
+    pool: Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(Scope::Admin)?;
+
+    let input = validate(data.into_inner())?;
+
+    let connection = pool.get().expect("Unable to get database connection.");
+    let uid = user.id();
+
+    let (api_token, token) =
+        block(move || create_api_token(uid, input.name, &input.scopes, &connection))
+            .await
+            .map_err(|_| HttpResponse::InternalServerError().body("Could not create API token."))?;
+
+    let mut response = ApiTokenResponse::from(api_token);
+    response.token = Some(token);
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Lists the API tokens of the logged in user.
+///
+/// `/api-tokens` GET
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Response:
+/// ```json
+/// [
+///     {
+///        "id": 3,
+///        "name": "CI import",
+///        "scopes": ["write"],
+///        "created": "2020-11-18T03:30:22",
+///        "last_used": "2020-11-19T10:02:51"
+///     }
+/// ]
+/// ```
+///
+/// Response (API token without the `admin` scope): 403 Forbidden
+#[get("/api-tokens")]
+pub async fn get(pool: Data<DbPool>, user: AuthenticatedUser) -> Result<HttpResponse, Error> {
+    user.require_scope(Scope::Admin)?;
+
+    let connection = pool.get().expect("Unable to get database connection.");
+    let uid = user.id();
+
+    let tokens = block(move || get_api_tokens(uid, &connection))
+        .await
+        .map_err(|_| HttpResponse::InternalServerError().finish())?;
+
+    Ok(HttpResponse::Ok().json(
+        tokens
+            .into_iter()
+            .map(ApiTokenResponse::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Revokes an API token of the logged in user. Requests with the token fail from then on.
+///
+/// `/api-tokens/{id}/revoke` POST
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Response: 200 OK
+///
+/// Response (API token without the `admin` scope): 403 Forbidden
+///
+/// Response (no such token): 404 Not Found
+#[post("/api-tokens/{id}/revoke")]
+pub async fn revoke(
+    pool: Data<DbPool>,
+    data: Path<i32>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(Scope::Admin)?;
+
+    let connection = pool.get().expect("Unable to get database connection.");
+    let uid = user.id();
+    let token_id = data.into_inner();
+
+    let revoked = block(move || revoke_api_token(uid, token_id, &connection))
+        .await
+        .map_err(|_: BlockingError<DieselError>| HttpResponse::InternalServerError().finish())?;
+
+    if revoked {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().body("Could not find the API token."))
+    }
+}
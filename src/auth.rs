@@ -0,0 +1,262 @@
+//! Authentication of requests, by the auth cookie or by a personal API token.
+//!
+//! Handlers take an `AuthenticatedUser`, which accepts either. API tokens are sent as
+//! `Authorization: Bearer <token>` and only allow what their scopes cover, cookie sessions are
+//! allowed everything.
+
+use crate::config::Config;
+use crate::models::{ApiToken, User};
+use crate::session::{load_user_by_id, session_user_id};
+use crate::{DbConnection, DbPool};
+
+use actix_identity::RequestIdentity;
+use actix_web::dev::Payload;
+use actix_web::error::BlockingError;
+use actix_web::http::header;
+use actix_web::web::{block, Data};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// Prefix of API tokens, which makes leaked tokens easy to recognize.
+const API_TOKEN_PREFIX: &str = "qat_";
+
+/// Number of random characters of an API token.
+const API_TOKEN_LENGTH: usize = 40;
+
+/// What an API token may be used for. Every scope includes the ones before it, so a `write` token
+/// can also read.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Read data that requires a logged in user.
+    Read,
+    /// Create presentations, questions, options and answers.
+    Write,
+    /// Manage the account, including its API tokens.
+    Admin,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Write => write!(f, "write"),
+            Scope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(format!("unknown scope `{}`", input)),
+        }
+    }
+}
+
+/// Formats scopes the way they are stored, sorted and separated by commas.
+pub fn format_scopes(scopes: &[Scope]) -> String {
+    let mut scopes = scopes.to_vec();
+    scopes.sort();
+    scopes.dedup();
+
+    scopes
+        .iter()
+        .map(Scope::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parses stored scopes, ignoring unknown ones.
+pub fn parse_scopes(scopes: &str) -> Vec<Scope> {
+    scopes
+        .split(',')
+        .filter_map(|scope| scope.trim().parse().ok())
+        .collect()
+}
+
+/// Generates a new API token.
+pub fn generate_api_token() -> String {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_TOKEN_LENGTH)
+        .collect();
+
+    format!("{}{}", API_TOKEN_PREFIX, random)
+}
+
+/// Hashes an API or session token for storage, as lowercase hex SHA-256.
+///
+/// Tokens are long and random, so a fast unsalted hash is enough, and lets them be looked up by
+/// their hash.
+pub fn hash_api_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Returns the API token and its user, recording that the token was used.
+pub fn authenticate_api_token(
+    token: &str,
+    connection: &DbConnection,
+) -> Result<Option<(ApiToken, User)>, DieselError> {
+    use crate::schema::api_tokens::dsl::{api_tokens, last_used, token_hash};
+
+    let api_token = api_tokens
+        .filter(token_hash.eq(hash_api_token(token)))
+        .first::<ApiToken>(connection)
+        .optional()?;
+
+    match api_token {
+        Some(api_token) => {
+            diesel::update(api_tokens.find(api_token.id))
+                .set(last_used.eq(Utc::now().naive_utc()))
+                .execute(connection)?;
+            let user = load_user_by_id(api_token.user_id, connection)?;
+
+            Ok(Some((api_token, user)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// How a request was authenticated.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Credentials {
+    Cookie,
+    /// The id of the API token.
+    ApiToken(i32),
+}
+
+/// The user a request was made by, authenticated by the auth cookie or an API token.
+///
+/// Extracting it fails with 400 Bad Request without credentials or with an expired session, like
+/// the handlers did before API tokens, and with 401 Unauthorized for an unknown or revoked API
+/// token.
+pub struct AuthenticatedUser {
+    pub user: User,
+    pub credentials: Credentials,
+    /// Scopes of the API token, cookie sessions have every scope.
+    pub scopes: Vec<Scope>,
+}
+
+impl AuthenticatedUser {
+    pub fn id(&self) -> i32 {
+        self.user.id
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|granted| *granted >= scope)
+    }
+
+    /// Fails with 403 Forbidden unless the credentials have `scope`.
+    pub fn require_scope(&self, scope: Scope) -> Result<(), HttpResponse> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(HttpResponse::Forbidden()
+                .body(format!("The API token lacks the `{}` scope.", scope)))
+        }
+    }
+}
+
+/// Returns the token of an `Authorization: Bearer` header, if the request has one.
+pub fn bearer_token<R: HttpMessage>(request: &R) -> Option<String> {
+    let value = request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let mut parts = value.splitn(2, ' ');
+
+    match (parts.next(), parts.next()) {
+        (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => {
+            Some(token.trim().to_owned())
+        }
+        _ => None,
+    }
+}
+
+fn unidentified() -> Error {
+    HttpResponse::BadRequest()
+        .body("Could not identify the user.")
+        .into()
+}
+
+fn invalid_token() -> Error {
+    HttpResponse::Unauthorized()
+        .header(header::WWW_AUTHENTICATE, r#"Bearer error="invalid_token""#)
+        .body("Invalid API token.")
+        .into()
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
+    type Config = ();
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let pool = request.app_data::<Data<DbPool>>().cloned();
+        let timeouts = request
+            .app_data::<Data<Config>>()
+            .map(|config| config.session_timeouts)
+            .expect("The configuration is not registered.");
+        let token = bearer_token(request);
+        let identity = request.get_identity();
+
+        async move {
+            let pool = pool.expect("The database pool is not configured.");
+            if let Some(token) = token {
+                let connection = pool.get().expect("Unable to get database connection.");
+
+                let authenticated = block(move || authenticate_api_token(&token, &connection))
+                    .await
+                    .map_err(|_| HttpResponse::InternalServerError().finish())?;
+
+                return match authenticated {
+                    Some((api_token, user)) => Ok(AuthenticatedUser {
+                        user,
+                        credentials: Credentials::ApiToken(api_token.id),
+                        scopes: parse_scopes(&api_token.scopes),
+                    }),
+                    None => Err(invalid_token()),
+                };
+            }
+
+            let uid = session_user_id(identity, pool.get_ref(), timeouts)
+                .await?
+                .ok_or_else(unidentified)?;
+            let connection = pool.get().expect("Unable to get database connection.");
+
+            let user = block(move || load_user_by_id(uid, &connection))
+                .await
+                .map_err(|error| match error {
+                    BlockingError::Error(DieselError::NotFound) => unidentified(),
+                    _ => HttpResponse::InternalServerError().finish().into(),
+                })?;
+
+            Ok(AuthenticatedUser {
+                user,
+                credentials: Credentials::Cookie,
+                scopes: vec![Scope::Admin],
+            })
+        }
+        .boxed_local()
+    }
+}
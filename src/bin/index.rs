@@ -3,14 +3,20 @@ use actix_cors::Cors;
 use actix_web::cookie::SameSite;
 use actix_web::http::header;
 use actix_web::middleware::Logger;
+use actix_web::web::Data;
 use actix_web::App;
 use actix_web::HttpServer;
-use diesel::r2d2::{ConnectionManager, Pool};
-use diesel::MysqlConnection;
 use dotenv::dotenv;
+use questionnaire_rs::config::Config;
+use questionnaire_rs::csrf::CsrfProtection;
+use questionnaire_rs::identity::RotatingCookieIdentityPolicy;
+use questionnaire_rs::oidc::OidcProvider;
+use questionnaire_rs::participants::DeviceTokens;
+use questionnaire_rs::rate_limit::RateLimiter;
 use questionnaire_rs::*;
 
 use actix_identity::{CookieIdentityPolicy, IdentityService};
+use rand::Rng;
 use std::env;
 
 #[actix_rt::main]
@@ -18,43 +24,114 @@ async fn main() -> std::io::Result<()> {
     dotenv().ok();
     env_logger::init();
 
-    let server_address = env::var("ADDRESS").expect("Server ADDRESS must be set.");
-    let server_port = env::var("PORT").unwrap_or_else(|port| port.to_string());
-    let complete_address = format!("{}:{}", server_address, server_port);
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
-    let max_db_pool_size = env::var("MAX_DATABASE_POOL_SIZE")
-        .unwrap_or_else(|_error| String::from("10"))
-        .parse::<u32>()
-        .expect("Failed to convert max_db_pool_size to u32");
-    let manager = ConnectionManager::<MysqlConnection>::new(database_url);
-    let pool = Pool::builder()
-        .max_size(max_db_pool_size)
-        .build(manager)
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(error) => {
+            eprint!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let pool = db::build_pool(&config.database_url, config.max_database_pool_size)
         .expect("Failed to create pool.");
 
+    // Pending migrations are applied on startup, unless they are run separately. Either way the
+    // server does not start against a database migrated by a newer release.
+    let run_migrations =
+        config.run_migrations && !env::args().any(|arg| arg == "--skip-migrations");
+    {
+        let connection = pool.get().expect("Unable to get database connection.");
+        let result = if run_migrations {
+            migrations::run_pending_migrations(&connection, &mut std::io::stdout())
+        } else {
+            migrations::check_schema_version(&connection)
+        };
+
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+
+    let bind_address = config.bind_address();
+    // The browser drops the cookie when the session would end anyway, the sessions table decides
+    // whether it is still valid.
+    let cookie_max_age = i64::from(config.session_timeouts.absolute) * 60;
+
+    // Cookies are signed with the newest key, the older ones only verify cookies issued before
+    // the key was rotated.
+    let newest_cookie_key = match &config.cookie_secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => {
+            eprintln!("COOKIE_SECRET is not set, auth cookies will not survive a restart.");
+            rand::thread_rng().gen::<[u8; 32]>().to_vec()
+        }
+    };
+    let cookie_keys: Vec<Vec<u8>> = std::iter::once(newest_cookie_key)
+        .chain(
+            config
+                .old_cookie_secrets
+                .iter()
+                .map(|secret| secret.as_bytes().to_vec()),
+        )
+        .collect();
+
+    // Device tokens are signed with the cookie keys, so they rotate along with the cookies.
+    let device_tokens = Data::new(DeviceTokens::new(cookie_keys.clone()));
+    // Created once, so the workers share the request counts.
+    let rate_limiter = RateLimiter::new(
+        config.rate_limits,
+        config.trust_forwarded_for,
+        device_tokens.get_ref().clone(),
+    );
+    let csrf_protection = CsrfProtection::new(config.front_end_base_url.iter().cloned().collect());
+
+    // Shared by the workers, so the discovery document and the signing keys are loaded once.
+    let oidc = config
+        .oidc
+        .clone()
+        .map(|oidc| Data::new(OidcProvider::new(oidc)));
+
     HttpServer::new(move || {
-        let front_end_base_url = env::var("FRONT_END_BASE_URL").unwrap_or(String::from(""));
+        let mut cors = Cors::default()
+            .allowed_headers(vec![
+                header::AUTHORIZATION,
+                header::ACCEPT,
+                header::CONTENT_TYPE,
+                // `from_static` needs the lowercase form of `DEVICE_TOKEN_HEADER`.
+                header::HeaderName::from_static("x-device-token"),
+            ])
+            .allowed_methods(vec!["GET", "POST"])
+            .supports_credentials();
+        if let Some(origin) = &config.front_end_base_url {
+            cors = cors.allowed_origin(origin);
+        }
 
         App::new()
             .data(pool.clone())
+            .data(config.clone())
+            .data(csrf_protection.clone())
+            .app_data(device_tokens.clone())
+            .configure(|cfg| {
+                if let Some(provider) = &oidc {
+                    cfg.app_data(provider.clone());
+                }
+            })
+            .wrap(csrf_protection.clone())
+            .wrap(rate_limiter.clone())
             .wrap(Logger::default())
-            .wrap(IdentityService::new(
-                CookieIdentityPolicy::new(&[0; 32])
-                    .name("auth-cookie")
-                    .same_site(SameSite::None)
-                    .secure(true),
-            ))
-            .wrap(
-                Cors::default()
-                    .allowed_headers(vec![
-                        header::AUTHORIZATION,
-                        header::ACCEPT,
-                        header::CONTENT_TYPE,
-                    ])
-                    .allowed_methods(vec!["GET", "POST"])
-                    .allowed_origin(&front_end_base_url)
-                    .supports_credentials(),
-            )
+            .wrap(IdentityService::new(RotatingCookieIdentityPolicy::new(
+                cookie_keys
+                    .iter()
+                    .map(|key| {
+                        CookieIdentityPolicy::new(key)
+                            .name("auth-cookie")
+                            .same_site(SameSite::None)
+                            .secure(true)
+                            .max_age(cookie_max_age)
+                    })
+                    .collect(),
+            )))
+            .wrap(cors)
             .service(answers::post)
             .service(answers::get)
             .service(answers::get_by_option)
@@ -63,15 +140,33 @@ async fn main() -> std::io::Result<()> {
             .service(options::get_by_question)
             .service(presentations::post)
             .service(presentations::get)
+            .service(presentations::get_tree)
+            .service(participants::join)
+            .service(export::get)
+            .service(documents::get)
+            .service(documents::import)
+            .service(markdown::import)
             .service(questions::get)
             .service(questions::post)
+            .service(questions::post_with_options)
             .service(questions::get_by_presentation)
             .service(session::login)
+            .service(accounts::register)
+            .service(accounts::post_change_password)
+            .service(api_tokens::post)
+            .service(api_tokens::get)
+            .service(api_tokens::revoke)
+            .service(github::authorize)
+            .service(github::callback)
+            .service(oidc::authorize)
+            .service(oidc::callback)
             .service(session::logout)
+            .service(session::logout_everywhere)
             .service(session::is_logged_in)
+            .service(session::me)
             .service(web_socket::index)
     })
-    .bind(complete_address)
+    .bind(bind_address)
     .unwrap()
     .run()
     .await
@@ -0,0 +1,227 @@
+use dotenv::dotenv;
+use questionnaire_rs::config::Config;
+use questionnaire_rs::documents::{
+    export_presentation, import_presentation, parse_document, validate_document, DocumentFormat,
+};
+use questionnaire_rs::markdown::parse_markdown;
+use questionnaire_rs::*;
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use structopt::StructOpt;
+
+/// Presentation created by the `seed` command.
+const DEMO_PRESENTATION: &str = "# Demo Presentation
+
+## Which language is this backend written in?
+
+- [x] Rust
+- [ ] Go
+- [ ] Python
+
+## How did you hear about Questionnaire?
+
+- A friend
+- A conference
+- The internet
+";
+
+/// Operates the Questionnaire database.
+#[derive(StructOpt)]
+#[structopt(name = "questionnaire-admin")]
+enum Command {
+    /// Manages users.
+    Users(UsersCommand),
+    /// Makes another user the owner of a presentation, its questions and options.
+    Transfer { presentation_id: i32, user_id: i32 },
+    /// Deletes all answers of a presentation.
+    PurgeAnswers { presentation_id: i32 },
+    /// Applies the pending migrations.
+    Migrate {
+        /// Lists the pending migrations instead of applying them.
+        #[structopt(long)]
+        status: bool,
+    },
+    /// Creates a demo user with a demo presentation.
+    Seed {
+        #[structopt(long, default_value = "demo")]
+        user: String,
+    },
+    /// Writes the document of a presentation.
+    Export {
+        presentation_id: i32,
+        #[structopt(long, default_value = "json", possible_values = &["json", "yaml"])]
+        format: DocumentFormat,
+        /// Writes to a file instead of the standard output.
+        #[structopt(long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Creates a presentation from a document.
+    Import {
+        /// The owner of the new presentation.
+        user_id: i32,
+        /// Reads from the standard input if omitted.
+        #[structopt(parse(from_os_str))]
+        input: Option<PathBuf>,
+        #[structopt(long, default_value = "json", possible_values = &["json", "yaml"])]
+        format: DocumentFormat,
+    },
+}
+
+#[derive(StructOpt)]
+enum UsersCommand {
+    /// Lists all users.
+    List,
+    /// Creates a user.
+    Create { name: String },
+    /// Deletes a user, along with their presentations and answers.
+    Delete { user_id: i32 },
+    /// Logs a user out everywhere, ending all of their sessions.
+    Logout { user_id: i32 },
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    dotenv().ok();
+
+    let command = Command::from_args();
+    let database_url = Config::load_database_url().unwrap_or_else(|e| fail(e.to_string()));
+    // A pool of one, so the connection is set up the same way as the server's.
+    let pool = db::build_pool(&database_url, 1).expect("Failed to create pool.");
+    let connection = pool.get().expect("Unable to connect to the database.");
+
+    // Everything but `migrate` expects the schema the binary was built for.
+    if !matches!(command, Command::Migrate { .. }) {
+        migrations::check_schema_version(&connection).unwrap_or_else(|e| fail(e.to_string()));
+        let pending =
+            migrations::pending_versions(&connection).unwrap_or_else(|e| fail(e.to_string()));
+        if !pending.is_empty() {
+            fail(String::from(
+                "The database has pending migrations, run `questionnaire-admin migrate` first.",
+            ));
+        }
+    }
+
+    match command {
+        Command::Migrate { status: true } => {
+            let pending =
+                migrations::pending_versions(&connection).unwrap_or_else(|e| fail(e.to_string()));
+            for version in pending {
+                println!("{}", version);
+            }
+        }
+        Command::Migrate { status: false } => {
+            migrations::run_pending_migrations(&connection, &mut io::stdout())
+                .unwrap_or_else(|e| fail(e.to_string()));
+        }
+        Command::Users(UsersCommand::List) => {
+            let users = session::get_users(&connection).unwrap_or_else(|e| fail(e.to_string()));
+            for user in users {
+                println!("{}\t{}\t{}", user.id, user.name, user.created);
+            }
+        }
+        Command::Users(UsersCommand::Create { name }) => {
+            let user =
+                session::create_user(name, &connection).unwrap_or_else(|e| fail(e.to_string()));
+            println!("{}", user.id);
+        }
+        Command::Users(UsersCommand::Delete { user_id }) => {
+            let deleted =
+                session::delete_user(user_id, &connection).unwrap_or_else(|e| fail(e.to_string()));
+            if deleted == 0 {
+                fail(format!("Could not find user {}.", user_id));
+            }
+        }
+        Command::Users(UsersCommand::Logout { user_id }) => {
+            let ended = session::end_user_sessions(user_id, &connection)
+                .unwrap_or_else(|e| fail(e.to_string()));
+            println!("{}", ended);
+        }
+        Command::Transfer {
+            presentation_id,
+            user_id,
+        } => {
+            session::load_user_by_id(user_id, &connection)
+                .unwrap_or_else(|_| fail(format!("Could not find user {}.", user_id)));
+            presentations::get_presentation(presentation_id, &connection).unwrap_or_else(|_| {
+                fail(format!("Could not find presentation {}.", presentation_id))
+            });
+            presentations::transfer_presentation(presentation_id, user_id, &connection)
+                .unwrap_or_else(|e| fail(e.to_string()));
+        }
+        Command::PurgeAnswers { presentation_id } => {
+            let deleted = answers::delete_answers_by_presentation(presentation_id, &connection)
+                .unwrap_or_else(|e| fail(e.to_string()));
+            println!("Deleted {} answers.", deleted);
+        }
+        Command::Seed { user } => {
+            let document = parse_markdown(DEMO_PRESENTATION)
+                .expect("The demo presentation is not valid Markdown.");
+            let user = session::get_user_by_name(user, &connection)
+                .unwrap_or_else(|e| fail(e.to_string()));
+            let presentation_id = import_presentation(&document, user.id, &connection)
+                .unwrap_or_else(|e| fail(e.to_string()));
+            println!("{}", presentation_id);
+        }
+        Command::Export {
+            presentation_id,
+            format,
+            output,
+        } => {
+            let document = export_presentation(presentation_id, &connection).unwrap_or_else(|_| {
+                fail(format!("Could not find presentation {}.", presentation_id))
+            });
+            let body = match format {
+                DocumentFormat::Json => serde_json::to_string_pretty(&document)
+                    .expect("Could not serialize the document."),
+                DocumentFormat::Yaml => {
+                    serde_yaml::to_string(&document).expect("Could not serialize the document.")
+                }
+            };
+
+            match output {
+                Some(path) => fs::write(path, body).unwrap_or_else(|e| fail(e.to_string())),
+                None => writeln!(io::stdout(), "{}", body).unwrap_or_else(|e| fail(e.to_string())),
+            }
+        }
+        Command::Import {
+            user_id,
+            input,
+            format,
+        } => {
+            let body = match input {
+                Some(path) => fs::read_to_string(path).unwrap_or_else(|e| fail(e.to_string())),
+                None => {
+                    let mut body = String::new();
+                    io::stdin()
+                        .read_to_string(&mut body)
+                        .unwrap_or_else(|e| fail(e.to_string()));
+                    body
+                }
+            };
+
+            let document = parse_document(&body, format)
+                .unwrap_or_else(|e| fail(format!("{}: {}", e.path, e.message)));
+            let errors = validate_document(&document);
+            if !errors.is_empty() {
+                let messages: Vec<String> = errors
+                    .iter()
+                    .map(|e| format!("{}: {}", e.path, e.message))
+                    .collect();
+                fail(messages.join("\n"));
+            }
+
+            session::load_user_by_id(user_id, &connection)
+                .unwrap_or_else(|_| fail(format!("Could not find user {}.", user_id)));
+            let presentation_id = import_presentation(&document, user_id, &connection)
+                .unwrap_or_else(|e| fail(e.to_string()));
+            println!("{}", presentation_id);
+        }
+    }
+}
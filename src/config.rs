@@ -0,0 +1,944 @@
+//! Configuration of the server.
+//!
+//! Settings are read from an optional TOML file, named by the `CONFIG_FILE` environment variable,
+//! and from environment variables, which take precedence over the file. Everything is validated
+//! once on startup.
+
+use crate::models::FieldError;
+
+use actix_web::http::Uri;
+use serde::Deserialize;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+const DEFAULT_PORT: u16 = 8088;
+const DEFAULT_MAX_DATABASE_POOL_SIZE: u32 = 10;
+
+const DEFAULT_GITHUB_AUTHORIZE_URL: &str = "https://github.com/login/oauth/authorize";
+const DEFAULT_GITHUB_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+const DEFAULT_GITHUB_API_URL: &str = "https://api.github.com";
+
+const DEFAULT_OIDC_SCOPES: &str = "openid profile email";
+
+/// Login sessions end after two hours without a request, and a week after the login.
+const DEFAULT_SESSION_IDLE_TIMEOUT: u32 = 120;
+const DEFAULT_SESSION_ABSOLUTE_TIMEOUT: u32 = 7 * 24 * 60;
+
+const DEFAULT_RATE_LIMIT_AUTH: RateLimit = RateLimit {
+    requests: 10,
+    seconds: 60,
+};
+/// Generous, since a whole audience behind one address joins at once.
+const DEFAULT_RATE_LIMIT_JOIN: RateLimit = RateLimit {
+    requests: 300,
+    seconds: 60,
+};
+const DEFAULT_RATE_LIMIT_WRITE: RateLimit = RateLimit {
+    requests: 120,
+    seconds: 60,
+};
+const DEFAULT_RATE_LIMIT_READ: RateLimit = RateLimit {
+    requests: 600,
+    seconds: 60,
+};
+const DEFAULT_RATE_LIMIT_WEB_SOCKET: RateLimit = RateLimit {
+    requests: 10,
+    seconds: 1,
+};
+
+/// Minimum length of the cookie signing keys, in bytes.
+pub const MIN_COOKIE_SECRET_LENGTH: usize = 32;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Development,
+    Production,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::Development
+    }
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "development" => Ok(Environment::Development),
+            "production" => Ok(Environment::Production),
+            _ => Err(String::from("must be `development` or `production`")),
+        }
+    }
+}
+
+/// Settings of a configuration file, keyed by the lowercase environment variable names.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub database_url: Option<String>,
+    pub max_database_pool_size: Option<u32>,
+    pub front_end_base_url: Option<String>,
+    pub run_migrations: Option<bool>,
+    pub environment: Option<Environment>,
+    pub cookie_secret: Option<String>,
+    pub old_cookie_secrets: Option<Vec<String>>,
+    pub session_idle_timeout: Option<u32>,
+    pub session_absolute_timeout: Option<u32>,
+    pub rate_limit_auth: Option<String>,
+    pub rate_limit_join: Option<String>,
+    pub rate_limit_write: Option<String>,
+    pub rate_limit_read: Option<String>,
+    pub rate_limit_web_socket: Option<String>,
+    pub trust_forwarded_for: Option<bool>,
+    pub github_client_id: Option<String>,
+    pub github_client_secret: Option<String>,
+    pub github_authorize_url: Option<String>,
+    pub github_token_url: Option<String>,
+    pub github_api_url: Option<String>,
+    pub github_redirect_url: Option<String>,
+    pub oidc_issuer_url: Option<String>,
+    pub oidc_client_id: Option<String>,
+    pub oidc_client_secret: Option<String>,
+    pub oidc_redirect_url: Option<String>,
+    pub oidc_scopes: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub address: String,
+    pub port: u16,
+    pub database_url: String,
+    pub max_database_pool_size: u32,
+    /// Origin of the front end allowed to make credentialed cross origin requests.
+    pub front_end_base_url: Option<String>,
+    /// Whether pending migrations are applied on startup.
+    pub run_migrations: bool,
+    pub environment: Environment,
+    /// Key the auth cookies are signed with. Required in production, a random key is used
+    /// otherwise.
+    pub cookie_secret: Option<String>,
+    /// Keys of earlier releases, only used to verify cookies signed before a key rotation.
+    pub old_cookie_secrets: Vec<String>,
+    pub session_timeouts: SessionTimeouts,
+    pub rate_limits: RateLimits,
+    /// Whether anonymous clients are rate limited by the address in `X-Forwarded-For`, set by a
+    /// trusted reverse proxy, instead of the peer address.
+    pub trust_forwarded_for: bool,
+    /// Login with GitHub, enabled when the OAuth app credentials are set.
+    pub github: Option<GitHubConfig>,
+    /// Login with an OpenID Connect provider, enabled when the issuer and client are set.
+    pub oidc: Option<OidcConfig>,
+}
+
+/// Lifetime of login sessions, in minutes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SessionTimeouts {
+    /// A session ends when no request was made with it for this long.
+    pub idle: u32,
+    /// A session ends this long after the login, however active it is.
+    pub absolute: u32,
+}
+
+/// Token bucket limit: `requests` at once, refilled evenly over `seconds`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub seconds: u32,
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut parts = input.splitn(2, '/');
+        let requests = parts.next().and_then(|v| v.trim().parse::<u32>().ok());
+        let seconds = parts.next().and_then(|v| v.trim().parse::<u32>().ok());
+
+        match (requests, seconds) {
+            (Some(requests), Some(seconds)) if requests > 0 && seconds > 0 => {
+                Ok(RateLimit { requests, seconds })
+            }
+            _ => Err(String::from(
+                "must be `<requests>/<seconds>`, like `10/60`, or `off`",
+            )),
+        }
+    }
+}
+
+/// Rate limits of every route group, per client. `None` turns the limit off.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RateLimits {
+    /// Logging in and registering.
+    pub auth: Option<RateLimit>,
+    /// Joining presentations.
+    pub join: Option<RateLimit>,
+    /// Every other request changing data.
+    pub write: Option<RateLimit>,
+    pub read: Option<RateLimit>,
+    /// Messages of a web socket connection.
+    pub web_socket: Option<RateLimit>,
+}
+
+/// OAuth app of the GitHub login.
+#[derive(Clone)]
+pub struct GitHubConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    /// Endpoints of the provider. They point at GitHub, unless overridden to test against a mock
+    /// server.
+    pub authorize_url: String,
+    pub token_url: String,
+    pub api_url: String,
+    /// Callback URL sent to GitHub, which uses the one registered for the app if unset.
+    pub redirect_url: Option<String>,
+}
+
+/// Client registered at an OpenID Connect provider.
+#[derive(Clone)]
+pub struct OidcConfig {
+    /// Issuer identifier, the endpoints are discovered from its
+    /// `/.well-known/openid-configuration`.
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Callback URL sent to the provider, which must match one registered for the client.
+    pub redirect_url: String,
+    /// Space separated scopes requested, always including `openid`.
+    pub scopes: String,
+}
+
+/// Stands in for a secret in `Debug` output, so that logging the configuration does not leak the
+/// signing keys and client secrets.
+struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+fn redact_all(secrets: &[String]) -> Vec<Redacted> {
+    secrets.iter().map(|_| Redacted).collect()
+}
+
+impl fmt::Debug for ConfigFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConfigFile")
+            .field("address", &self.address)
+            .field("port", &self.port)
+            .field("database_url", &self.database_url)
+            .field("max_database_pool_size", &self.max_database_pool_size)
+            .field("front_end_base_url", &self.front_end_base_url)
+            .field("run_migrations", &self.run_migrations)
+            .field("environment", &self.environment)
+            .field(
+                "cookie_secret",
+                &self.cookie_secret.as_ref().map(|_| Redacted),
+            )
+            .field(
+                "old_cookie_secrets",
+                &self
+                    .old_cookie_secrets
+                    .as_ref()
+                    .map(|secrets| redact_all(secrets)),
+            )
+            .field("session_idle_timeout", &self.session_idle_timeout)
+            .field("session_absolute_timeout", &self.session_absolute_timeout)
+            .field("rate_limit_auth", &self.rate_limit_auth)
+            .field("rate_limit_join", &self.rate_limit_join)
+            .field("rate_limit_write", &self.rate_limit_write)
+            .field("rate_limit_read", &self.rate_limit_read)
+            .field("rate_limit_web_socket", &self.rate_limit_web_socket)
+            .field("trust_forwarded_for", &self.trust_forwarded_for)
+            .field("github_client_id", &self.github_client_id)
+            .field(
+                "github_client_secret",
+                &self.github_client_secret.as_ref().map(|_| Redacted),
+            )
+            .field("github_authorize_url", &self.github_authorize_url)
+            .field("github_token_url", &self.github_token_url)
+            .field("github_api_url", &self.github_api_url)
+            .field("github_redirect_url", &self.github_redirect_url)
+            .field("oidc_issuer_url", &self.oidc_issuer_url)
+            .field("oidc_client_id", &self.oidc_client_id)
+            .field(
+                "oidc_client_secret",
+                &self.oidc_client_secret.as_ref().map(|_| Redacted),
+            )
+            .field("oidc_redirect_url", &self.oidc_redirect_url)
+            .field("oidc_scopes", &self.oidc_scopes)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("address", &self.address)
+            .field("port", &self.port)
+            .field("database_url", &self.database_url)
+            .field("max_database_pool_size", &self.max_database_pool_size)
+            .field("front_end_base_url", &self.front_end_base_url)
+            .field("run_migrations", &self.run_migrations)
+            .field("environment", &self.environment)
+            .field(
+                "cookie_secret",
+                &self.cookie_secret.as_ref().map(|_| Redacted),
+            )
+            .field("old_cookie_secrets", &redact_all(&self.old_cookie_secrets))
+            .field("session_timeouts", &self.session_timeouts)
+            .field("rate_limits", &self.rate_limits)
+            .field("trust_forwarded_for", &self.trust_forwarded_for)
+            .field("github", &self.github)
+            .field("oidc", &self.oidc)
+            .finish()
+    }
+}
+
+impl fmt::Debug for GitHubConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GitHubConfig")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &Redacted)
+            .field("authorize_url", &self.authorize_url)
+            .field("token_url", &self.token_url)
+            .field("api_url", &self.api_url)
+            .field("redirect_url", &self.redirect_url)
+            .finish()
+    }
+}
+
+impl fmt::Debug for OidcConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OidcConfig")
+            .field("issuer_url", &self.issuer_url)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &Redacted)
+            .field("redirect_url", &self.redirect_url)
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+/// Every problem found in the configuration, the path of each error is the setting name.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub errors: Vec<FieldError>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for error in &self.errors {
+            writeln!(f, "  {}: {}", error.path, error.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Parses a boolean setting, accepting `true`/`false` and `1`/`0`.
+struct Flag(bool);
+
+impl FromStr for Flag {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "true" | "1" => Ok(Flag(true)),
+            "false" | "0" => Ok(Flag(false)),
+            _ => Err(String::from("must be `true` or `false`")),
+        }
+    }
+}
+
+/// Returns the value of a setting, from the environment if it is set there, otherwise from the
+/// file.
+fn setting<T, F>(
+    key: &str,
+    env_value: Option<String>,
+    file_value: Option<T>,
+    errors: &mut Vec<FieldError>,
+    parse: F,
+) -> Option<T>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    match env_value {
+        Some(value) => match parse(&value) {
+            Ok(value) => Some(value),
+            Err(message) => {
+                errors.push(FieldError {
+                    path: String::from(key),
+                    message,
+                });
+                None
+            }
+        },
+        None => file_value,
+    }
+}
+
+fn required<T>(key: &str, value: Option<T>, errors: &mut Vec<FieldError>) -> Option<T> {
+    if value.is_none() && !errors.iter().any(|error| error.path == key) {
+        errors.push(FieldError {
+            path: String::from(key),
+            message: String::from("must be set"),
+        });
+    }
+
+    value
+}
+
+fn parse_number<T>(input: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    input.parse::<T>().map_err(|error| error.to_string())
+}
+
+/// Parses a rate limit setting, which is `off` or a `RateLimit`.
+fn parse_rate_limit(input: &str) -> Result<Option<RateLimit>, String> {
+    match input.trim() {
+        "off" => Ok(None),
+        input => input.parse::<RateLimit>().map(Some),
+    }
+}
+
+fn validate_cookie_secret(key: &str, secret: &str, errors: &mut Vec<FieldError>) {
+    if secret.len() < MIN_COOKIE_SECRET_LENGTH {
+        errors.push(FieldError {
+            path: String::from(key),
+            message: format!("must be at least {} bytes long", MIN_COOKIE_SECRET_LENGTH),
+        });
+    }
+}
+
+fn validate_url(key: &str, url: &str, errors: &mut Vec<FieldError>) {
+    let valid = url.parse::<Uri>().ok().map_or(false, |uri| {
+        uri.authority().is_some()
+            && (uri.scheme_str() == Some("http") || uri.scheme_str() == Some("https"))
+    });
+
+    if !valid {
+        errors.push(FieldError {
+            path: String::from(key),
+            message: String::from("must be an http or https URL"),
+        });
+    }
+}
+
+fn validate_origin(origin: &str) -> Result<(), String> {
+    let uri = origin
+        .parse::<Uri>()
+        .map_err(|error| format!("is not a valid URL: {}", error))?;
+
+    let has_path = uri.path_and_query().map_or(false, |path| path != "/");
+    if uri.authority().is_none()
+        || !(uri.scheme_str() == Some("http") || uri.scheme_str() == Some("https"))
+        || has_path
+        || origin.ends_with('/')
+    {
+        return Err(String::from(
+            "must be an origin like `https://example.com`, without a path or trailing slash",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reads the file named by `CONFIG_FILE`, or no settings when it is not set.
+fn read_config_file() -> Result<ConfigFile, ConfigError> {
+    match env::var("CONFIG_FILE") {
+        Ok(path) => {
+            let contents = fs::read_to_string(&path).map_err(|error| ConfigError {
+                errors: vec![FieldError {
+                    path: String::from("CONFIG_FILE"),
+                    message: format!("could not read `{}`: {}", path, error),
+                }],
+            })?;
+
+            toml::from_str(&contents).map_err(|error| ConfigError {
+                errors: vec![FieldError {
+                    path: String::from("CONFIG_FILE"),
+                    message: format!("could not parse `{}`: {}", path, error),
+                }],
+            })
+        }
+        Err(_) => Ok(ConfigFile::default()),
+    }
+}
+
+impl Config {
+    /// Loads the configuration from `CONFIG_FILE`, if set, and the environment.
+    pub fn load() -> Result<Config, ConfigError> {
+        Self::from_sources(read_config_file()?, |key| env::var(key).ok())
+    }
+
+    /// Loads only `DATABASE_URL`, for tools that need nothing else of the server's settings.
+    pub fn load_database_url() -> Result<String, ConfigError> {
+        Self::database_url_from_sources(read_config_file()?, |key| env::var(key).ok())
+    }
+
+    /// Reads `DATABASE_URL` from the settings of a file and a lookup of environment variables.
+    pub fn database_url_from_sources<E>(file: ConfigFile, env_var: E) -> Result<String, ConfigError>
+    where
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut errors = vec![];
+
+        let database_url = setting(
+            "DATABASE_URL",
+            env_var("DATABASE_URL"),
+            file.database_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let database_url = required(
+            "DATABASE_URL",
+            database_url.filter(|v| !v.is_empty()),
+            &mut errors,
+        );
+
+        match database_url {
+            Some(database_url) if errors.is_empty() => Ok(database_url),
+            _ => Err(ConfigError { errors }),
+        }
+    }
+
+    /// Builds the configuration from the settings of a file and a lookup of environment
+    /// variables, reporting every invalid setting at once.
+    pub fn from_sources<E>(file: ConfigFile, env_var: E) -> Result<Config, ConfigError>
+    where
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut errors = vec![];
+
+        let address = setting(
+            "ADDRESS",
+            env_var("ADDRESS"),
+            file.address,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let port = setting(
+            "PORT",
+            env_var("PORT"),
+            file.port,
+            &mut errors,
+            parse_number::<u16>,
+        );
+        let database_url = setting(
+            "DATABASE_URL",
+            env_var("DATABASE_URL"),
+            file.database_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let max_database_pool_size = setting(
+            "MAX_DATABASE_POOL_SIZE",
+            env_var("MAX_DATABASE_POOL_SIZE"),
+            file.max_database_pool_size,
+            &mut errors,
+            parse_number::<u32>,
+        );
+        let front_end_base_url = setting(
+            "FRONT_END_BASE_URL",
+            env_var("FRONT_END_BASE_URL"),
+            file.front_end_base_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let run_migrations = setting(
+            "RUN_MIGRATIONS",
+            env_var("RUN_MIGRATIONS"),
+            file.run_migrations,
+            &mut errors,
+            |v| v.parse::<Flag>().map(|flag| flag.0),
+        );
+        let environment = setting(
+            "ENVIRONMENT",
+            env_var("ENVIRONMENT"),
+            file.environment,
+            &mut errors,
+            |v| v.parse::<Environment>(),
+        );
+        let cookie_secret = setting(
+            "COOKIE_SECRET",
+            env_var("COOKIE_SECRET"),
+            file.cookie_secret,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        // Several old keys are separated by commas in the environment.
+        let old_cookie_secrets = setting(
+            "OLD_COOKIE_SECRETS",
+            env_var("OLD_COOKIE_SECRETS"),
+            file.old_cookie_secrets,
+            &mut errors,
+            |v| {
+                Ok(v.split(',')
+                    .map(str::trim)
+                    .filter(|secret| !secret.is_empty())
+                    .map(String::from)
+                    .collect())
+            },
+        );
+        let session_idle_timeout = setting(
+            "SESSION_IDLE_TIMEOUT",
+            env_var("SESSION_IDLE_TIMEOUT"),
+            file.session_idle_timeout,
+            &mut errors,
+            parse_number::<u32>,
+        );
+        let session_absolute_timeout = setting(
+            "SESSION_ABSOLUTE_TIMEOUT",
+            env_var("SESSION_ABSOLUTE_TIMEOUT"),
+            file.session_absolute_timeout,
+            &mut errors,
+            parse_number::<u32>,
+        );
+        let rate_limit_auth = setting(
+            "RATE_LIMIT_AUTH",
+            env_var("RATE_LIMIT_AUTH"),
+            file.rate_limit_auth,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let rate_limit_join = setting(
+            "RATE_LIMIT_JOIN",
+            env_var("RATE_LIMIT_JOIN"),
+            file.rate_limit_join,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let rate_limit_write = setting(
+            "RATE_LIMIT_WRITE",
+            env_var("RATE_LIMIT_WRITE"),
+            file.rate_limit_write,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let rate_limit_read = setting(
+            "RATE_LIMIT_READ",
+            env_var("RATE_LIMIT_READ"),
+            file.rate_limit_read,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let rate_limit_web_socket = setting(
+            "RATE_LIMIT_WEB_SOCKET",
+            env_var("RATE_LIMIT_WEB_SOCKET"),
+            file.rate_limit_web_socket,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let trust_forwarded_for = setting(
+            "TRUST_FORWARDED_FOR",
+            env_var("TRUST_FORWARDED_FOR"),
+            file.trust_forwarded_for,
+            &mut errors,
+            |v| v.parse::<Flag>().map(|flag| flag.0),
+        );
+        let github_client_id = setting(
+            "GITHUB_CLIENT_ID",
+            env_var("GITHUB_CLIENT_ID"),
+            file.github_client_id,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let github_client_secret = setting(
+            "GITHUB_CLIENT_SECRET",
+            env_var("GITHUB_CLIENT_SECRET"),
+            file.github_client_secret,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let github_authorize_url = setting(
+            "GITHUB_AUTHORIZE_URL",
+            env_var("GITHUB_AUTHORIZE_URL"),
+            file.github_authorize_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let github_token_url = setting(
+            "GITHUB_TOKEN_URL",
+            env_var("GITHUB_TOKEN_URL"),
+            file.github_token_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let github_api_url = setting(
+            "GITHUB_API_URL",
+            env_var("GITHUB_API_URL"),
+            file.github_api_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let github_redirect_url = setting(
+            "GITHUB_REDIRECT_URL",
+            env_var("GITHUB_REDIRECT_URL"),
+            file.github_redirect_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let oidc_issuer_url = setting(
+            "OIDC_ISSUER_URL",
+            env_var("OIDC_ISSUER_URL"),
+            file.oidc_issuer_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let oidc_client_id = setting(
+            "OIDC_CLIENT_ID",
+            env_var("OIDC_CLIENT_ID"),
+            file.oidc_client_id,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let oidc_client_secret = setting(
+            "OIDC_CLIENT_SECRET",
+            env_var("OIDC_CLIENT_SECRET"),
+            file.oidc_client_secret,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let oidc_redirect_url = setting(
+            "OIDC_REDIRECT_URL",
+            env_var("OIDC_REDIRECT_URL"),
+            file.oidc_redirect_url,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+        let oidc_scopes = setting(
+            "OIDC_SCOPES",
+            env_var("OIDC_SCOPES"),
+            file.oidc_scopes,
+            &mut errors,
+            |v| Ok(v.to_owned()),
+        );
+
+        let address = required("ADDRESS", address.filter(|v| !v.is_empty()), &mut errors);
+        let database_url = required(
+            "DATABASE_URL",
+            database_url.filter(|v| !v.is_empty()),
+            &mut errors,
+        );
+        let port = port.unwrap_or(DEFAULT_PORT);
+        let max_database_pool_size =
+            max_database_pool_size.unwrap_or(DEFAULT_MAX_DATABASE_POOL_SIZE);
+        let front_end_base_url = front_end_base_url.filter(|v| !v.is_empty());
+        let environment = environment.unwrap_or_default();
+        let cookie_secret = cookie_secret.filter(|v| !v.is_empty());
+        let old_cookie_secrets = old_cookie_secrets.unwrap_or_default();
+        let session_timeouts = SessionTimeouts {
+            idle: session_idle_timeout.unwrap_or(DEFAULT_SESSION_IDLE_TIMEOUT),
+            absolute: session_absolute_timeout.unwrap_or(DEFAULT_SESSION_ABSOLUTE_TIMEOUT),
+        };
+
+        if port == 0 {
+            errors.push(FieldError {
+                path: String::from("PORT"),
+                message: String::from("must be between 1 and 65535"),
+            });
+        }
+
+        if max_database_pool_size == 0 {
+            errors.push(FieldError {
+                path: String::from("MAX_DATABASE_POOL_SIZE"),
+                message: String::from("must be at least 1"),
+            });
+        }
+
+        if let Some(origin) = &front_end_base_url {
+            if let Err(message) = validate_origin(origin) {
+                errors.push(FieldError {
+                    path: String::from("FRONT_END_BASE_URL"),
+                    message,
+                });
+            }
+        }
+
+        match &cookie_secret {
+            Some(secret) => validate_cookie_secret("COOKIE_SECRET", secret, &mut errors),
+            None if environment == Environment::Production => errors.push(FieldError {
+                path: String::from("COOKIE_SECRET"),
+                message: String::from("must be set in production"),
+            }),
+            None => {}
+        }
+
+        for secret in &old_cookie_secrets {
+            validate_cookie_secret("OLD_COOKIE_SECRETS", secret, &mut errors);
+        }
+
+        let mut rate_limit = |key: &str, value: Option<String>, default: RateLimit| match value {
+            Some(value) => parse_rate_limit(&value).unwrap_or_else(|message| {
+                errors.push(FieldError {
+                    path: String::from(key),
+                    message,
+                });
+                None
+            }),
+            None => Some(default),
+        };
+        let rate_limits = RateLimits {
+            auth: rate_limit("RATE_LIMIT_AUTH", rate_limit_auth, DEFAULT_RATE_LIMIT_AUTH),
+            join: rate_limit("RATE_LIMIT_JOIN", rate_limit_join, DEFAULT_RATE_LIMIT_JOIN),
+            write: rate_limit(
+                "RATE_LIMIT_WRITE",
+                rate_limit_write,
+                DEFAULT_RATE_LIMIT_WRITE,
+            ),
+            read: rate_limit("RATE_LIMIT_READ", rate_limit_read, DEFAULT_RATE_LIMIT_READ),
+            web_socket: rate_limit(
+                "RATE_LIMIT_WEB_SOCKET",
+                rate_limit_web_socket,
+                DEFAULT_RATE_LIMIT_WEB_SOCKET,
+            ),
+        };
+
+        if session_timeouts.idle == 0 {
+            errors.push(FieldError {
+                path: String::from("SESSION_IDLE_TIMEOUT"),
+                message: String::from("must be at least 1"),
+            });
+        }
+
+        if session_timeouts.absolute < session_timeouts.idle {
+            errors.push(FieldError {
+                path: String::from("SESSION_ABSOLUTE_TIMEOUT"),
+                message: String::from("must be at least SESSION_IDLE_TIMEOUT"),
+            });
+        }
+
+        let github = match (
+            github_client_id.filter(|v| !v.is_empty()),
+            github_client_secret.filter(|v| !v.is_empty()),
+        ) {
+            (Some(client_id), Some(client_secret)) => {
+                let github = GitHubConfig {
+                    client_id,
+                    client_secret,
+                    authorize_url: github_authorize_url
+                        .unwrap_or_else(|| String::from(DEFAULT_GITHUB_AUTHORIZE_URL)),
+                    token_url: github_token_url
+                        .unwrap_or_else(|| String::from(DEFAULT_GITHUB_TOKEN_URL)),
+                    api_url: github_api_url
+                        .unwrap_or_else(|| String::from(DEFAULT_GITHUB_API_URL))
+                        .trim_end_matches('/')
+                        .to_owned(),
+                    redirect_url: github_redirect_url.filter(|v| !v.is_empty()),
+                };
+
+                validate_url("GITHUB_AUTHORIZE_URL", &github.authorize_url, &mut errors);
+                validate_url("GITHUB_TOKEN_URL", &github.token_url, &mut errors);
+                validate_url("GITHUB_API_URL", &github.api_url, &mut errors);
+                if let Some(url) = &github.redirect_url {
+                    validate_url("GITHUB_REDIRECT_URL", url, &mut errors);
+                }
+
+                Some(github)
+            }
+            (None, None) => None,
+            (Some(_), None) => {
+                errors.push(FieldError {
+                    path: String::from("GITHUB_CLIENT_SECRET"),
+                    message: String::from("must be set together with GITHUB_CLIENT_ID"),
+                });
+                None
+            }
+            (None, Some(_)) => {
+                errors.push(FieldError {
+                    path: String::from("GITHUB_CLIENT_ID"),
+                    message: String::from("must be set together with GITHUB_CLIENT_SECRET"),
+                });
+                None
+            }
+        };
+
+        let oidc = match oidc_issuer_url.filter(|v| !v.is_empty()) {
+            Some(issuer_url) => {
+                let client_id = required(
+                    "OIDC_CLIENT_ID",
+                    oidc_client_id.filter(|v| !v.is_empty()),
+                    &mut errors,
+                );
+                let client_secret = required(
+                    "OIDC_CLIENT_SECRET",
+                    oidc_client_secret.filter(|v| !v.is_empty()),
+                    &mut errors,
+                );
+                let redirect_url = required(
+                    "OIDC_REDIRECT_URL",
+                    oidc_redirect_url.filter(|v| !v.is_empty()),
+                    &mut errors,
+                );
+                let scopes = oidc_scopes
+                    .filter(|v| !v.trim().is_empty())
+                    .unwrap_or_else(|| String::from(DEFAULT_OIDC_SCOPES));
+
+                validate_url("OIDC_ISSUER_URL", &issuer_url, &mut errors);
+                if let Some(url) = &redirect_url {
+                    validate_url("OIDC_REDIRECT_URL", url, &mut errors);
+                }
+                if !scopes.split_whitespace().any(|scope| scope == "openid") {
+                    errors.push(FieldError {
+                        path: String::from("OIDC_SCOPES"),
+                        message: String::from("must include `openid`"),
+                    });
+                }
+
+                match (client_id, client_secret, redirect_url) {
+                    (Some(client_id), Some(client_secret), Some(redirect_url)) => {
+                        Some(OidcConfig {
+                            issuer_url: issuer_url.trim_end_matches('/').to_owned(),
+                            client_id,
+                            client_secret,
+                            redirect_url,
+                            scopes,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            None => None,
+        };
+
+        match (address, database_url) {
+            (Some(address), Some(database_url)) if errors.is_empty() => Ok(Config {
+                address,
+                port,
+                database_url,
+                max_database_pool_size,
+                front_end_base_url,
+                run_migrations: run_migrations.unwrap_or(true),
+                environment,
+                cookie_secret,
+                old_cookie_secrets,
+                session_timeouts,
+                rate_limits,
+                trust_forwarded_for: trust_forwarded_for.unwrap_or(false),
+                github,
+                oidc,
+            }),
+            _ => Err(ConfigError { errors }),
+        }
+    }
+
+    /// The address the server binds to.
+    pub fn bind_address(&self) -> String {
+        format!("{}:{}", self.address, self.port)
+    }
+}
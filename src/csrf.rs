@@ -0,0 +1,140 @@
+//! Protection against cross-site request forgery.
+//!
+//! The auth cookie is sent with cross-site requests, so requests changing data must come from the
+//! front end or the backend itself, as told by their `Origin`, or `Referer`, header. Requests
+//! authenticated by an API token, and answers given with a device token, are exempt, since
+//! browsers don't send those headers on their own.
+
+use crate::auth::bearer_token;
+use crate::participants::DEVICE_TOKEN_HEADER;
+
+use actix_service::{Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, HeaderMap, Method, Uri};
+use actix_web::{Error, HttpRequest, HttpResponse};
+use futures::future::{ok, Either, Ready};
+use std::task::{Context, Poll};
+
+/// The only route authenticating by device token. Elsewhere the device token is ignored and the
+/// auth cookie used, so it must not exempt the request.
+const DEVICE_TOKEN_PATH: &str = "/answers";
+
+/// Returns the `scheme://host[:port]` origin of a URL, if it has one.
+fn origin_of(url: &str) -> Option<String> {
+    let uri = url.parse::<Uri>().ok()?;
+
+    Some(format!("{}://{}", uri.scheme_str()?, uri.authority()?))
+}
+
+/// Middleware refusing state-changing requests from other sites with 403 Forbidden.
+///
+/// Requests without `Origin` and `Referer` are let through, browsers send one of them with every
+/// cross-site request, so those come from other clients.
+#[derive(Clone)]
+pub struct CsrfProtection {
+    /// Origins besides the backend's own allowed to make requests, like the front end.
+    trusted_origins: Vec<String>,
+}
+
+impl CsrfProtection {
+    pub fn new(trusted_origins: Vec<String>) -> Self {
+        CsrfProtection { trusted_origins }
+    }
+
+    /// Whether the request may go ahead.
+    pub fn allows(&self, request: &ServiceRequest) -> bool {
+        let method = request.method();
+        if method == Method::GET || method == Method::HEAD || method == Method::OPTIONS {
+            return true;
+        }
+
+        if bearer_token(request).is_some() {
+            return true;
+        }
+        if request.path() == DEVICE_TOKEN_PATH
+            && request.headers().contains_key(DEVICE_TOKEN_HEADER)
+        {
+            return true;
+        }
+
+        self.trusts(request.headers(), request.connection_info().host())
+    }
+
+    /// Whether the request comes from a trusted origin or the backend itself, as told by its
+    /// `Origin`, or `Referer`, header. Requests with neither come from other clients than
+    /// browsers, and are trusted.
+    pub fn trusts_origin(&self, request: &HttpRequest) -> bool {
+        self.trusts(request.headers(), request.connection_info().host())
+    }
+
+    fn trusts(&self, headers: &HeaderMap, own_host: &str) -> bool {
+        let header_value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        let origin = match (header_value(header::ORIGIN), header_value(header::REFERER)) {
+            (Some(origin), _) => origin_of(origin),
+            (None, Some(referer)) => origin_of(referer),
+            (None, None) => return true,
+        };
+
+        match origin {
+            Some(origin) => {
+                // Same origin requests, compared by host since a proxy may terminate TLS.
+                self.trusted_origins.contains(&origin)
+                    || origin.ends_with(&format!("://{}", own_host))
+            }
+            // `Origin: null`, sent from sandboxed documents among others.
+            None => false,
+        }
+    }
+}
+
+impl<S, B> Transform<S> for CsrfProtection
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CsrfMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(CsrfMiddleware {
+            service,
+            protection: self.clone(),
+        })
+    }
+}
+
+pub struct CsrfMiddleware<S> {
+    service: S,
+    protection: CsrfProtection,
+}
+
+impl<S, B> Service for CsrfMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: ServiceRequest) -> Self::Future {
+        if self.protection.allows(&request) {
+            Either::Left(self.service.call(request))
+        } else {
+            let response = HttpResponse::Forbidden().body("Cross-site request refused.");
+
+            Either::Right(ok(request.into_response(response.into_body())))
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Selects the database backend the application is compiled for.
+//!
+//! MySQL is used by default. Building with `--no-default-features --features sqlite` runs the same
+//! handlers against a single file SQLite database, and `--features postgres` against PostgreSQL.
+
+use diesel::r2d2::{ConnectionManager, Pool, PoolError};
+
+#[cfg(any(
+    all(feature = "mysql", feature = "sqlite"),
+    all(feature = "mysql", feature = "postgres"),
+    all(feature = "sqlite", feature = "postgres")
+))]
+compile_error!("Only one of the `mysql`, `sqlite` and `postgres` features can be enabled.");
+
+#[cfg(not(any(feature = "mysql", feature = "sqlite", feature = "postgres")))]
+compile_error!("One of the `mysql`, `sqlite` or `postgres` features must be enabled.");
+
+#[cfg(feature = "mysql")]
+pub type DbConnection = diesel::mysql::MysqlConnection;
+
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::sqlite::SqliteConnection;
+
+#[cfg(feature = "postgres")]
+pub type DbConnection = diesel::pg::PgConnection;
+
+pub type DbPool = Pool<ConnectionManager<DbConnection>>;
+
+#[cfg(feature = "mysql")]
+no_arg_sql_function!(
+    last_insert_id,
+    diesel::sql_types::Integer,
+    "Represents the `LAST_INSERT_ID()` function, the id generated by the last insert statement."
+);
+
+#[cfg(feature = "sqlite")]
+no_arg_sql_function!(
+    last_insert_rowid,
+    diesel::sql_types::Integer,
+    "Represents the `last_insert_rowid()` function, the id generated by the last insert statement."
+);
+
+#[cfg(feature = "sqlite")]
+pub use self::last_insert_rowid as last_insert_id;
+
+/// Inserts `$values` into the `$table` schema table and returns the id of the new row.
+///
+/// PostgreSQL returns the id with the insert statement itself, the other backends look it up on
+/// the same connection right after the insert.
+#[cfg(feature = "postgres")]
+macro_rules! insert_returning_id {
+    ($table:ident, $values:expr, $connection:expr) => {
+        diesel::insert_into(crate::schema::$table::table)
+            .values($values)
+            .returning(crate::schema::$table::id)
+            .get_result::<i32>($connection)
+    };
+}
+
+/// Inserts `$values` into the `$table` schema table and returns the id of the new row.
+#[cfg(not(feature = "postgres"))]
+macro_rules! insert_returning_id {
+    ($table:ident, $values:expr, $connection:expr) => {
+        diesel::insert_into(crate::schema::$table::table)
+            .values($values)
+            .execute($connection)
+            .and_then(|_| diesel::select(crate::db::last_insert_id).first::<i32>($connection))
+    };
+}
+
+/// Turns on the SQLite settings the application relies on, for every pooled connection.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+struct SqliteConnectionCustomizer;
+
+#[cfg(feature = "sqlite")]
+impl diesel::r2d2::CustomizeConnection<DbConnection, diesel::r2d2::Error>
+    for SqliteConnectionCustomizer
+{
+    fn on_acquire(&self, connection: &mut DbConnection) -> Result<(), diesel::r2d2::Error> {
+        use diesel::connection::SimpleConnection;
+
+        // Foreign keys are off by default, and concurrent writers should wait instead of failing.
+        connection
+            .batch_execute("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;")
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// Creates the database connection pool.
+pub fn build_pool(database_url: &str, max_size: u32) -> Result<DbPool, PoolError> {
+    let manager = ConnectionManager::<DbConnection>::new(database_url);
+    let builder = Pool::builder().max_size(max_size);
+
+    #[cfg(feature = "sqlite")]
+    let builder = builder.connection_customizer(Box::new(SqliteConnectionCustomizer));
+
+    builder.build(manager)
+}
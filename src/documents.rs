@@ -0,0 +1,333 @@
+use crate::accounts::require_presenter_account;
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::models::{
+    FieldError, FieldErrors, NewOption, NewPresentation, NewQuestion, OptionDocument,
+    PresentationDocument, PresentationSettings, QuestionDocument,
+};
+use crate::options::new_option;
+use crate::presentations::{get_presentation, get_presentation_tree, new_presentation};
+use crate::questions::new_question;
+use crate::validation::SHORT_TEXT;
+use crate::{DbConnection, DbPool};
+
+use actix_web::error::BlockingError;
+use actix_web::web::{block, Data, Path, Query};
+use actix_web::{get, post, Error, HttpResponse};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Version of the `PresentationDocument` schema produced and accepted by this application.
+pub const DOCUMENT_VERSION: u32 = 1;
+
+/// Maximum voting duration of a presentation, a day in seconds.
+const MAX_VOTING_DURATION: u32 = 24 * 60 * 60;
+
+/// Formats a presentation document can be written in.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+    Json,
+    Yaml,
+}
+
+impl Default for DocumentFormat {
+    fn default() -> Self {
+        DocumentFormat::Json
+    }
+}
+
+impl FromStr for DocumentFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "json" => Ok(DocumentFormat::Json),
+            "yaml" => Ok(DocumentFormat::Yaml),
+            _ => Err(format!("unknown document format `{}`", input)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DocumentQuery {
+    #[serde(default)]
+    pub format: DocumentFormat,
+}
+
+/// Parses a presentation document.
+///
+/// Both formats are read into a generic value first, so that syntax and schema errors are
+/// reported the same way, with the path of the offending field.
+pub fn parse_document(
+    input: &str,
+    format: DocumentFormat,
+) -> Result<PresentationDocument, FieldError> {
+    let value: serde_json::Value = match format {
+        DocumentFormat::Json => serde_json::from_str(input).map_err(|error| FieldError {
+            path: String::from("."),
+            message: error.to_string(),
+        })?,
+        DocumentFormat::Yaml => serde_yaml::from_str(input).map_err(|error| FieldError {
+            path: String::from("."),
+            message: error.to_string(),
+        })?,
+    };
+
+    serde_path_to_error::deserialize(value).map_err(|error| FieldError {
+        path: error.path().to_string(),
+        message: error.into_inner().to_string(),
+    })
+}
+
+/// Checks a parsed document against the rules the schema can not express.
+///
+/// Returns every problem found, an empty list means the document can be imported.
+pub fn validate_document(document: &PresentationDocument) -> Vec<FieldError> {
+    let mut errors = vec![];
+
+    if document.version != DOCUMENT_VERSION {
+        errors.push(FieldError {
+            path: String::from("version"),
+            message: format!("unsupported version, expected {}", DOCUMENT_VERSION),
+        });
+    }
+
+    SHORT_TEXT.check("title", &document.title, &mut errors);
+
+    if let Some(seconds) = document.settings.voting_duration {
+        if seconds == 0 || seconds > MAX_VOTING_DURATION {
+            errors.push(FieldError {
+                path: String::from("settings.voting_duration"),
+                message: format!("must be between 1 and {} seconds", MAX_VOTING_DURATION),
+            });
+        }
+    }
+
+    for (question_index, question) in document.questions.iter().enumerate() {
+        SHORT_TEXT.check(
+            &format!("questions[{}].title", question_index),
+            &question.title,
+            &mut errors,
+        );
+
+        for (option_index, option) in question.options.iter().enumerate() {
+            SHORT_TEXT.check(
+                &format!(
+                    "questions[{}].options[{}].data",
+                    question_index, option_index
+                ),
+                &option.data,
+                &mut errors,
+            );
+        }
+    }
+
+    errors
+}
+
+/// Builds the document of a presentation.
+pub fn export_presentation(
+    presentation_id: i32,
+    connection: &DbConnection,
+) -> Result<PresentationDocument, DieselError> {
+    let tree = get_presentation_tree(presentation_id, false, connection)?;
+
+    let questions = tree
+        .questions
+        .into_iter()
+        .map(|tree| QuestionDocument {
+            title: tree.question.title,
+            options: tree
+                .options
+                .into_iter()
+                .map(|tree| OptionDocument {
+                    data: tree.option.data,
+                    is_correct: tree.option.is_correct,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(PresentationDocument {
+        version: DOCUMENT_VERSION,
+        title: tree.presentation.title,
+        settings: PresentationSettings {
+            voting_duration: tree
+                .presentation
+                .voting_duration
+                .map(|seconds| seconds as u32),
+        },
+        questions,
+    })
+}
+
+/// Creates a presentation with its questions and options from a document.
+///
+/// Everything is created in a single transaction, and owned by `user_id`. Returns the id of the
+/// new presentation.
+pub fn import_presentation(
+    document: &PresentationDocument,
+    user_id: i32,
+    connection: &DbConnection,
+) -> Result<i32, DieselError> {
+    connection.transaction(|| {
+        let now = Utc::now().naive_utc();
+        let presentation = NewPresentation::new(
+            document.title.clone(),
+            user_id,
+            now,
+            document
+                .settings
+                .voting_duration
+                .map(|seconds| seconds as i32),
+        );
+        let presentation_id = new_presentation(presentation, connection)?;
+
+        for question in &document.questions {
+            let record = NewQuestion::new(question.title.clone(), now, presentation_id, user_id);
+            let question_id = new_question(record, connection)?;
+
+            for option in &question.options {
+                let record = NewOption::new(
+                    option.data.clone(),
+                    user_id,
+                    question_id,
+                    now,
+                    option.is_correct,
+                );
+                new_option(record, connection)?;
+            }
+        }
+
+        Ok(presentation_id)
+    })
+}
+
+/// `/presentations/{id}/document?format=json|yaml` GET
+///
+/// Response:
+/// ```json
+/// {
+///    "version": 1,
+///    "title": "New Presentation",
+///    "settings": {
+///        "voting_duration": 30
+///    },
+///    "questions": [
+///        {
+///            "title": "New Question",
+///            "options": [
+///                {
+///                    "data": "Option 1",
+///                    "is_correct": false
+///                }
+///            ]
+///        }
+///    ]
+/// }
+/// ```
+#[get("/presentations/{id}/document")]
+pub async fn get(
+    pool: Data<DbPool>,
+    data: Path<i32>,
+    query: Query<DocumentQuery>,
+) -> Result<HttpResponse, Error> {
+    let connection = pool.get().expect("Unable to get database connection.");
+    let presentation_id = data.into_inner();
+
+    let document = block(move || export_presentation(presentation_id, &connection))
+        .await
+        .map_err(|error| match error {
+            BlockingError::Error(DieselError::NotFound) => {
+                HttpResponse::NotFound().body("Could not find the presentation.")
+            }
+            _ => HttpResponse::InternalServerError().finish(),
+        })?;
+
+    match query.into_inner().format {
+        DocumentFormat::Json => Ok(HttpResponse::Ok().json(document)),
+        DocumentFormat::Yaml => {
+            let body = serde_yaml::to_string(&document)
+                .map_err(|_| HttpResponse::InternalServerError().finish())?;
+
+            Ok(HttpResponse::Ok()
+                .content_type("application/x-yaml")
+                .body(body))
+        }
+    }
+}
+
+/// Creates a presentation from a document, owned by the logged in user.
+///
+/// `/presentations/import?format=json|yaml` POST
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Body: a document as returned by `/presentations/{id}/document`.
+///
+/// Response:
+/// ```json
+/// {
+///    "id": 48,
+///    "title": "New Presentation",
+///    "user_id": 7,
+///    "created": "2019-11-01T14:30:30",
+///    "voting_duration": null
+/// }
+/// ```
+///
+/// Response (not a presenter): 403 Forbidden
+///
+/// Response (invalid document): 422 Unprocessable Entity
+/// ```json
+/// {
+///    "errors": [
+///        {
+///            "path": "questions[0].options[1].data",
+///            "message": "must not be empty"
+///        }
+///    ]
+/// }
+/// ```
+#[post("/presentations/import")]
+pub async fn import(
+    pool: Data<DbPool>,
+    body: String,
+    query: Query<DocumentQuery>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(Scope::Write)?;
+    require_presenter_account(user.id(), &pool).await?;
+
+    let document = match parse_document(&body, query.into_inner().format) {
+        Ok(document) => document,
+        Err(error) => {
+            return Ok(HttpResponse::UnprocessableEntity().json(FieldErrors {
+                errors: vec![error],
+            }))
+        }
+    };
+
+    let errors = validate_document(&document);
+    if !errors.is_empty() {
+        return Ok(HttpResponse::UnprocessableEntity().json(FieldErrors { errors }));
+    }
+
+    let uid = user.id();
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    let presentation = block(move || {
+        let presentation_id = import_presentation(&document, uid, &connection)?;
+
+        get_presentation(presentation_id, &connection)
+    })
+    .await
+    .map_err(|_| HttpResponse::InternalServerError().body("Could not import presentation."))?;
+
+    Ok(HttpResponse::Ok().json(presentation))
+}
@@ -0,0 +1,249 @@
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::models::ExportRow;
+use crate::parents::{attach_error_response, AttachError, Parent};
+use crate::{DbConnection, DbPool};
+
+use actix_web::error::BlockingError;
+use actix_web::http::header;
+use actix_web::web::{block, Bytes, Data, Path, Query};
+use actix_web::{get, Error, HttpResponse};
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use futures::stream;
+use serde::Deserialize;
+
+/// Number of answers loaded from the database for every chunk of the export.
+const EXPORT_BATCH_SIZE: i64 = 500;
+
+/// Column names of the CSV export, in the order of the `ExportRow` fields.
+const CSV_HEADER: [&str; 9] = [
+    "answer_id",
+    "question_id",
+    "question_title",
+    "option_id",
+    "option_data",
+    "user_id",
+    "user_name",
+    "participant_id",
+    "created",
+];
+
+/// Formats the presentation results can be exported in.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+impl Default for ExportFormat {
+    fn default() -> Self {
+        ExportFormat::Csv
+    }
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Loads the next batch of answers of a presentation, ordered by answer id.
+///
+/// Only answers with an id greater than `after` are returned, so that the export can walk the
+/// table in chunks without keeping the whole result set in memory.
+pub fn get_export_rows(
+    presentation_id: i32,
+    after: i32,
+    limit: i64,
+    connection: &DbConnection,
+) -> Result<Vec<ExportRow>, DieselError> {
+    use crate::schema::{answers, options, questions, users};
+
+    answers::table
+        .inner_join(options::table.on(options::id.eq(answers::option_id)))
+        .inner_join(questions::table.on(questions::id.eq(options::question_id)))
+        .left_join(users::table.on(answers::user_id.eq(users::id.nullable())))
+        .filter(questions::presentation_id.eq(presentation_id))
+        .filter(answers::id.gt(after))
+        .order_by(answers::id.asc())
+        .limit(limit)
+        .select((
+            answers::id,
+            questions::id,
+            questions::title,
+            options::id,
+            options::data,
+            users::id.nullable(),
+            users::name.nullable(),
+            answers::participant_id,
+            answers::created,
+        ))
+        .load(connection)
+}
+
+/// Encodes a batch of rows, the CSV header is only written before the first one.
+pub fn encode_rows(
+    rows: &[ExportRow],
+    format: ExportFormat,
+    with_header: bool,
+) -> Result<Vec<u8>, Error> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(vec![]);
+
+            if with_header {
+                writer
+                    .write_record(&CSV_HEADER)
+                    .map_err(|_| HttpResponse::InternalServerError().finish())?;
+            }
+
+            for row in rows {
+                writer
+                    .serialize(row)
+                    .map_err(|_| HttpResponse::InternalServerError().finish())?;
+            }
+
+            writer
+                .into_inner()
+                .map_err(|_| HttpResponse::InternalServerError().finish().into())
+        }
+        ExportFormat::Jsonl => {
+            let mut buffer = vec![];
+
+            for row in rows {
+                serde_json::to_writer(&mut buffer, row)
+                    .map_err(|_| HttpResponse::InternalServerError().finish())?;
+                buffer.push(b'\n');
+            }
+
+            Ok(buffer)
+        }
+    }
+}
+
+/// Exports the answers of a presentation, one row per answer.
+///
+/// The response is streamed in chunks of `EXPORT_BATCH_SIZE` answers. Only the presenter can
+/// export the answers. Answers of anonymous participants have empty user columns, and the id of
+/// the participant instead, so they can be told apart without knowing who gave them.
+///
+/// `/presentations/{id}/export?format=csv|jsonl` GET
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Response (csv):
+/// ```txt
+/// answer_id,question_id,question_title,option_id,option_data,user_id,user_name,participant_id,created
+/// 47,23,New Question,12,Option 1,7,agent 42,,2019-11-01T14:30:30
+/// 48,23,New Question,13,Option 2,,,5,2019-11-01T14:30:32
+/// ```
+///
+/// Response (jsonl):
+/// ```txt
+/// {"answer_id":47,"question_id":23,"question_title":"New Question","option_id":12,"option_data":"Option 1","user_id":7,"user_name":"agent 42","participant_id":null,"created":"2019-11-01T14:30:30"}
+/// ```
+///
+/// Response (presentation of someone else): 403 Forbidden
+///
+/// Response (unknown presentation): 404 Not Found
+#[get("/presentations/{id}/export")]
+pub async fn get(
+    pool: Data<DbPool>,
+    data: Path<i32>,
+    query: Query<ExportQuery>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(Scope::Read)?;
+
+    let presentation_id = data.into_inner();
+    let format = query.into_inner().format;
+    let uid = user.id();
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    block(move || Parent::Presentation(presentation_id).require_presenter(uid, &connection))
+        .await
+        .map_err(|error| match error {
+            BlockingError::Error(AttachError::Forbidden(_)) => {
+                HttpResponse::Forbidden().body("Only the presenter can export the answers.")
+            }
+            error => attach_error_response(error),
+        })?;
+
+    let pool = pool.get_ref().clone();
+
+    // The state is the pool, the id of the last exported answer and whether the header is still
+    // to be written. The cursor becomes `None` once the last batch has been sent.
+    let chunks = stream::unfold(
+        (pool, Some(0), true),
+        move |(pool, cursor, with_header)| async move {
+            let after = cursor?;
+            let connection = match pool.get() {
+                Ok(connection) => connection,
+                Err(_) => {
+                    let error = HttpResponse::InternalServerError().finish().into();
+                    return Some((Err(error), (pool, None, false)));
+                }
+            };
+
+            let rows = block(move || {
+                get_export_rows(presentation_id, after, EXPORT_BATCH_SIZE, &connection)
+            })
+            .await;
+
+            let rows = match rows {
+                Ok(rows) => rows,
+                Err(_) => {
+                    let error = HttpResponse::InternalServerError().finish().into();
+                    return Some((Err(error), (pool, None, false)));
+                }
+            };
+
+            if rows.is_empty() && !with_header {
+                return None;
+            }
+
+            let next_cursor = if (rows.len() as i64) < EXPORT_BATCH_SIZE {
+                None
+            } else {
+                rows.last().map(|row| row.answer_id)
+            };
+
+            let chunk = encode_rows(&rows, format, with_header).map(Bytes::from);
+
+            Some((chunk, (pool, next_cursor, false)))
+        },
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"presentation-{}.{}\"",
+                presentation_id,
+                format.extension()
+            ),
+        )
+        .streaming(Box::pin(chunks)))
+}
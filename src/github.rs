@@ -0,0 +1,244 @@
+//! Login with GitHub, using the OAuth web application flow.
+
+use crate::accounts::link_identity;
+use crate::config::{Config, GitHubConfig};
+use crate::models::User;
+use crate::oauth::{self, OAuthError};
+use crate::session::{session_user_id, start_session};
+use crate::DbPool;
+
+use actix_identity::Identity;
+use actix_web::cookie::Cookie;
+use actix_web::error::BlockingError;
+use actix_web::http::header;
+use actix_web::web::{block, Data, Query};
+use actix_web::{get, Error, HttpRequest, HttpResponse};
+use reqwest::Url;
+use serde::Deserialize;
+
+/// Name of the provider in `user_identities`.
+const PROVIDER: &str = "github";
+
+/// Cookie holding the `state` of an authorization request until GitHub redirects back.
+const STATE_COOKIE: &str = "github-oauth-state";
+
+/// Sent as the `User-Agent`, which the GitHub API requires.
+const USER_AGENT: &str = "questionnaire-rs";
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Response of the token endpoint, which reports errors with a 200 status as well.
+#[derive(Deserialize)]
+struct AccessTokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// The fields of a GitHub user profile the application needs.
+#[derive(Deserialize, Debug)]
+pub struct GitHubProfile {
+    pub id: i64,
+    pub login: String,
+}
+
+fn state_cookie(state: String) -> Cookie<'static> {
+    oauth::state_cookie(STATE_COOKIE, "/auth/github", state)
+}
+
+/// Builds the URL the user is sent to, to authorize the application at GitHub.
+pub fn authorize_url(config: &GitHubConfig, state: &str) -> Url {
+    let mut url = Url::parse(&config.authorize_url).expect("Invalid GitHub authorize URL.");
+
+    {
+        let mut query = url.query_pairs_mut();
+        query
+            .append_pair("client_id", &config.client_id)
+            .append_pair("scope", "read:user")
+            .append_pair("state", state);
+
+        if let Some(redirect_url) = &config.redirect_url {
+            query.append_pair("redirect_uri", redirect_url);
+        }
+    }
+
+    url
+}
+
+/// Exchanges the code GitHub redirected back with for an access token.
+pub fn exchange_code(config: &GitHubConfig, code: &str) -> Result<String, OAuthError> {
+    let mut form = vec![
+        ("client_id", config.client_id.as_str()),
+        ("client_secret", config.client_secret.as_str()),
+        ("code", code),
+    ];
+    if let Some(redirect_url) = &config.redirect_url {
+        form.push(("redirect_uri", redirect_url));
+    }
+
+    let response: AccessTokenResponse = reqwest::Client::new()
+        .post(&config.token_url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .form(&form)
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    match response.access_token {
+        Some(token) => Ok(token),
+        None => Err(OAuthError::Provider(
+            response
+                .error_description
+                .or(response.error)
+                .unwrap_or_else(|| String::from("No access token in the response.")),
+        )),
+    }
+}
+
+/// Loads the profile of the user an access token belongs to.
+pub fn fetch_profile(config: &GitHubConfig, token: &str) -> Result<GitHubProfile, OAuthError> {
+    let profile = reqwest::Client::new()
+        .get(&format!("{}/user", config.api_url))
+        .header(reqwest::header::ACCEPT, "application/vnd.github.v3+json")
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .header(reqwest::header::AUTHORIZATION, format!("token {}", token))
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    Ok(profile)
+}
+
+/// Completes the login once GitHub redirected back, and returns the linked user.
+pub fn login_with_code(
+    config: &GitHubConfig,
+    code: &str,
+    current_user: Option<i32>,
+    pool: &DbPool,
+) -> Result<User, OAuthError> {
+    let token = exchange_code(config, code)?;
+    let profile = fetch_profile(config, &token)?;
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    let user = link_identity(
+        PROVIDER,
+        &profile.id.to_string(),
+        &profile.login,
+        current_user,
+        &connection,
+    )?;
+
+    Ok(user)
+}
+
+/// Starts a login with GitHub, by redirecting to its authorization page.
+///
+/// `/auth/github` GET
+///
+/// Response: 302 Found, redirecting to GitHub.
+///
+/// Response (GitHub login not configured): 404 Not Found
+#[get("/auth/github")]
+pub async fn authorize(config: Data<Config>) -> HttpResponse {
+    let github = match &config.github {
+        Some(github) => github,
+        None => return HttpResponse::NotFound().body("GitHub login is not configured."),
+    };
+
+    let state = oauth::random_token();
+    let url = authorize_url(github, &state);
+
+    HttpResponse::Found()
+        .header(header::LOCATION, url.as_str())
+        .cookie(state_cookie(state))
+        .finish()
+}
+
+/// Completes a login with GitHub.
+///
+/// GitHub redirects here after the user authorized the application. The GitHub account is linked
+/// to the logged in user if that is a presenter, and to a new user named after the GitHub login
+/// otherwise. Audience members logged in with just a name get a new user.
+///
+/// `/auth/github/callback?code=<code>&state=<state>` GET
+///
+/// Response: 302 Found, redirecting to `FRONT_END_BASE_URL`. Without a front end the user is
+/// returned instead.
+/// ```json
+/// {
+///     "id": 9,
+///     "name": "octocat",
+///     "created": "2020-11-18T03:30:22"
+/// }
+/// ```
+///
+/// Response (missing code or state mismatch): 400 Bad Request
+///
+/// Response (GitHub refused the code): 502 Bad Gateway
+#[get("/auth/github/callback")]
+pub async fn callback(
+    request: HttpRequest,
+    query: Query<CallbackQuery>,
+    config: Data<Config>,
+    pool: Data<DbPool>,
+    id: Identity,
+) -> Result<HttpResponse, Error> {
+    let github = match &config.github {
+        Some(github) => github.clone(),
+        None => return Ok(HttpResponse::NotFound().body("GitHub login is not configured.")),
+    };
+    let query = query.into_inner();
+
+    let expected_state = oauth::state_cookie_value(&request, STATE_COOKIE);
+    if expected_state.is_none() || expected_state != query.state {
+        return Ok(HttpResponse::BadRequest().body("Invalid OAuth state."));
+    }
+
+    if let Some(error) = query.error {
+        return Ok(HttpResponse::BadRequest().body(format!("GitHub login failed: {}", error)));
+    }
+
+    let code = match query.code {
+        Some(code) => code,
+        None => return Ok(HttpResponse::BadRequest().body("Missing OAuth code.")),
+    };
+
+    let timeouts = config.session_timeouts;
+    let current_user = session_user_id(id.identity(), pool.get_ref(), timeouts).await?;
+    let pool = pool.get_ref().clone();
+
+    let (user, session_token) = block(move || {
+        let user = login_with_code(&github, &code, current_user, &pool)?;
+        let connection = pool.get().expect("Unable to get database connection.");
+        let session_token = start_session(user.id, timeouts, &connection)?;
+
+        Ok((user, session_token))
+    })
+    .await
+    .map_err(|error| match error {
+        BlockingError::Error(OAuthError::Database(_)) | BlockingError::Canceled => {
+            HttpResponse::InternalServerError().body("Could not log in with GitHub.")
+        }
+        BlockingError::Error(error) => {
+            HttpResponse::BadGateway().body(format!("GitHub login failed: {}", error))
+        }
+    })?;
+
+    id.remember(session_token);
+
+    Ok(match &config.front_end_base_url {
+        Some(front_end_base_url) => HttpResponse::Found()
+            .header(header::LOCATION, front_end_base_url.as_str())
+            .del_cookie(&state_cookie(String::new()))
+            .finish(),
+        None => HttpResponse::Ok()
+            .del_cookie(&state_cookie(String::new()))
+            .json(user),
+    })
+}
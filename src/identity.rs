@@ -0,0 +1,75 @@
+//! Cookie identity policy supporting signing key rotation.
+
+use actix_identity::{CookieIdentityPolicy, IdentityPolicy};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{Error, HttpMessage};
+use futures::future::{ok, Ready};
+use futures::FutureExt;
+
+/// Marks a request whose identity cookie was signed with an older key.
+struct SignedWithOldKey;
+
+/// Reads identity cookies signed with any of the configured keys, and writes them with the
+/// newest one.
+///
+/// A valid cookie signed with an older key is signed again with the newest key in the response,
+/// so older keys can be dropped once every active cookie has been renewed.
+pub struct RotatingCookieIdentityPolicy {
+    /// Policies of every key, the newest first.
+    policies: Vec<CookieIdentityPolicy>,
+}
+
+impl RotatingCookieIdentityPolicy {
+    /// Creates the policy from one `CookieIdentityPolicy` per key, the newest first.
+    ///
+    /// Panics if `policies` is empty.
+    pub fn new(policies: Vec<CookieIdentityPolicy>) -> Self {
+        assert!(
+            !policies.is_empty(),
+            "At least one cookie identity policy is required."
+        );
+
+        RotatingCookieIdentityPolicy { policies }
+    }
+}
+
+impl IdentityPolicy for RotatingCookieIdentityPolicy {
+    type Future = Ready<Result<Option<String>, Error>>;
+    type ResponseFuture = Ready<Result<(), Error>>;
+
+    fn from_request(&self, request: &mut ServiceRequest) -> Self::Future {
+        for (index, policy) in self.policies.iter().enumerate() {
+            // The cookie policy resolves immediately.
+            let identity = policy
+                .from_request(request)
+                .now_or_never()
+                .unwrap_or(Ok(None));
+
+            if let Ok(Some(identity)) = identity {
+                if index > 0 {
+                    request.extensions_mut().insert(SignedWithOldKey);
+                }
+
+                return ok(Some(identity));
+            }
+        }
+
+        ok(None)
+    }
+
+    fn to_response<B>(
+        &self,
+        identity: Option<String>,
+        changed: bool,
+        response: &mut ServiceResponse<B>,
+    ) -> Self::ResponseFuture {
+        let renew = identity.is_some()
+            && response
+                .request()
+                .extensions()
+                .get::<SignedWithOldKey>()
+                .is_some();
+
+        self.policies[0].to_response(identity, changed || renew, response)
+    }
+}
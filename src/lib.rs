@@ -1,6 +1,17 @@
 //! Backend of the Questionnaire app.
 //! ### API endpoints available:
 //!
+//! `POST` requests authenticated by the auth cookie must come from the front end: their `Origin`,
+//! or `Referer`, must be `FRONT_END_BASE_URL` or the backend itself, otherwise they are refused
+//! with 403 Forbidden. Requests with an API token, and answers with a device token, are exempt.
+//!
+//! Presentations, and their questions and options, are only changed by their owner, who also
+//! needs a presenter account or a GitHub or OpenID Connect login. Otherwise the owner gets the same
+//! 403 Forbidden as anyone else.
+//!
+//! Every endpoint is rate limited per client. Over the limit, requests are answered with
+//! 429 Too Many Requests and a `Retry-After` header with the seconds to wait.
+//!
 //! #### `/answers`
 //!
 //! **Method:** POST
@@ -17,6 +28,9 @@
 //! auth-cookie: <cookie_value>
 //! ```
 //!
+//! Anonymous participants send `X-Device-Token: <device_token>` instead of the cookie, and
+//! answer every question once. Questions take answers only while the presenter has them open.
+//!
 //! **Body:**
 //!
 //! ```json
@@ -27,6 +41,32 @@
 //!
 //! **Response:** 200 OK
 //!
+//! **Response (option of another presentation than the participant's):** 403 Forbidden
+//!
+//! **Response (unknown option):** 404 Not Found
+//!
+//! **Response (the participant already answered the question):** 409 Conflict
+//!
+//! **Response (the question is not open for answers):** 409 Conflict
+//!
+//! #### Join a presentation anonymously
+//!
+//! Creates a participant of the presentation, who answers with the returned device token instead
+//! of logging in. The presenter only sees the vote counts of participants.
+//!
+//! **Endpoint:** `/presentations/{id}/join`
+//!
+//! **Method:** POST
+//!
+//! **Response:**
+//!
+//! ```json
+//! {
+//!    "participant_id": 5,
+//!    "device_token": "5.6b1d0c3f9a8e7d2c4b5a69788f0e1d2c3b4a59687f6e5d4c3b2a190817263544"
+//! }
+//! ```
+//!
 //! #### `/answers/{id}`
 //!
 //! **Method:** GET
@@ -52,8 +92,32 @@
 //! Authorization: token <access_token>
 //! ```
 //!
+//! Ends the session of the auth cookie, which is not accepted anymore afterwards.
+//!
 //! **Response:** 200 OK
 //!
+//! #### Log out everywhere
+//!
+//! Ends every session of the logged in user, on all devices. API tokens stay valid.
+//!
+//! **Endpoint:** `/logout-everywhere`
+//!
+//! **Method:** POST
+//!
+//! **Cookies:**
+//!
+//! ```txt
+//! auth-cookie: <cookie_value>
+//! ```
+//!
+//! **Response:**
+//!
+//! ```json
+//! {
+//!    "sessions": 3
+//! }
+//! ```
+//!
 //! #### `/presentations`
 //!
 //! **Method:** POST
@@ -80,6 +144,12 @@
 //!
 //! **Response:** 200 OK
 //!
+//! **Response (not a presenter):** 403 Forbidden. Only users with a presenter account or a GitHub
+//! or OpenID Connect login create presentations.
+//!
+//! **Response (invalid input):** 422 Unprocessable Entity, with the errors of each field. Titles
+//! must not be empty, start or end with whitespace, or be longer than 255 characters.
+//!
 //! #### `/presentations/{id}`
 //!
 //! **Method:** GET
@@ -91,10 +161,224 @@
 //!    "id": 47,
 //!    "title": "New Presentation",
 //!    "user_id": 7,
-//!    "created": "2019-11-01T14:30:30"
+//!    "created": "2019-11-01T14:30:30",
+//!    "voting_duration": null
+//! }
+//! ```
+//!
+//! #### Get a presentation with its questions and options
+//!
+//! The questions and options are ordered. When `votes` is set every option also has the number of
+//! answers it received.
+//!
+//! **Endpoint:** `/presentations/{id}/tree?votes=true`
+//!
+//! **Method:** GET
+//!
+//! **Response:**
+//!
+//! ```json
+//! {
+//!    "id": 47,
+//!    "title": "New Presentation",
+//!    "user_id": 7,
+//!    "created": "2019-11-01T14:30:30",
+//!    "voting_duration": null,
+//!    "questions": [
+//!        {
+//!            "id": 23,
+//!            "title": "New Question",
+//!            "created": "2019-11-01T14:30:30",
+//!            "presentation_id": 47,
+//!            "user_id": 7,
+//!            "options": [
+//!                {
+//!                    "id": 12,
+//!                    "data": "Option 1",
+//!                    "user_id": 7,
+//!                    "question_id": 23,
+//!                    "created": "2019-11-01T14:30:30",
+//!                    "is_correct": false,
+//!                    "votes": 3
+//!                }
+//!            ]
+//!        }
+//!    ]
 //! }
 //! ```
 //!
+//! #### Export the answers of a presentation
+//!
+//! One row per answer. The response is streamed in chunks, `format` defaults to `csv`. Only the
+//! presenter can export the answers. Answers of anonymous participants have empty user columns,
+//! and the id of the participant instead.
+//!
+//! **Endpoint:** `/presentations/{id}/export?format=csv|jsonl`
+//!
+//! **Method:** GET
+//!
+//! **Cookies:**
+//!
+//! ```txt
+//! auth-cookie: <cookie_value>
+//! ```
+//!
+//! **Response (csv):**
+//!
+//! ```txt
+//! answer_id,question_id,question_title,option_id,option_data,user_id,user_name,participant_id,created
+//! 47,23,New Question,12,Option 1,7,agent 42,,2019-11-01T14:30:30
+//! 48,23,New Question,13,Option 2,,,5,2019-11-01T14:30:32
+//! ```
+//!
+//! **Response (jsonl):**
+//!
+//! ```txt
+//! {"answer_id":47,"question_id":23,"question_title":"New Question","option_id":12,"option_data":"Option 1","user_id":7,"user_name":"agent 42","participant_id":null,"created":"2019-11-01T14:30:30"}
+//! ```
+//!
+//! **Response (presentation of someone else):** 403 Forbidden
+//!
+//! **Response (unknown presentation):** 404 Not Found
+//!
+//! #### Export a presentation as a document
+//!
+//! The document keeps the questions and options in order and can be imported again. `format`
+//! defaults to `json`. Every setting is optional, `voting_duration` is the number of seconds
+//! questions stay open for when they are opened without a duration.
+//!
+//! **Endpoint:** `/presentations/{id}/document?format=json|yaml`
+//!
+//! **Method:** GET
+//!
+//! **Response:**
+//!
+//! ```json
+//! {
+//!    "version": 1,
+//!    "title": "New Presentation",
+//!    "settings": {
+//!        "voting_duration": 30
+//!    },
+//!    "questions": [
+//!        {
+//!            "title": "New Question",
+//!            "options": [
+//!                {
+//!                    "data": "Option 1",
+//!                    "is_correct": false
+//!                }
+//!            ]
+//!        }
+//!    ]
+//! }
+//! ```
+//!
+//! #### Import a presentation from a document
+//!
+//! Creates the presentation, its questions and options in a single transaction. The logged in
+//! user becomes the owner.
+//!
+//! **Endpoint:** `/presentations/import?format=json|yaml`
+//!
+//! **Method:** POST
+//!
+//! **Cookies:**
+//!
+//! ```txt
+//! auth-cookie: <cookie_value>
+//! ```
+//!
+//! **Body:** a document as returned by `/presentations/{id}/document`.
+//!
+//! **Response:**
+//!
+//! ```json
+//! {
+//!    "id": 48,
+//!    "title": "New Presentation",
+//!    "user_id": 7,
+//!    "created": "2019-11-01T14:30:30",
+//!    "voting_duration": null
+//! }
+//! ```
+//!
+//! **Response (not a presenter):** 403 Forbidden
+//!
+//! **Response (invalid document):** 422 Unprocessable Entity. Titles and option texts follow the
+//! rules of `/presentations`.
+//!
+//! ```json
+//! {
+//!    "errors": [
+//!        {
+//!            "path": "questions[0].options[1].data",
+//!            "message": "must not be empty"
+//!        }
+//!    ]
+//! }
+//! ```
+//!
+//! #### Create a presentation from Markdown
+//!
+//! The `#` heading is the title of the presentation, `##` headings are questions, and the list
+//! items below them are options. `[x]` marks the correct options of a quiz. The whole presentation
+//! is created in a single transaction.
+//!
+//! **Endpoint:** `/presentations/import/markdown`
+//!
+//! **Method:** POST
+//!
+//! **Cookies:**
+//!
+//! ```txt
+//! auth-cookie: <cookie_value>
+//! ```
+//!
+//! **Body:**
+//!
+//! ```md
+//! # New Presentation
+//!
+//! ## New Question
+//!
+//! - [x] Option 1
+//! - [ ] Option 2
+//! ```
+//!
+//! **Response:**
+//!
+//! ```json
+//! {
+//!    "id": 48,
+//!    "title": "New Presentation",
+//!    "user_id": 7,
+//!    "created": "2019-11-01T14:30:30",
+//!    "voting_duration": null,
+//!    "questions": [
+//!        {
+//!            "id": 23,
+//!            "title": "New Question",
+//!            "created": "2019-11-01T14:30:30",
+//!            "presentation_id": 48,
+//!            "user_id": 7,
+//!            "options": [
+//!                {
+//!                    "id": 12,
+//!                    "data": "Option 1",
+//!                    "user_id": 7,
+//!                    "question_id": 23,
+//!                    "created": "2019-11-01T14:30:30",
+//!                    "is_correct": true
+//!                }
+//!            ]
+//!        }
+//!    ]
+//! }
+//! ```
+//!
+//! **Response (not a presenter):** 403 Forbidden
+//!
 //! #### `/questions`
 //!
 //! **Method:** POST
@@ -122,6 +406,66 @@
 //!
 //! **Response:** 200 OK
 //!
+//! **Response (presentation of someone else):** 403 Forbidden
+//!
+//! **Response (unknown presentation):** 404 Not Found
+//!
+//! **Response (invalid input):** 422 Unprocessable Entity, with the errors of each field
+//!
+//! #### Create a question together with its options
+//!
+//! Everything is created in a single transaction. `is_correct` is optional.
+//!
+//! **Endpoint:** `/questions-options`
+//!
+//! **Method:** POST
+//!
+//! **Headers:**
+//!
+//! ```txt
+//! Content type: application/json
+//! ```
+//!
+//! **Cookies:**
+//!
+//! ```txt
+//! auth-cookie: <cookie_value>
+//! ```
+//!
+//! **Body:**
+//!
+//! ```json
+//! {
+//!    "title": "New Question",
+//!    "presentation_id": 1,
+//!    "options": [
+//!        {
+//!            "data": "Option 1",
+//!            "is_correct": true
+//!        },
+//!        {
+//!            "data": "Option 2"
+//!        }
+//!    ]
+//! }
+//! ```
+//!
+//! **Response:**
+//!
+//! ```json
+//! {
+//!    "question_id": 23,
+//!    "option_ids": [12, 13]
+//! }
+//! ```
+//!
+//! **Response (presentation of someone else):** 403 Forbidden
+//!
+//! **Response (unknown presentation):** 404 Not Found
+//!
+//! **Response (invalid input):** 422 Unprocessable Entity, with the errors of each field, like
+//! `options[1].data`
+//!
 //! #### `/questions/{id}`
 //!
 //! **Method:** GET
@@ -135,6 +479,9 @@
 //!    "created": "2019-11-01T14:30:30",
 //!    "presentation_id": 3,
 //!    "user_id": 7,
+//!    "opened_at": "2019-11-01T14:35:00",
+//!    "closes_at": "2019-11-01T14:35:30",
+//!    "closed_at": null
 //! }
 //! ```
 //!
@@ -154,6 +501,9 @@
 //!         "created": "2019-11-01T14:30:30",
 //!         "presentation_id": 3,
 //!         "user_id": 7,
+//!         "opened_at": "2019-11-01T14:35:00",
+//!         "closes_at": "2019-11-01T14:35:30",
+//!         "closed_at": null
 //!     }
 //! ]
 //! ```
@@ -179,11 +529,18 @@
 //! {
 //!    "data": "Option 1",
 //!    "question_id": 1,
+//!    "is_correct": false,
 //! }
 //! ```
 //!
 //! **Response:** 200 OK
 //!
+//! **Response (question of someone else's presentation):** 403 Forbidden
+//!
+//! **Response (unknown question):** 404 Not Found
+//!
+//! **Response (invalid input):** 422 Unprocessable Entity, with the errors of each field
+//!
 //! #### `/options/{id}`
 //!
 //! **Method:** GET
@@ -196,7 +553,8 @@
 //!    "data": "Option 1",
 //!    "user_id": 9,
 //!    "question_id": 1,
-//!    "created": "2019-06-19T03:40:50"
+//!    "created": "2019-06-19T03:40:50",
+//!    "is_correct": false
 //! }
 //! ```
 //!
@@ -215,24 +573,78 @@
 //!         "data": "Option 1",
 //!         "user_id": 9,
 //!         "question_id": 1,
-//!         "created": "2019-06-19T03:40:50"
+//!         "created": "2019-06-19T03:40:50",
+//!         "is_correct": false
 //!     }
 //! ]
 //! ```
 //!
-//! #### `/gh-access-token`
+//! #### Log in with GitHub
+//!
+//! Redirects to GitHub, which sends the user back to `/auth/github/callback` once they authorized
+//! the application. The GitHub account is linked to the logged in presenter, or to a new user named
+//! after the GitHub login. Audience members logged in with just their name are not linked, as
+//! anyone can log in with that name. Users linked to GitHub can not log in with just their name.
+//!
+//! **Endpoint:** `/auth/github`
+//!
+//! **Method:** GET
+//!
+//! **Response:** 302 Found, or 404 Not Found if GitHub login is not configured.
+//!
+//! #### GitHub login callback
+//!
+//! **Endpoint:** `/auth/github/callback`
+//!
+//! **Method:** GET
+//!
+//! **Parameters:**
+//!
+//! ```txt
+//! code: the authorization code from GitHub
+//! state: the state sent with the authorization request
+//! ```
+//!
+//! **Response:** 302 Found, redirecting to `FRONT_END_BASE_URL` with the auth cookie set. Without
+//! a front end, the user is returned as for `/login`.
+//!
+//! **Response (state mismatch):** 400 Bad Request
+//!
+//! **Response (GitHub refused the code):** 502 Bad Gateway
+//!
+//! #### Log in with OpenID Connect
+//!
+//! Redirects to the configured OpenID Connect provider, which sends the user back to
+//! `/auth/oidc/callback` once they logged in. The identity is linked to the logged in presenter, or
+//! to a new user named after the `preferred_username`, `name` or `email` claim. Audience members
+//! logged in with just their name are not linked, as anyone can log in with that name.
+//!
+//! **Endpoint:** `/auth/oidc`
+//!
+//! **Method:** GET
+//!
+//! **Response:** 302 Found, 404 Not Found if OpenID Connect login is not configured, or 502 Bad
+//! Gateway if the provider could not be discovered.
+//!
+//! #### OpenID Connect login callback
+//!
+//! **Endpoint:** `/auth/oidc/callback`
 //!
 //! **Method:** GET
 //!
 //! **Parameters:**
 //!
 //! ```txt
-//! code: GITHUB_LOGIN_CODE obtained from https://github.com/login/oauth/authorize
+//! code: the authorization code from the provider
+//! state: the state sent with the authorization request
 //! ```
 //!
-//! **Response:**
+//! **Response:** 302 Found, redirecting to `FRONT_END_BASE_URL` with the auth cookie set. Without
+//! a front end, the user is returned as for `/login`.
+//!
+//! **Response (state mismatch):** 400 Bad Request
 //!
-//! GITHUB_ACCESS_TOKEN in JSON.
+//! **Response (the provider refused the code, or the ID token is invalid):** 502 Bad Gateway
 //!
 //! #### Get answers for an option
 //!
@@ -261,6 +673,9 @@
 //!
 //! #### User login
 //!
+//! Audience members log in with just a name. Presenter accounts also need their password, and
+//! are locked for 15 minutes after 5 failed logins in a row.
+//!
 //! **Endpoint:** `/login`
 //!
 //! **Method:** POST
@@ -270,6 +685,7 @@
 //! ```json
 //! {
 //!    "name": "agent 42",
+//!    "password": "correct horse battery staple"
 //! }
 //! ```
 //!
@@ -283,9 +699,162 @@
 //! }
 //! ```
 //!
+//! **Response (wrong password):** 401 Unauthorized
+//!
+//! **Response (locked account):** 429 Too Many Requests, with a `Retry-After` header
+//!
+//! **Response (invalid input):** 422 Unprocessable Entity, with the errors of each field
+//!
+//! #### Register a presenter account
+//!
+//! Creates the account and logs it in.
+//!
+//! **Endpoint:** `/register`
+//!
+//! **Method:** POST
+//!
+//! **Body:**
+//!
+//! ```json
+//! {
+//!    "name": "agent 42",
+//!    "password": "correct horse battery staple"
+//! }
+//! ```
+//!
+//! **Response:** the new user, as for `/login`.
+//!
+//! **Response (name taken):** 409 Conflict
+//!
+//! **Response (invalid input):** 422 Unprocessable Entity, with the errors of each field
+//!
+//! #### Change the password of a presenter account
+//!
+//! **Endpoint:** `/change-password`
+//!
+//! **Method:** POST
+//!
+//! **Cookies:**
+//!
+//! ```txt
+//! auth-cookie: <cookie_value>
+//! ```
+//!
+//! **Body:**
+//!
+//! ```json
+//! {
+//!    "current_password": "correct horse battery staple",
+//!    "new_password": "battery staple correct horse"
+//! }
+//! ```
+//!
+//! **Response:** 200 OK
+//!
+//! **Response (wrong current password):** 403 Forbidden
+//!
+//! #### API tokens
+//!
+//! Endpoints that need a logged in user also accept a personal API token instead of the auth
+//! cookie, sent as `Authorization: Bearer <token>`. A token only allows what its scopes cover:
+//! `read`, `write` (creating presentations, questions, options and answers, includes `read`) or
+//! `admin` (managing the account and its tokens, includes `write`). Unknown or revoked tokens get
+//! 401 Unauthorized, tokens without the needed scope 403 Forbidden.
+//!
+//! #### Create an API token
+//!
+//! **Endpoint:** `/api-tokens`
+//!
+//! **Method:** POST
+//!
+//! **Cookies:**
+//!
+//! ```txt
+//! auth-cookie: <cookie_value>
+//! ```
+//!
+//! **Body:**
+//!
+//! ```json
+//! {
+//!    "name": "CI import",
+//!    "scopes": ["write"]
+//! }
+//! ```
+//!
+//! **Response:** the token is only returned here.
+//!
+//! ```json
+//! {
+//!    "id": 3,
+//!    "name": "CI import",
+//!    "scopes": ["write"],
+//!    "created": "2020-11-18T03:30:22",
+//!    "last_used": null,
+//!    "token": "qat_2bXq0P0tZ7tHc8GQwU3aV1lJmY9nR4sKdE6fB5hT"
+//! }
+//! ```
+//!
+//! **Response (invalid input):** 422 Unprocessable Entity, with the errors of each field
+//!
+//! #### List API tokens
+//!
+//! **Endpoint:** `/api-tokens`
+//!
+//! **Method:** GET
+//!
+//! **Response:** the tokens of the user as above, without the `token` field.
+//!
+//! #### Revoke an API token
+//!
+//! **Endpoint:** `/api-tokens/{id}/revoke`
+//!
+//! **Method:** POST
+//!
+//! **Response:** 200 OK, or 404 Not Found if the user has no such token.
+//!
+//! #### Get the current user
+//!
+//! Returns the user the request is authenticated as, by the auth cookie or an API token, and their
+//! roles in presentations: `presenter` of the ones they own, `audience` of the ones they answered.
+//!
+//! **Endpoint:** `/me`
+//!
+//! **Method:** GET
+//!
+//! **Cookies (optional):**
+//!
+//! ```txt
+//! auth-cookie: <cookie_value>
+//! ```
+//!
+//! **Response:**
+//!
+//! ```json
+//! {
+//!     "authenticated": true,
+//!     "user": {
+//!         "id": 7,
+//!         "name": "agent 42",
+//!         "created": "2019-11-01T14:30:30"
+//!     },
+//!     "roles": [
+//!         {
+//!             "presentation_id": 3,
+//!             "role": "presenter"
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! Without a valid session or token, `authenticated` is false, `user` is null and `roles` is empty.
+//!
 //! #### Check if a request is authenticated.
 //!
-//! If the authenticating cookie is not passed, or is not valid, then it will return false.
+//! Kept for older clients. Returns the same as `/me`, with `result` set like `authenticated`.
+//!
+//! If the authenticating cookie is not passed, is not valid, or its session has expired or was
+//! logged out, then it will return false.
 //!
 //! **Endpoint:** `/is-logged-in`
 //!
@@ -302,16 +871,29 @@
 //! ```json
 //! {
 //!     "result": false,
+//!     "authenticated": false,
+//!     "user": null,
+//!     "roles": []
 //! }
 //! ```
 
 extern crate chrono;
+extern crate csv;
 extern crate env_logger;
 extern crate reqwest;
 extern crate serde_json;
+extern crate serde_path_to_error;
+extern crate serde_yaml;
+extern crate hmac;
+extern crate sha2;
+extern crate jsonwebtoken;
+extern crate toml;
 #[macro_use]
 extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
 extern crate actix;
+extern crate argon2;
 extern crate actix_cors;
 extern crate actix_http;
 extern crate actix_session;
@@ -324,20 +906,36 @@ extern crate serde;
 extern crate serde_derive;
 extern crate time;
 
-use diesel::{
-    mysql::MysqlConnection,
-    r2d2::{ConnectionManager, Pool},
-};
+#[macro_use]
+pub mod db;
 
+pub mod accounts;
 pub mod answers;
+pub mod api_tokens;
+pub mod auth;
+pub mod config;
+pub mod csrf;
+pub mod documents;
+pub mod export;
+pub mod github;
+pub mod identity;
+pub mod markdown;
+pub mod migrations;
 pub mod models;
+pub mod oauth;
+pub mod oidc;
 pub mod options;
+pub mod parents;
+pub mod participants;
 pub mod presentations;
 pub mod questions;
+pub mod rate_limit;
 pub mod schema;
 pub mod session;
+pub mod validation;
 pub mod web_socket;
 pub mod web_socket_server;
 
 pub const GH_USER_SESSION_ID_KEY: &str = "gh_user_id";
-pub type DbPool = Pool<ConnectionManager<MysqlConnection>>;
+
+pub use crate::db::{DbConnection, DbPool};
@@ -0,0 +1,213 @@
+use crate::accounts::require_presenter_account;
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::documents::{import_presentation, validate_document, DOCUMENT_VERSION};
+use crate::models::{
+    FieldError, FieldErrors, OptionDocument, PresentationDocument, PresentationSettings,
+    QuestionDocument,
+};
+use crate::presentations::get_presentation_tree;
+use crate::DbPool;
+
+use actix_web::web::{block, Data};
+use actix_web::{post, Error, HttpResponse};
+
+/// Returns the text of an ATX heading of the given level, if the line is one.
+fn heading(line: &str, level: usize) -> Option<&str> {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[hashes..];
+
+    if hashes != level || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+
+    // A closing sequence of `#` only counts when whitespace comes before it, so `C#` keeps its `#`.
+    let text = rest.trim();
+    let closed = text.trim_end_matches('#');
+    if closed.is_empty() || closed.ends_with(char::is_whitespace) {
+        Some(closed.trim_end())
+    } else {
+        Some(text)
+    }
+}
+
+/// Returns the text of a bullet or ordered list item, if the line is one.
+fn list_item(line: &str) -> Option<&str> {
+    for marker in &["- ", "* ", "+ "] {
+        if line.starts_with(marker) {
+            return Some(line[marker.len()..].trim());
+        }
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let rest = &line[digits..];
+        if rest.starts_with(". ") || rest.starts_with(") ") {
+            return Some(rest[2..].trim());
+        }
+    }
+
+    None
+}
+
+/// Splits the task list marker off a list item, `[x]` marks a correct answer.
+fn task(item: &str) -> (&str, bool) {
+    if item.starts_with("[x] ") || item.starts_with("[X] ") {
+        (item[4..].trim(), true)
+    } else if item.starts_with("[ ] ") {
+        (item[4..].trim(), false)
+    } else {
+        (item, false)
+    }
+}
+
+/// Reads a presentation from Markdown.
+///
+/// The `#` heading is the title of the presentation, every `##` heading starts a question, and
+/// the list items below it are its options. Options can be written as a task list, where `[x]`
+/// marks the correct answers. Any other line is ignored.
+pub fn parse_markdown(input: &str) -> Result<PresentationDocument, FieldError> {
+    let mut title: Option<String> = None;
+    let mut questions: Vec<QuestionDocument> = vec![];
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        let path = format!("line {}", index + 1);
+
+        if let Some(text) = heading(line, 1) {
+            if title.is_some() {
+                return Err(FieldError {
+                    path,
+                    message: String::from("only one `#` presentation title is allowed"),
+                });
+            }
+
+            title = Some(text.to_owned());
+        } else if let Some(text) = heading(line, 2) {
+            questions.push(QuestionDocument {
+                title: text.to_owned(),
+                options: vec![],
+            });
+        } else if let Some(item) = list_item(line) {
+            let question = questions.last_mut().ok_or_else(|| FieldError {
+                path,
+                message: String::from("list item outside of a `##` question"),
+            })?;
+            let (data, is_correct) = task(item);
+
+            question.options.push(OptionDocument {
+                data: data.to_owned(),
+                is_correct,
+            });
+        }
+    }
+
+    let title = title.ok_or_else(|| FieldError {
+        path: String::from("title"),
+        message: String::from("missing `#` presentation title"),
+    })?;
+
+    Ok(PresentationDocument {
+        version: DOCUMENT_VERSION,
+        title,
+        settings: PresentationSettings::default(),
+        questions,
+    })
+}
+
+/// Creates a presentation from a Markdown document, owned by the logged in user.
+///
+/// `/presentations/import/markdown` POST
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Body:
+/// ```md
+/// # New Presentation
+///
+/// ## New Question
+///
+/// - [x] Option 1
+/// - [ ] Option 2
+/// ```
+///
+/// Response:
+/// ```json
+/// {
+///    "id": 48,
+///    "title": "New Presentation",
+///    "user_id": 7,
+///    "created": "2019-11-01T14:30:30",
+///    "voting_duration": null,
+///    "questions": [
+///        {
+///            "id": 23,
+///            "title": "New Question",
+///            "created": "2019-11-01T14:30:30",
+///            "presentation_id": 48,
+///            "user_id": 7,
+///            "options": [
+///                {
+///                    "id": 12,
+///                    "data": "Option 1",
+///                    "user_id": 7,
+///                    "question_id": 23,
+///                    "created": "2019-11-01T14:30:30",
+///                    "is_correct": true
+///                }
+///            ]
+///        }
+///    ]
+/// }
+/// ```
+///
+/// Response (not a presenter): 403 Forbidden
+///
+/// Response (invalid document): 422 Unprocessable Entity
+/// ```json
+/// {
+///    "errors": [
+///        {
+///            "path": "line 3",
+///            "message": "list item outside of a `##` question"
+///        }
+///    ]
+/// }
+/// ```
+#[post("/presentations/import/markdown")]
+pub async fn import(
+    pool: Data<DbPool>,
+    body: String,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(Scope::Write)?;
+    require_presenter_account(user.id(), &pool).await?;
+
+    let document = match parse_markdown(&body) {
+        Ok(document) => document,
+        Err(error) => {
+            return Ok(HttpResponse::UnprocessableEntity().json(FieldErrors {
+                errors: vec![error],
+            }))
+        }
+    };
+
+    let errors = validate_document(&document);
+    if !errors.is_empty() {
+        return Ok(HttpResponse::UnprocessableEntity().json(FieldErrors { errors }));
+    }
+
+    let uid = user.id();
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    let tree = block(move || {
+        let presentation_id = import_presentation(&document, uid, &connection)?;
+
+        get_presentation_tree(presentation_id, false, &connection)
+    })
+    .await
+    .map_err(|_| HttpResponse::InternalServerError().body("Could not import presentation."))?;
+
+    Ok(HttpResponse::Ok().json(tree))
+}
@@ -0,0 +1,119 @@
+//! Database migrations embedded in the binary.
+//!
+//! Every backend embeds its own migration directory, `migrations`, `migrations-sqlite` or
+//! `migrations-postgres`, so a release can bring its database up to date without `diesel_cli`.
+
+use crate::DbConnection;
+
+use diesel_migrations::{setup_database, MigrationConnection, RunMigrationsError};
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+
+#[allow(bare_trait_objects)]
+mod embedded {
+    #[cfg(feature = "mysql")]
+    #[derive(EmbedMigrations)]
+    #[embed_migrations_options(migrations_path = "migrations")]
+    struct _Dummy;
+
+    #[cfg(feature = "sqlite")]
+    #[derive(EmbedMigrations)]
+    #[embed_migrations_options(migrations_path = "migrations-sqlite")]
+    struct _Dummy;
+
+    #[cfg(feature = "postgres")]
+    #[derive(EmbedMigrations)]
+    #[embed_migrations_options(migrations_path = "migrations-postgres")]
+    struct _Dummy;
+
+    /// Versions of the embedded migrations, oldest first.
+    pub fn versions() -> Vec<&'static str> {
+        ALL_MIGRATIONS
+            .iter()
+            .map(|migration| migration.version())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum MigrationsError {
+    /// The database has migrations applied this binary does not know, it was migrated by a newer
+    /// release.
+    SchemaTooNew(Vec<String>),
+    Run(RunMigrationsError),
+}
+
+impl fmt::Display for MigrationsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationsError::SchemaTooNew(versions) => write!(
+                f,
+                "The database schema is newer than this binary, unknown migrations: {}",
+                versions.join(", ")
+            ),
+            MigrationsError::Run(error) => write!(f, "Could not run migrations: {}", error),
+        }
+    }
+}
+
+impl Error for MigrationsError {}
+
+impl From<RunMigrationsError> for MigrationsError {
+    fn from(error: RunMigrationsError) -> Self {
+        MigrationsError::Run(error)
+    }
+}
+
+impl From<diesel::result::Error> for MigrationsError {
+    fn from(error: diesel::result::Error) -> Self {
+        MigrationsError::Run(RunMigrationsError::QueryError(error))
+    }
+}
+
+/// Returns the versions of the migrations embedded in the binary, oldest first.
+pub fn embedded_versions() -> Vec<&'static str> {
+    embedded::versions()
+}
+
+/// Returns the versions of the embedded migrations not yet applied to the database.
+pub fn pending_versions(connection: &DbConnection) -> Result<Vec<&'static str>, MigrationsError> {
+    setup_database(connection)?;
+    let applied = connection.previously_run_migration_versions()?;
+
+    Ok(embedded_versions()
+        .into_iter()
+        .filter(|version| !applied.contains(*version))
+        .collect())
+}
+
+/// Fails if the database has migrations applied that are not embedded in the binary.
+pub fn check_schema_version(connection: &DbConnection) -> Result<(), MigrationsError> {
+    setup_database(connection)?;
+    let embedded = embedded_versions();
+    let mut unknown: Vec<String> = connection
+        .previously_run_migration_versions()?
+        .into_iter()
+        .filter(|version| !embedded.contains(&version.as_str()))
+        .collect();
+
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        unknown.sort();
+        Err(MigrationsError::SchemaTooNew(unknown))
+    }
+}
+
+/// Applies the pending embedded migrations, writing the name of each one to `output`.
+///
+/// Refuses to touch a database whose schema is newer than the binary.
+pub fn run_pending_migrations(
+    connection: &DbConnection,
+    output: &mut dyn Write,
+) -> Result<(), MigrationsError> {
+    check_schema_version(connection)?;
+    embedded::run_with_output(connection, output)?;
+
+    Ok(())
+}
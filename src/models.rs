@@ -1,7 +1,13 @@
+use crate::auth::Scope;
 use crate::schema::answers;
+use crate::schema::api_tokens;
 use crate::schema::options;
+use crate::schema::participants;
 use crate::schema::presentations;
+use crate::schema::presenter_accounts;
 use crate::schema::questions;
+use crate::schema::sessions;
+use crate::schema::user_identities;
 use crate::schema::users;
 use chrono::NaiveDateTime;
 use diesel::Insertable;
@@ -17,6 +23,12 @@ pub struct Questions {
     pub created: NaiveDateTime,
     pub presentation_id: i32,
     pub user_id: i32,
+    /// When the presenter last opened the question for answers.
+    pub opened_at: std::option::Option<NaiveDateTime>,
+    /// When the question closes on its own, if it was opened for a duration.
+    pub closes_at: std::option::Option<NaiveDateTime>,
+    /// When the presenter closed the question.
+    pub closed_at: std::option::Option<NaiveDateTime>,
 }
 
 /// Creates a new question.
@@ -50,6 +62,29 @@ pub struct NewQuestionJson {
     pub presentation_id: i32,
 }
 
+/// The structure of the body of JSON request for creating a question together with its options.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct NewQuestionWithOptionsJson {
+    pub title: String,
+    pub presentation_id: i32,
+    pub options: Vec<QuestionOptionJson>,
+}
+
+/// An option of a `NewQuestionWithOptionsJson` request.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct QuestionOptionJson {
+    pub data: String,
+    #[serde(default)]
+    pub is_correct: bool,
+}
+
+/// The ids of a question created together with its options, in the order of the options.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CreatedQuestion {
+    pub question_id: i32,
+    pub option_ids: Vec<i32>,
+}
+
 /// This defines an actor for retrieving question from database by id.
 #[derive(Queryable, Deserialize)]
 pub struct GetQuestion(pub i32);
@@ -67,9 +102,16 @@ pub struct GetQuestionByPresentation {
 #[table_name = "answers"]
 pub struct Answer {
     pub id: i32,
-    pub user_id: i32,
+    /// Empty for answers of anonymous participants.
+    pub user_id: std::option::Option<i32>,
     pub created: NaiveDateTime,
     pub option_id: i32,
+    /// Not exposed, so answers can't be traced back to a participant.
+    #[serde(skip)]
+    pub participant_id: std::option::Option<i32>,
+    /// The question of the option, kept so a participant's answers are unique per question.
+    #[serde(skip)]
+    pub question_id: i32,
 }
 
 /// Creates a new answer.
@@ -77,17 +119,37 @@ pub struct Answer {
 #[belongs_to(Option, foreign_key = "option_id")]
 #[table_name = "answers"]
 pub struct NewAnswer {
-    pub user_id: i32,
+    pub user_id: std::option::Option<i32>,
     pub created: NaiveDateTime,
     pub option_id: i32,
+    pub participant_id: std::option::Option<i32>,
+    pub question_id: i32,
 }
 
 impl NewAnswer {
-    pub fn new(user_id: i32, created: NaiveDateTime, option_id: i32) -> Self {
+    pub fn new(user_id: i32, created: NaiveDateTime, option_id: i32, question_id: i32) -> Self {
         NewAnswer {
-            user_id,
+            user_id: Some(user_id),
             created,
             option_id,
+            participant_id: None,
+            question_id,
+        }
+    }
+
+    /// Creates an answer of an anonymous participant.
+    pub fn from_participant(
+        participant_id: i32,
+        created: NaiveDateTime,
+        option_id: i32,
+        question_id: i32,
+    ) -> Self {
+        NewAnswer {
+            user_id: None,
+            created,
+            option_id,
+            participant_id: Some(participant_id),
+            question_id,
         }
     }
 }
@@ -113,6 +175,9 @@ pub struct Presentation {
     pub title: String,
     pub user_id: i32,
     pub created: NaiveDateTime,
+    /// Seconds a question stays open for when the presenter opens it without a duration. Without
+    /// it questions stay open until they are closed.
+    pub voting_duration: std::option::Option<i32>,
 }
 
 /// Creates a new presentation.
@@ -122,6 +187,7 @@ pub struct NewPresentation {
     title: String,
     user_id: i32,
     created: NaiveDateTime,
+    voting_duration: std::option::Option<i32>,
 }
 
 /// The structure of the body of JSON request for creating a new presentation.
@@ -134,11 +200,17 @@ pub struct PresentationInput {
 }
 
 impl NewPresentation {
-    pub fn new(title: String, user_id: i32, created: NaiveDateTime) -> Self {
+    pub fn new(
+        title: String,
+        user_id: i32,
+        created: NaiveDateTime,
+        voting_duration: std::option::Option<i32>,
+    ) -> Self {
         NewPresentation {
             title,
             user_id,
             created,
+            voting_duration,
         }
     }
 }
@@ -157,6 +229,7 @@ pub struct Option {
     pub user_id: i32,
     pub question_id: i32,
     pub created: NaiveDateTime,
+    pub is_correct: bool,
 }
 
 /// Creates a new option.
@@ -167,15 +240,23 @@ pub struct NewOption {
     pub user_id: i32,
     pub question_id: i32,
     pub created: NaiveDateTime,
+    pub is_correct: bool,
 }
 
 impl NewOption {
-    pub fn new(data: String, user_id: i32, question_id: i32, created: NaiveDateTime) -> Self {
+    pub fn new(
+        data: String,
+        user_id: i32,
+        question_id: i32,
+        created: NaiveDateTime,
+        is_correct: bool,
+    ) -> Self {
         NewOption {
             data,
             user_id,
             question_id,
             created,
+            is_correct,
         }
     }
 }
@@ -188,6 +269,9 @@ impl NewOption {
 pub struct NewOptionJson {
     pub data: String,
     pub question_id: i32,
+    /// Marks the correct answer of a quiz question.
+    #[serde(default)]
+    pub is_correct: bool,
 }
 
 /// Defines an actor to retrieve an option from database by id.
@@ -217,3 +301,249 @@ pub struct User {
     pub name: String,
     pub created: NaiveDateTime,
 }
+
+/// What a user is to a presentation.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Owns the presentation.
+    Presenter,
+    /// Answered questions of the presentation.
+    Audience,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct PresentationRole {
+    pub presentation_id: i32,
+    pub role: Role,
+}
+
+/// Password credentials of a user who owns presentations.
+///
+/// Users without a presenter account are audience members, who log in with just their name.
+#[derive(Queryable, Identifiable, Debug)]
+#[primary_key(user_id)]
+#[table_name = "presenter_accounts"]
+pub struct PresenterAccount {
+    pub user_id: i32,
+    pub password_hash: String,
+    /// Failed login attempts since the last successful login or lockout.
+    pub failed_logins: i32,
+    pub locked_until: std::option::Option<NaiveDateTime>,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "presenter_accounts"]
+pub struct NewPresenterAccount {
+    pub user_id: i32,
+    pub password_hash: String,
+    pub created: NaiveDateTime,
+}
+
+/// Links a user to their account at an external identity provider.
+#[derive(Queryable, Identifiable, Debug)]
+#[table_name = "user_identities"]
+pub struct UserIdentity {
+    pub id: i32,
+    pub user_id: i32,
+    /// Name of the provider, for example `github`.
+    pub provider: String,
+    /// Id of the account at the provider.
+    pub subject: String,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "user_identities"]
+pub struct NewUserIdentity {
+    pub user_id: i32,
+    pub provider: String,
+    pub subject: String,
+    pub created: NaiveDateTime,
+}
+
+/// An anonymous member of the audience of a presentation, identified by a signed device token
+/// instead of a user.
+#[derive(Queryable, Identifiable, Debug)]
+#[table_name = "participants"]
+pub struct Participant {
+    pub id: i32,
+    pub presentation_id: i32,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "participants"]
+pub struct NewParticipant {
+    pub presentation_id: i32,
+    pub created: NaiveDateTime,
+}
+
+/// A personal API token, accepted as `Authorization: Bearer <token>`.
+///
+/// Only the SHA-256 hash of the token is stored, the token itself is shown once on creation.
+#[derive(Queryable, Identifiable, Debug)]
+#[table_name = "api_tokens"]
+pub struct ApiToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    /// Comma separated scopes, for example `read,write`.
+    pub scopes: String,
+    pub created: NaiveDateTime,
+    pub last_used: std::option::Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "api_tokens"]
+pub struct NewApiToken {
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: String,
+    pub created: NaiveDateTime,
+}
+
+/// A login session, kept in the auth cookie by its token.
+///
+/// Only the SHA-256 hash of the token is stored, like for API tokens.
+#[derive(Queryable, Identifiable, Debug)]
+#[table_name = "sessions"]
+pub struct Session {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub created: NaiveDateTime,
+    /// Time of the latest request made with the session.
+    pub last_seen: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "sessions"]
+pub struct NewSession {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub created: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct ApiTokenInput {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterInput {
+    pub name: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordInput {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// A single answer together with the question, option and user it belongs to.
+///
+/// This is the row format of the presentation results export.
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+pub struct ExportRow {
+    pub answer_id: i32,
+    pub question_id: i32,
+    pub question_title: String,
+    pub option_id: i32,
+    pub option_data: String,
+    /// The user is empty for answers of anonymous participants, which have the participant
+    /// instead.
+    pub user_id: std::option::Option<i32>,
+    pub user_name: std::option::Option<String>,
+    pub participant_id: std::option::Option<i32>,
+    pub created: NaiveDateTime,
+}
+
+/// Describes why a field of a request body was rejected.
+///
+/// `path` points at the field, for example `questions[1].options[0].data`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FieldError {
+    pub path: String,
+    pub message: String,
+}
+
+/// The body of a response rejecting a request because of invalid fields.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FieldErrors {
+    pub errors: Vec<FieldError>,
+}
+
+/// Portable representation of a presentation, used for exporting and importing whole decks.
+///
+/// Questions and options are kept in the order they are presented in.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PresentationDocument {
+    pub version: u32,
+    pub title: String,
+    #[serde(default)]
+    pub settings: PresentationSettings,
+    #[serde(default)]
+    pub questions: Vec<QuestionDocument>,
+}
+
+/// Settings of a `PresentationDocument`.
+///
+/// Every setting is optional and unknown settings are ignored, so that settings can be added
+/// without a new version of the schema.
+#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+pub struct PresentationSettings {
+    /// Seconds a question stays open for when the presenter opens it without a duration.
+    #[serde(default)]
+    pub voting_duration: std::option::Option<u32>,
+}
+
+/// A question of a `PresentationDocument`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct QuestionDocument {
+    pub title: String,
+    #[serde(default)]
+    pub options: Vec<OptionDocument>,
+}
+
+/// An option of a `QuestionDocument`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OptionDocument {
+    pub data: String,
+    #[serde(default)]
+    pub is_correct: bool,
+}
+
+/// A presentation together with its questions and their options.
+#[derive(Serialize, Deserialize)]
+pub struct PresentationTree {
+    #[serde(flatten)]
+    pub presentation: Presentation,
+    pub questions: Vec<QuestionTree>,
+}
+
+/// A question of a `PresentationTree`.
+#[derive(Serialize, Deserialize)]
+pub struct QuestionTree {
+    #[serde(flatten)]
+    pub question: Questions,
+    pub options: Vec<OptionTree>,
+}
+
+/// An option of a `QuestionTree`, with the number of answers it received when requested.
+#[derive(Serialize, Deserialize)]
+pub struct OptionTree {
+    #[serde(flatten)]
+    pub option: Option,
+    #[serde(default, skip_serializing_if = "std::option::Option::is_none")]
+    pub votes: std::option::Option<i64>,
+}
@@ -0,0 +1,66 @@
+//! Pieces shared by the OAuth based login providers.
+
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{HttpMessage, HttpRequest};
+use diesel::result::Error as DieselError;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum OAuthError {
+    /// The provider could not be reached, or answered with an unexpected response.
+    Http(reqwest::Error),
+    /// The provider refused the request, for example because the code expired.
+    Provider(String),
+    /// The provider returned an ID token that did not pass validation.
+    InvalidToken(String),
+    Database(DieselError),
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OAuthError::Http(error) => write!(f, "{}", error),
+            OAuthError::Provider(message) => write!(f, "{}", message),
+            OAuthError::InvalidToken(message) => write!(f, "invalid ID token: {}", message),
+            OAuthError::Database(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for OAuthError {
+    fn from(error: reqwest::Error) -> Self {
+        OAuthError::Http(error)
+    }
+}
+
+impl From<DieselError> for OAuthError {
+    fn from(error: DieselError) -> Self {
+        OAuthError::Database(error)
+    }
+}
+
+/// Generates a random value for the `state` and `nonce` parameters.
+pub fn random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .collect()
+}
+
+/// Builds the cookie holding the values of an authorization request until the provider
+/// redirects back to `path`.
+pub fn state_cookie(name: &'static str, path: &'static str, value: String) -> Cookie<'static> {
+    Cookie::build(name, value)
+        .path(path)
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .finish()
+}
+
+/// Returns the value of a state cookie, if the request has one.
+pub fn state_cookie_value(request: &HttpRequest, name: &str) -> Option<String> {
+    request.cookie(name).map(|cookie| cookie.value().to_owned())
+}
@@ -0,0 +1,471 @@
+//! Login with a generic OpenID Connect provider, using the authorization code flow.
+//!
+//! The endpoints and signing keys of the provider are discovered from its issuer URL, and the
+//! ID token returned by the token endpoint is validated before the user is logged in.
+
+use crate::accounts::link_identity;
+use crate::config::{Config, OidcConfig};
+use crate::models::User;
+use crate::oauth::{self, OAuthError};
+use crate::session::{session_user_id, start_session};
+use crate::DbPool;
+
+use actix_identity::Identity;
+use actix_web::cookie::Cookie;
+use actix_web::error::BlockingError;
+use actix_web::http::header;
+use actix_web::web::{block, Data, Query};
+use actix_web::{get, Error, HttpRequest, HttpResponse};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+use serde::Deserialize;
+use std::sync::RwLock;
+
+/// Name of the provider in `user_identities`.
+const PROVIDER: &str = "oidc";
+
+/// Cookie holding the `state` and `nonce` of an authorization request until the provider
+/// redirects back.
+const STATE_COOKIE: &str = "oidc-oauth-state";
+
+/// Clock skew tolerated when checking the expiry of ID tokens, in seconds.
+const LEEWAY_SECONDS: u64 = 60;
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// The fields of the discovery document the application needs.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// Signing keys of the provider.
+#[derive(Deserialize, Clone, Default, Debug)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// A JSON Web Key. Only RSA keys are used, the fields of other key types are ignored.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Jwk {
+    pub kty: String,
+    pub kid: Option<String>,
+    pub alg: Option<String>,
+    #[serde(rename = "use")]
+    pub key_use: Option<String>,
+    pub n: Option<String>,
+    pub e: Option<String>,
+}
+
+impl Jwks {
+    /// Finds the RSA signing key an ID token refers to. Tokens without a `kid` can only be
+    /// verified when the provider publishes a single key.
+    pub fn find(&self, kid: Option<&str>, algorithm: Algorithm) -> Option<&Jwk> {
+        let mut candidates = self.keys.iter().filter(|key| {
+            key.kty == "RSA"
+                && key.n.is_some()
+                && key.e.is_some()
+                && key
+                    .key_use
+                    .as_deref()
+                    .map_or(true, |key_use| key_use == "sig")
+                && key
+                    .alg
+                    .as_deref()
+                    .map_or(true, |alg| alg.parse::<Algorithm>().ok() == Some(algorithm))
+        });
+
+        match kid {
+            Some(kid) => candidates.find(|key| key.kid.as_deref() == Some(kid)),
+            None => {
+                let key = candidates.next();
+                match candidates.next() {
+                    Some(_) => None,
+                    None => key,
+                }
+            }
+        }
+    }
+}
+
+/// Claims of an ID token. `iss`, `aud` and `exp` are checked while decoding.
+#[derive(Deserialize, Debug)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub azp: Option<String>,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl IdTokenClaims {
+    /// Name of the user created for the identity, the first claim the provider shared.
+    pub fn preferred_name(&self) -> &str {
+        self.preferred_username
+            .as_deref()
+            .or(self.name.as_deref())
+            .or(self.email.as_deref())
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or(&self.sub)
+    }
+}
+
+/// Response of the token endpoint.
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+fn invalid_token(message: &str) -> OAuthError {
+    OAuthError::InvalidToken(String::from(message))
+}
+
+impl From<jsonwebtoken::errors::Error> for OAuthError {
+    fn from(error: jsonwebtoken::errors::Error) -> Self {
+        match error.kind() {
+            ErrorKind::InvalidSignature => invalid_token("signature does not match"),
+            ErrorKind::ExpiredSignature => invalid_token("token expired"),
+            ErrorKind::ImmatureSignature => invalid_token("token not valid yet"),
+            ErrorKind::InvalidIssuer => invalid_token("wrong issuer"),
+            ErrorKind::InvalidAudience => invalid_token("wrong audience"),
+            ErrorKind::InvalidAlgorithm => invalid_token("unexpected signing algorithm"),
+            _ => OAuthError::InvalidToken(error.to_string()),
+        }
+    }
+}
+
+/// Validates an ID token against the signing keys of the provider, and returns its claims.
+///
+/// The token must be signed with an RSA key of `jwks`, be issued by `issuer` to `client_id`,
+/// not be expired, and carry the `nonce` of the authorization request.
+pub fn validate_id_token(
+    token: &str,
+    jwks: &Jwks,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+) -> Result<IdTokenClaims, OAuthError> {
+    let header = decode_header(token)?;
+    match header.alg {
+        Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 => {}
+        _ => return Err(invalid_token("unsupported signing algorithm")),
+    }
+
+    let key = jwks
+        .find(header.kid.as_deref(), header.alg)
+        .ok_or_else(|| invalid_token("unknown signing key"))?;
+    let key = DecodingKey::from_rsa_components(
+        key.n.as_deref().unwrap_or_default(),
+        key.e.as_deref().unwrap_or_default(),
+    );
+
+    let mut validation = Validation::new(header.alg);
+    validation.leeway = LEEWAY_SECONDS;
+    validation.iss = Some(issuer.to_owned());
+    validation.set_audience(&[client_id]);
+
+    let claims = decode::<IdTokenClaims>(token, &key, &validation)?.claims;
+
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(invalid_token("nonce does not match"));
+    }
+    if claims.azp.as_deref().map_or(false, |azp| azp != client_id) {
+        return Err(invalid_token("issued to another client"));
+    }
+
+    Ok(claims)
+}
+
+/// Loads the discovery document of an issuer, which must name itself as the issuer.
+pub fn discover(issuer_url: &str) -> Result<ProviderMetadata, OAuthError> {
+    let metadata: ProviderMetadata = reqwest::Client::new()
+        .get(&format!("{}/.well-known/openid-configuration", issuer_url))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    if metadata.issuer.trim_end_matches('/') != issuer_url {
+        return Err(OAuthError::Provider(format!(
+            "Discovery document names {} as the issuer.",
+            metadata.issuer
+        )));
+    }
+
+    Ok(metadata)
+}
+
+/// Loads the signing keys of a provider.
+pub fn fetch_jwks(jwks_uri: &str) -> Result<Jwks, OAuthError> {
+    let jwks = reqwest::Client::new()
+        .get(jwks_uri)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    Ok(jwks)
+}
+
+/// A configured OpenID Connect provider, shared by every worker.
+///
+/// The discovery document is loaded on first use, so the server starts while the provider is
+/// unreachable. The signing keys are loaded again whenever a token refers to an unknown key,
+/// which picks up keys rotated by the provider.
+pub struct OidcProvider {
+    pub config: OidcConfig,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    jwks: RwLock<Jwks>,
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcConfig) -> Self {
+        OidcProvider {
+            config,
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(Jwks::default()),
+        }
+    }
+
+    /// Returns the discovery document, loading it if needed.
+    pub fn metadata(&self) -> Result<ProviderMetadata, OAuthError> {
+        if let Some(metadata) = &*self.metadata.read().unwrap() {
+            return Ok(metadata.clone());
+        }
+
+        let metadata = discover(&self.config.issuer_url)?;
+        *self.metadata.write().unwrap() = Some(metadata.clone());
+
+        Ok(metadata)
+    }
+
+    /// Builds the URL the user is sent to, to log in at the provider.
+    pub fn authorize_url(&self, state: &str, nonce: &str) -> Result<Url, OAuthError> {
+        let metadata = self.metadata()?;
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|_| OAuthError::Provider(String::from("Invalid authorization endpoint.")))?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce);
+
+        Ok(url)
+    }
+
+    /// Validates an ID token, loading the signing keys again if it refers to an unknown key.
+    pub fn verify_id_token(&self, token: &str, nonce: &str) -> Result<IdTokenClaims, OAuthError> {
+        let metadata = self.metadata()?;
+        let header = decode_header(token)?;
+
+        let known_key = self
+            .jwks
+            .read()
+            .unwrap()
+            .find(header.kid.as_deref(), header.alg)
+            .is_some();
+        if !known_key {
+            *self.jwks.write().unwrap() = fetch_jwks(&metadata.jwks_uri)?;
+        }
+
+        validate_id_token(
+            token,
+            &self.jwks.read().unwrap(),
+            &metadata.issuer,
+            &self.config.client_id,
+            nonce,
+        )
+    }
+
+    /// Exchanges the code the provider redirected back with, and returns the claims of the
+    /// validated ID token.
+    pub fn exchange_code(&self, code: &str, nonce: &str) -> Result<IdTokenClaims, OAuthError> {
+        let metadata = self.metadata()?;
+
+        let response: TokenResponse = reqwest::Client::new()
+            .post(&metadata.token_endpoint)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+            ])
+            .send()?
+            .json()?;
+
+        match response.id_token {
+            Some(token) => self.verify_id_token(&token, nonce),
+            None => Err(OAuthError::Provider(
+                response
+                    .error_description
+                    .or(response.error)
+                    .unwrap_or_else(|| String::from("No ID token in the response.")),
+            )),
+        }
+    }
+
+    /// Completes the login once the provider redirected back, and returns the linked user.
+    pub fn login_with_code(
+        &self,
+        code: &str,
+        nonce: &str,
+        current_user: Option<i32>,
+        pool: &DbPool,
+    ) -> Result<User, OAuthError> {
+        let claims = self.exchange_code(code, nonce)?;
+        let connection = pool.get().expect("Unable to get database connection.");
+
+        let user = link_identity(
+            PROVIDER,
+            &claims.sub,
+            claims.preferred_name(),
+            current_user,
+            &connection,
+        )?;
+
+        Ok(user)
+    }
+}
+
+/// The cookie holds the state and the nonce, separated by a dot.
+fn state_cookie(state: &str, nonce: &str) -> Cookie<'static> {
+    oauth::state_cookie(STATE_COOKIE, "/auth/oidc", format!("{}.{}", state, nonce))
+}
+
+/// Starts a login with the OpenID Connect provider, by redirecting to its authorization page.
+///
+/// `/auth/oidc` GET
+///
+/// Response: 302 Found, redirecting to the provider.
+///
+/// Response (OpenID Connect login not configured): 404 Not Found
+///
+/// Response (the discovery document could not be loaded): 502 Bad Gateway
+#[get("/auth/oidc")]
+pub async fn authorize(provider: Option<Data<OidcProvider>>) -> Result<HttpResponse, Error> {
+    let provider = match provider {
+        Some(provider) => provider,
+        None => return Ok(HttpResponse::NotFound().body("OpenID Connect login is not configured.")),
+    };
+
+    let state = oauth::random_token();
+    let nonce = oauth::random_token();
+    let cookie = state_cookie(&state, &nonce);
+
+    let url = block(move || provider.authorize_url(&state, &nonce))
+        .await
+        .map_err(|error| {
+            HttpResponse::BadGateway().body(format!("OpenID Connect login failed: {}", error))
+        })?;
+
+    Ok(HttpResponse::Found()
+        .header(header::LOCATION, url.as_str())
+        .cookie(cookie)
+        .finish())
+}
+
+/// Completes a login with the OpenID Connect provider.
+///
+/// The provider redirects here after the user logged in. The identity is linked to the logged in
+/// user if that is a presenter, and to a new user named after the `preferred_username`, `name` or
+/// `email` claim otherwise. Audience members logged in with just a name get a new user.
+///
+/// `/auth/oidc/callback?code=<code>&state=<state>` GET
+///
+/// Response: 302 Found, redirecting to `FRONT_END_BASE_URL`. Without a front end the user is
+/// returned instead.
+/// ```json
+/// {
+///     "id": 9,
+///     "name": "jane",
+///     "created": "2020-11-18T03:30:22"
+/// }
+/// ```
+///
+/// Response (missing code or state mismatch): 400 Bad Request
+///
+/// Response (the provider refused the code, or the ID token is invalid): 502 Bad Gateway
+#[get("/auth/oidc/callback")]
+pub async fn callback(
+    request: HttpRequest,
+    query: Query<CallbackQuery>,
+    config: Data<Config>,
+    provider: Option<Data<OidcProvider>>,
+    pool: Data<DbPool>,
+    id: Identity,
+) -> Result<HttpResponse, Error> {
+    let provider = match provider {
+        Some(provider) => provider,
+        None => return Ok(HttpResponse::NotFound().body("OpenID Connect login is not configured.")),
+    };
+    let query = query.into_inner();
+
+    let cookie = oauth::state_cookie_value(&request, STATE_COOKIE).unwrap_or_default();
+    let mut parts = cookie.splitn(2, '.');
+    let expected_state = parts.next().filter(|state| !state.is_empty());
+    let nonce = parts.next().unwrap_or_default().to_owned();
+    if expected_state.is_none() || expected_state != query.state.as_deref() || nonce.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("Invalid OAuth state."));
+    }
+
+    if let Some(error) = query.error_description.or(query.error) {
+        return Ok(
+            HttpResponse::BadRequest().body(format!("OpenID Connect login failed: {}", error))
+        );
+    }
+
+    let code = match query.code {
+        Some(code) => code,
+        None => return Ok(HttpResponse::BadRequest().body("Missing OAuth code.")),
+    };
+
+    let timeouts = config.session_timeouts;
+    let current_user = session_user_id(id.identity(), pool.get_ref(), timeouts).await?;
+    let pool = pool.get_ref().clone();
+
+    let (user, session_token) = block(move || {
+        let user = provider.login_with_code(&code, &nonce, current_user, &pool)?;
+        let connection = pool.get().expect("Unable to get database connection.");
+        let session_token = start_session(user.id, timeouts, &connection)?;
+
+        Ok((user, session_token))
+    })
+    .await
+    .map_err(|error| match error {
+        BlockingError::Error(OAuthError::Database(_)) | BlockingError::Canceled => {
+            HttpResponse::InternalServerError().body("Could not log in with OpenID Connect.")
+        }
+        BlockingError::Error(error) => {
+            HttpResponse::BadGateway().body(format!("OpenID Connect login failed: {}", error))
+        }
+    })?;
+
+    id.remember(session_token);
+
+    Ok(match &config.front_end_base_url {
+        Some(front_end_base_url) => HttpResponse::Found()
+            .header(header::LOCATION, front_end_base_url.as_str())
+            .del_cookie(&state_cookie("", ""))
+            .finish(),
+        None => HttpResponse::Ok()
+            .del_cookie(&state_cookie("", ""))
+            .json(user),
+    })
+}
@@ -1,8 +1,9 @@
-use crate::models::{NewOption, NewOptionJson, Option};
-use crate::DbPool;
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::models::{FieldError, NewOption, NewOptionJson, Option};
+use crate::parents::{attach_error_response, AttachError, Parent};
+use crate::validation::{validate, Validate, SHORT_TEXT};
+use crate::{DbConnection, DbPool};
 
-use crate::session::get_user_by_name;
-use actix_identity::Identity;
 use actix_web::web::{block, Data, Json, Path};
 use actix_web::Error;
 use actix_web::HttpResponse;
@@ -11,20 +12,19 @@ use chrono::Utc;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
 use diesel::result::Error as DieselError;
-use diesel::MysqlConnection;
 
-fn new_option(record: NewOption, connection: &MysqlConnection) -> Result<(), DieselError> {
-    use crate::schema::options::dsl::options;
-
-    diesel::insert_into(options)
-        .values(&record)
-        .execute(connection)
-        .expect("Error saving the option.");
+impl Validate for NewOptionJson {
+    fn check(&self, errors: &mut Vec<FieldError>) {
+        SHORT_TEXT.check("data", &self.data, errors);
+    }
+}
 
-    Ok(())
+/// Saves a new option and returns its id.
+pub fn new_option(record: NewOption, connection: &DbConnection) -> Result<i32, DieselError> {
+    insert_returning_id!(options, &record, connection)
 }
 
-fn get_option(option_id: i32, connection: &MysqlConnection) -> Result<Option, DieselError> {
+fn get_option(option_id: i32, connection: &DbConnection) -> Result<Option, DieselError> {
     use crate::schema::options::dsl::{id, options};
 
     options.filter(id.eq(option_id)).first::<Option>(connection)
@@ -32,12 +32,15 @@ fn get_option(option_id: i32, connection: &MysqlConnection) -> Result<Option, Di
 
 fn get_option_by_question_id(
     id: i32,
-    connection: &MysqlConnection,
+    connection: &DbConnection,
 ) -> Result<Vec<Option>, DieselError> {
     use crate::schema::options;
-    use crate::schema::options::dsl::question_id;
+    use crate::schema::options::dsl::{id as option_id, question_id};
 
-    let options = options::table.filter(question_id.eq(id)).load(connection)?;
+    let options = options::table
+        .filter(question_id.eq(id))
+        .order_by(option_id.asc())
+        .load(connection)?;
 
     Ok(options)
 }
@@ -52,45 +55,64 @@ fn get_option_by_question_id(
 ///
 /// auth-cookie: <cookie_value>
 ///
-/// Body:
+/// Body (`is_correct` is optional):
 /// ```json
 /// {
 ///    "data": "Option 1",
 ///    "question_id": 1,
+///    "is_correct": false,
 /// }
 /// ```
 ///
 /// Response: 200 OK
+///
+/// Response (question of someone else's presentation): 403 Forbidden
+///
+/// Response (unknown question): 404 Not Found
+///
+/// Response (invalid input): 422 Unprocessable Entity
+/// ```json
+/// {
+///    "errors": [
+///        {
+///            "path": "data",
+///            "message": "must not be empty"
+///        }
+///    ]
+/// }
+/// ```
 #[post("/options")]
 pub async fn post(
     pool: Data<DbPool>,
     data: Json<NewOptionJson>,
-    id: Identity,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
-    if let Some(name) = id.identity() {
-        let connection = pool.get().expect("Could not get database connection.");
-
-        let user = block(move || get_user_by_name(name, &connection))
-            .await
-            .map_err(|_| {
-                HttpResponse::InternalServerError()
-                    .body("Something went wrong while retrieving the user.")
-            })?;
-
-        let input = data.into_inner();
-        let now = Utc::now();
-        // TODO: Try not to retrieve the connection again.
-        let connection = pool.get().expect("unable to get database connection.");
-        let record = NewOption::new(input.data, user.id, input.question_id, now.naive_utc());
-
-        block(move || new_option(record, &connection))
-            .await
-            .map_err(|_| HttpResponse::InternalServerError().finish())?;
-
-        Ok(HttpResponse::Ok().finish())
-    } else {
-        Ok(HttpResponse::BadRequest().body("Could not identify user."))
-    }
+    user.require_scope(Scope::Write)?;
+
+    let input = validate(data.into_inner())?;
+    let now = Utc::now();
+    let uid = user.id();
+    let parent = Parent::Question(input.question_id);
+    let connection = pool.get().expect("unable to get database connection.");
+    let record = NewOption::new(
+        input.data,
+        uid,
+        input.question_id,
+        now.naive_utc(),
+        input.is_correct,
+    );
+
+    block(move || {
+        connection.transaction::<_, AttachError, _>(|| {
+            parent.require_presenter(uid, &connection)?;
+
+            Ok(new_option(record, &connection)?)
+        })
+    })
+    .await
+    .map_err(attach_error_response)?;
+
+    Ok(HttpResponse::Ok().finish())
 }
 
 /// `/options/{id}` GET
@@ -102,7 +124,8 @@ pub async fn post(
 ///    "data": "Option 1",
 ///    "user_id": 9,
 ///    "question_id": 1,
-///    "created": "2019-06-19T03:40:50"
+///    "created": "2019-06-19T03:40:50",
+///    "is_correct": false
 /// }
 /// ```
 #[get("/options/{id}")]
@@ -129,7 +152,8 @@ pub async fn get(pool: Data<DbPool>, data: Path<i32>) -> Result<HttpResponse, Er
 ///         "data": "Option 1",
 ///         "user_id": 9,
 ///         "question_id": 1,
-///         "created": "2019-06-19T03:40:50"
+///         "created": "2019-06-19T03:40:50",
+///         "is_correct": false
 ///     }
 /// ]
 /// ```
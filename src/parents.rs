@@ -0,0 +1,131 @@
+//! Checks of the parent a new row is attached to.
+//!
+//! Questions and options are only added by the presenter of their presentation. Answers can be
+//! given by every user, and by the participants of the presentation the option belongs to, while
+//! the question is open.
+
+use crate::accounts::is_presenter;
+use crate::DbConnection;
+
+use actix_web::error::BlockingError;
+use actix_web::HttpResponse;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+
+/// A row new rows are attached to, by its id.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Parent {
+    Presentation(i32),
+    Question(i32),
+    Option(i32),
+}
+
+/// Why a row could not be attached to its parent.
+#[derive(Debug)]
+pub enum AttachError {
+    NotFound(Parent),
+    /// The parent belongs to a presentation the caller may not add to.
+    Forbidden(Parent),
+    /// The parent does not take new rows right now, like a question outside its voting window.
+    Closed(Parent),
+    Database(DieselError),
+}
+
+impl From<DieselError> for AttachError {
+    fn from(error: DieselError) -> Self {
+        AttachError::Database(error)
+    }
+}
+
+impl Parent {
+    pub fn name(self) -> &'static str {
+        match self {
+            Parent::Presentation(_) => "presentation",
+            Parent::Question(_) => "question",
+            Parent::Option(_) => "option",
+        }
+    }
+
+    /// Returns the id and the presenter of the presentation the parent belongs to, or `None` if
+    /// the parent does not exist.
+    pub fn presentation(
+        self,
+        connection: &DbConnection,
+    ) -> Result<Option<(i32, i32)>, DieselError> {
+        use crate::schema::{options, presentations, questions};
+
+        let presentation = (presentations::id, presentations::user_id);
+
+        match self {
+            Parent::Presentation(id) => presentations::table
+                .find(id)
+                .select(presentation)
+                .first(connection)
+                .optional(),
+            Parent::Question(id) => questions::table
+                .inner_join(presentations::table)
+                .filter(questions::id.eq(id))
+                .select(presentation)
+                .first(connection)
+                .optional(),
+            Parent::Option(id) => options::table
+                .inner_join(questions::table.inner_join(presentations::table))
+                .filter(options::id.eq(id))
+                .select(presentation)
+                .first(connection)
+                .optional(),
+        }
+    }
+
+    /// Fails unless the parent exists, and returns the id of its presentation.
+    pub fn require_exists(self, connection: &DbConnection) -> Result<i32, AttachError> {
+        match self.presentation(connection)? {
+            Some((presentation_id, _)) => Ok(presentation_id),
+            None => Err(AttachError::NotFound(self)),
+        }
+    }
+
+    /// Fails unless the parent exists and belongs to a presentation of the user.
+    ///
+    /// The user also has to be a presenter, since anyone can log in by the name of an owner without
+    /// a password or linked login.
+    pub fn require_presenter(self, uid: i32, connection: &DbConnection) -> Result<(), AttachError> {
+        match self.presentation(connection)? {
+            Some((_, presenter)) if presenter == uid && is_presenter(uid, connection)? => Ok(()),
+            Some(_) => Err(AttachError::Forbidden(self)),
+            None => Err(AttachError::NotFound(self)),
+        }
+    }
+}
+
+impl AttachError {
+    /// A short description, for the body of a response.
+    pub fn message(&self) -> String {
+        match self {
+            AttachError::NotFound(parent) => format!("Could not find the {}.", parent.name()),
+            AttachError::Forbidden(parent) => {
+                format!("Not allowed to add to the {}.", parent.name())
+            }
+            AttachError::Closed(parent) => format!("The {} is closed.", parent.name()),
+            AttachError::Database(_) => String::from("Something went wrong."),
+        }
+    }
+}
+
+/// The response of a blocking call that failed to attach a row: 404 Not Found for a missing
+/// parent, 403 Forbidden for a parent the caller may not add to, and 409 Conflict for a closed
+/// one.
+pub fn attach_error_response(error: BlockingError<AttachError>) -> HttpResponse {
+    match error {
+        BlockingError::Error(error @ AttachError::NotFound(_)) => {
+            HttpResponse::NotFound().body(error.message())
+        }
+        BlockingError::Error(error @ AttachError::Forbidden(_)) => {
+            HttpResponse::Forbidden().body(error.message())
+        }
+        BlockingError::Error(error @ AttachError::Closed(_)) => {
+            HttpResponse::Conflict().body(error.message())
+        }
+        _ => HttpResponse::InternalServerError().finish(),
+    }
+}
@@ -0,0 +1,200 @@
+//! Anonymous participation in a presentation, without logging in.
+//!
+//! Joining a presentation creates a participant and returns a device token signed by the server.
+//! The token is sent as the `X-Device-Token` header, or with the `AnswersCreate` web socket
+//! event, to answer as the participant. Participants answer every question once.
+
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::models::{NewParticipant, Participant};
+use crate::presentations::get_presentation;
+use crate::{DbConnection, DbPool};
+
+use actix_web::dev::Payload;
+use actix_web::error::BlockingError;
+use actix_web::web::{block, Data, Path};
+use actix_web::{post, Error, FromRequest, HttpRequest, HttpResponse};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use hmac::{Hmac, Mac, NewMac};
+use serde::Serialize;
+use sha2::Sha256;
+
+/// Header carrying the device token of a participant.
+pub const DEVICE_TOKEN_HEADER: &str = "X-Device-Token";
+
+/// Signs and verifies device tokens.
+///
+/// Tokens are signed with the first key and verified with any of them, so they survive a
+/// rotation of the cookie secret the keys are derived from.
+#[derive(Clone)]
+pub struct DeviceTokens {
+    keys: Vec<Vec<u8>>,
+}
+
+impl DeviceTokens {
+    /// Panics if `keys` is empty.
+    pub fn new(keys: Vec<Vec<u8>>) -> Self {
+        assert!(
+            !keys.is_empty(),
+            "At least one device token key is required."
+        );
+
+        DeviceTokens { keys }
+    }
+
+    fn mac(key: &[u8], participant_id: i32) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts keys of any length.");
+        mac.update(format!("participant:{}", participant_id).as_bytes());
+
+        mac
+    }
+
+    /// Returns the token of a participant, `<participant id>.<signature>`.
+    pub fn sign(&self, participant_id: i32) -> String {
+        let signature: String = Self::mac(&self.keys[0], participant_id)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        format!("{}.{}", participant_id, signature)
+    }
+
+    /// Returns the participant a token was signed for, if the signature is valid.
+    pub fn verify(&self, token: &str) -> Option<i32> {
+        let mut parts = token.splitn(2, '.');
+        let participant_id = parts.next()?.parse::<i32>().ok()?;
+        let signature = parts.next()?;
+
+        if signature.len() % 2 != 0 || !signature.is_ascii() {
+            return None;
+        }
+        let signature = (0..signature.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&signature[index..index + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+
+        let valid = self
+            .keys
+            .iter()
+            .any(|key| Self::mac(key, participant_id).verify(&signature).is_ok());
+
+        if valid {
+            Some(participant_id)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn create_participant(
+    presentation_id: i32,
+    connection: &DbConnection,
+) -> Result<Participant, DieselError> {
+    use crate::schema::participants::dsl::participants;
+
+    // Fails with `NotFound` for unknown presentations.
+    get_presentation(presentation_id, connection)?;
+
+    let record = NewParticipant {
+        presentation_id,
+        created: Utc::now().naive_utc(),
+    };
+    let participant_id = insert_returning_id!(participants, &record, connection)?;
+
+    participants.find(participant_id).first(connection)
+}
+
+/// Who gives an answer: a user, by the auth cookie or an API token, or an anonymous participant
+/// by their device token.
+pub enum Voter {
+    User(AuthenticatedUser),
+    Participant(i32),
+}
+
+impl FromRequest for Voter {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
+    type Config = ();
+
+    fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let device_token = request
+            .headers()
+            .get(DEVICE_TOKEN_HEADER)
+            .map(|value| value.to_str().unwrap_or_default().to_owned());
+
+        match device_token {
+            Some(token) => {
+                let participant_id = request
+                    .app_data::<Data<DeviceTokens>>()
+                    .and_then(|device_tokens| device_tokens.verify(&token));
+
+                async move {
+                    participant_id.map(Voter::Participant).ok_or_else(|| {
+                        HttpResponse::Unauthorized()
+                            .body("Invalid device token.")
+                            .into()
+                    })
+                }
+                .boxed_local()
+            }
+            None => AuthenticatedUser::from_request(request, payload)
+                .map(|user| {
+                    let user = user?;
+                    user.require_scope(Scope::Write)?;
+
+                    Ok(Voter::User(user))
+                })
+                .boxed_local(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct JoinResponse {
+    pub participant_id: i32,
+    pub device_token: String,
+}
+
+/// Joins a presentation as an anonymous participant.
+///
+/// Keep the device token, and send it as the `X-Device-Token` header to answer questions.
+///
+/// `/presentations/{id}/join` POST
+///
+/// Response:
+/// ```json
+/// {
+///    "participant_id": 5,
+///    "device_token": "5.6b1d0c3f9a8e7d2c4b5a69788f0e1d2c3b4a59687f6e5d4c3b2a190817263544"
+/// }
+/// ```
+///
+/// Response (no such presentation): 404 Not Found
+#[post("/presentations/{id}/join")]
+pub async fn join(
+    pool: Data<DbPool>,
+    device_tokens: Data<DeviceTokens>,
+    data: Path<i32>,
+) -> Result<HttpResponse, Error> {
+    let connection = pool.get().expect("Unable to get database connection.");
+    let presentation_id = data.into_inner();
+
+    let participant = block(move || create_participant(presentation_id, &connection))
+        .await
+        .map_err(|error| match error {
+            BlockingError::Error(DieselError::NotFound) => {
+                HttpResponse::NotFound().body("Could not find the presentation.")
+            }
+            _ => HttpResponse::InternalServerError().finish(),
+        })?;
+
+    Ok(HttpResponse::Ok().json(JoinResponse {
+        participant_id: participant.id,
+        device_token: device_tokens.sign(participant.id),
+    }))
+}
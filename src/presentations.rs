@@ -1,33 +1,48 @@
-use crate::models::{NewPresentation, Presentation, PresentationInput};
-use crate::DbPool;
+use crate::accounts::require_presenter_account;
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::models::{
+    FieldError, NewPresentation, Option, OptionTree, Presentation, PresentationInput,
+    PresentationRole, PresentationTree, QuestionTree, Role,
+};
+use crate::questions::get_question_by_presentation;
+use crate::validation::{validate, Validate, SHORT_TEXT};
+use crate::{DbConnection, DbPool};
 
-use crate::session::get_user_by_name;
-use actix_identity::Identity;
-use actix_web::web::{block, Data, Json, Path};
+use actix_web::error::BlockingError;
+use actix_web::web::{block, Data, Json, Path, Query};
 use actix_web::Error;
 use actix_web::HttpResponse;
 use actix_web::{get, post};
 use chrono::Utc;
 use diesel::prelude::*;
 use diesel::result::Error as DieselError;
+use serde::Deserialize;
+use std::collections::HashMap;
 
-fn new_presentation(
-    data: NewPresentation,
-    connection: &MysqlConnection,
-) -> Result<(), DieselError> {
-    use crate::schema::presentations::dsl::presentations;
+impl Validate for PresentationInput {
+    fn check(&self, errors: &mut Vec<FieldError>) {
+        SHORT_TEXT.check("title", &self.title, errors);
+    }
+}
 
-    diesel::insert_into(presentations)
-        .values(data)
-        .execute(connection)
-        .expect("Error saving the presentation");
+#[derive(Deserialize)]
+pub struct TreeQuery {
+    /// Whether to count the answers of every option.
+    #[serde(default)]
+    pub votes: bool,
+}
 
-    Ok(())
+/// Saves a new presentation and returns its id.
+pub fn new_presentation(
+    data: NewPresentation,
+    connection: &DbConnection,
+) -> Result<i32, DieselError> {
+    insert_returning_id!(presentations, data, connection)
 }
 
-fn get_presentation(
+pub fn get_presentation(
     presentation_id: i32,
-    connection: &MysqlConnection,
+    connection: &DbConnection,
 ) -> Result<Presentation, DieselError> {
     use crate::schema::presentations::dsl::{id, presentations};
 
@@ -38,6 +53,149 @@ fn get_presentation(
     Ok(result)
 }
 
+/// Returns the roles of a user in presentations, ordered by presentation id.
+///
+/// A user is the presenter of the presentations they own, and audience of the ones they answered
+/// questions of.
+pub fn get_presentation_roles(
+    uid: i32,
+    connection: &DbConnection,
+) -> Result<Vec<PresentationRole>, DieselError> {
+    use crate::schema::{answers, options, presentations, questions};
+
+    let owned: Vec<i32> = presentations::table
+        .filter(presentations::user_id.eq(uid))
+        .select(presentations::id)
+        .load(connection)?;
+    let answered: Vec<i32> = answers::table
+        .inner_join(options::table.inner_join(questions::table))
+        .filter(answers::user_id.eq(uid))
+        .select(questions::presentation_id)
+        .distinct()
+        .load(connection)?;
+
+    let mut roles: Vec<PresentationRole> = owned
+        .iter()
+        .map(|presentation_id| PresentationRole {
+            presentation_id: *presentation_id,
+            role: Role::Presenter,
+        })
+        .chain(
+            answered
+                .into_iter()
+                .filter(|presentation_id| !owned.contains(presentation_id))
+                .map(|presentation_id| PresentationRole {
+                    presentation_id,
+                    role: Role::Audience,
+                }),
+        )
+        .collect();
+    roles.sort_by_key(|role| role.presentation_id);
+
+    Ok(roles)
+}
+
+/// Makes `user_id` the owner of a presentation, along with its questions and options.
+///
+/// Answers stay with the users who gave them.
+pub fn transfer_presentation(
+    presentation_id: i32,
+    user_id: i32,
+    connection: &DbConnection,
+) -> Result<(), DieselError> {
+    use crate::schema::{options, presentations, questions};
+
+    connection.transaction(|| {
+        diesel::update(presentations::table.find(presentation_id))
+            .set(presentations::user_id.eq(user_id))
+            .execute(connection)?;
+
+        let question_ids = questions::table
+            .filter(questions::presentation_id.eq(presentation_id))
+            .select(questions::id);
+
+        diesel::update(options::table.filter(options::question_id.eq_any(question_ids)))
+            .set(options::user_id.eq(user_id))
+            .execute(connection)?;
+
+        diesel::update(questions::table.filter(questions::presentation_id.eq(presentation_id)))
+            .set(questions::user_id.eq(user_id))
+            .execute(connection)?;
+
+        Ok(())
+    })
+}
+
+/// Counts the answers of each of the given options.
+///
+/// Options without answers are left out of the result.
+pub fn count_votes_by_option(
+    option_ids: &[i32],
+    connection: &DbConnection,
+) -> Result<HashMap<i32, i64>, DieselError> {
+    use crate::schema::answers::dsl::{answers, option_id};
+    use diesel::dsl::sql;
+    use diesel::sql_types::BigInt;
+
+    let counts: Vec<(i32, i64)> = answers
+        .filter(option_id.eq_any(option_ids))
+        .group_by(option_id)
+        // Diesel does not allow mixing aggregate and plain columns in a select clause yet.
+        .select((option_id, sql::<BigInt>("COUNT(*)")))
+        .load(connection)?;
+
+    Ok(counts.into_iter().collect())
+}
+
+/// Loads a presentation with its ordered questions, each with its ordered options.
+///
+/// The tree is built with a constant number of queries, whatever the number of questions. When
+/// `with_votes` is set the options also carry the number of answers they received.
+pub fn get_presentation_tree(
+    presentation_id: i32,
+    with_votes: bool,
+    connection: &DbConnection,
+) -> Result<PresentationTree, DieselError> {
+    use crate::schema::options::dsl::id;
+
+    let presentation = get_presentation(presentation_id, connection)?;
+    let questions = get_question_by_presentation(presentation_id, connection)?;
+    let options: Vec<Option> = Option::belonging_to(&questions)
+        .order_by(id.asc())
+        .load(connection)?;
+
+    let votes = if with_votes {
+        let option_ids: Vec<i32> = options.iter().map(|option| option.id).collect();
+        Some(count_votes_by_option(&option_ids, connection)?)
+    } else {
+        None
+    };
+
+    let options = options.grouped_by(&questions);
+
+    let questions = questions
+        .into_iter()
+        .zip(options)
+        .map(|(question, options)| QuestionTree {
+            question,
+            options: options
+                .into_iter()
+                .map(|option| OptionTree {
+                    votes: votes
+                        .as_ref()
+                        .map(|votes| votes.get(&option.id).cloned().unwrap_or(0)),
+                    option,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(PresentationTree {
+        presentation,
+        questions,
+    })
+}
+
 /// `/presentations` POST
 ///
 /// Headers:
@@ -56,37 +214,39 @@ fn get_presentation(
 /// ```
 ///
 /// Response: 200 OK
+///
+/// Response (not a presenter): 403 Forbidden
+///
+/// Response (invalid input): 422 Unprocessable Entity
+/// ```json
+/// {
+///    "errors": [
+///        {
+///            "path": "title",
+///            "message": "must not be empty"
+///        }
+///    ]
+/// }
+/// ```
 #[post("/presentations")]
 pub async fn post(
     pool: Data<DbPool>,
     data: Json<PresentationInput>,
-    id: Identity,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
-    if let Some(user_name) = id.identity() {
-        let connection = pool.get().expect("Could not get database connection.");
-
-        let user = block(move || get_user_by_name(user_name, &connection))
-            .await
-            .map_err(|_| {
-                HttpResponse::InternalServerError().body("Could not locate user by name.")
-            })?;
-
-        let now = Utc::now();
-        let input = data.into_inner();
-        let record = NewPresentation::new(input.title, user.id, now.naive_utc());
-        // TODO: Try not to retrieve the connection again.
-        let connection = pool.get().expect("Unable to get database connection.");
-
-        block(move || new_presentation(record, &connection))
-            .await
-            .map_err(|_| {
-                HttpResponse::InternalServerError().body("Could not create presentation.")
-            })?;
-
-        Ok(HttpResponse::Ok().finish())
-    } else {
-        Ok(HttpResponse::BadRequest().body("Could not identify the user."))
-    }
+    user.require_scope(Scope::Write)?;
+    require_presenter_account(user.id(), &pool).await?;
+
+    let input = validate(data.into_inner())?;
+    let now = Utc::now();
+    let record = NewPresentation::new(input.title, user.id(), now.naive_utc(), None);
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    block(move || new_presentation(record, &connection))
+        .await
+        .map_err(|_| HttpResponse::InternalServerError().body("Could not create presentation."))?;
+
+    Ok(HttpResponse::Ok().finish())
 }
 
 /// `/presentations/{id}` GET
@@ -97,7 +257,8 @@ pub async fn post(
 ///    "id": 47,
 ///    "title": "New Presentation",
 ///    "user_id": 7,
-///    "created": "2019-11-01T14:30:30"
+///    "created": "2019-11-01T14:30:30",
+///    "voting_duration": null
 /// }
 /// ```
 #[get("/presentations/{id}")]
@@ -111,3 +272,62 @@ pub async fn get(pool: Data<DbPool>, data: Path<i32>) -> Result<HttpResponse, Er
 
     Ok(HttpResponse::Ok().json(result))
 }
+
+/// Returns a presentation with its ordered questions and their options.
+///
+/// `/presentations/{id}/tree?votes=true` GET
+///
+/// The `votes` parameter is optional, when it is set every option has the number of answers it
+/// received.
+///
+/// Response:
+/// ```json
+/// {
+///    "id": 47,
+///    "title": "New Presentation",
+///    "user_id": 7,
+///    "created": "2019-11-01T14:30:30",
+///    "voting_duration": null,
+///    "questions": [
+///        {
+///            "id": 23,
+///            "title": "New Question",
+///            "created": "2019-11-01T14:30:30",
+///            "presentation_id": 47,
+///            "user_id": 7,
+///            "options": [
+///                {
+///                    "id": 12,
+///                    "data": "Option 1",
+///                    "user_id": 7,
+///                    "question_id": 23,
+///                    "created": "2019-11-01T14:30:30",
+///                    "is_correct": false,
+///                    "votes": 3
+///                }
+///            ]
+///        }
+///    ]
+/// }
+/// ```
+#[get("/presentations/{id}/tree")]
+pub async fn get_tree(
+    pool: Data<DbPool>,
+    data: Path<i32>,
+    query: Query<TreeQuery>,
+) -> Result<HttpResponse, Error> {
+    let connection = pool.get().expect("Unable to get database connection.");
+    let presentation_id = data.into_inner();
+    let with_votes = query.into_inner().votes;
+
+    let result = block(move || get_presentation_tree(presentation_id, with_votes, &connection))
+        .await
+        .map_err(|error| match error {
+            BlockingError::Error(DieselError::NotFound) => {
+                HttpResponse::NotFound().body("Could not find the presentation.")
+            }
+            _ => HttpResponse::InternalServerError().finish(),
+        })?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
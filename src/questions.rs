@@ -1,27 +1,78 @@
-use crate::models::{NewQuestion, NewQuestionJson, Questions};
-use crate::DbPool;
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::models::{
+    CreatedQuestion, FieldError, NewOption, NewQuestion, NewQuestionJson,
+    NewQuestionWithOptionsJson, QuestionOptionJson, Questions,
+};
+use crate::options::new_option;
+use crate::parents::{attach_error_response, AttachError, Parent};
+use crate::validation::{validate, Validate, SHORT_TEXT};
+use crate::{DbConnection, DbPool};
 
-use crate::session::get_user_by_name;
-use actix_identity::Identity;
 use actix_web::web::{block, Data, Json, Path};
 use actix_web::{get, post};
 use actix_web::{Error, HttpResponse};
-use chrono::Utc;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use diesel::result::Error as DieselError;
 
-fn new_question(input: NewQuestion, connection: &MysqlConnection) -> Result<(), DieselError> {
-    use crate::schema::questions::dsl::questions;
+impl Validate for NewQuestionJson {
+    fn check(&self, errors: &mut Vec<FieldError>) {
+        SHORT_TEXT.check("title", &self.title, errors);
+    }
+}
+
+impl Validate for NewQuestionWithOptionsJson {
+    fn check(&self, errors: &mut Vec<FieldError>) {
+        SHORT_TEXT.check("title", &self.title, errors);
+
+        for (index, option) in self.options.iter().enumerate() {
+            SHORT_TEXT.check(&format!("options[{}].data", index), &option.data, errors);
+        }
+    }
+}
+
+/// Saves a new question and returns its id.
+pub fn new_question(input: NewQuestion, connection: &DbConnection) -> Result<i32, DieselError> {
+    insert_returning_id!(questions, input, connection)
+}
+
+/// Saves a new question and its options in a single transaction.
+///
+/// The options are owned by the author of the question, and their ids are returned in the order
+/// they were given.
+pub fn new_question_with_options(
+    question: NewQuestion,
+    options: Vec<QuestionOptionJson>,
+    connection: &DbConnection,
+) -> Result<CreatedQuestion, DieselError> {
+    connection.transaction(|| {
+        let user_id = question.user_id;
+        let created = question.created;
+        let question_id = new_question(question, connection)?;
+
+        let option_ids = options
+            .into_iter()
+            .map(|option| {
+                let record = NewOption::new(
+                    option.data,
+                    user_id,
+                    question_id,
+                    created,
+                    option.is_correct,
+                );
 
-    diesel::insert_into(questions)
-        .values(input)
-        .execute(connection)
-        .expect("Error saving the question");
+                new_option(record, connection)
+            })
+            .collect::<Result<Vec<i32>, DieselError>>()?;
 
-    Ok(())
+        Ok(CreatedQuestion {
+            question_id,
+            option_ids,
+        })
+    })
 }
 
-fn get_question(question_id: i32, connection: &MysqlConnection) -> Result<Questions, DieselError> {
+pub fn get_question(question_id: i32, connection: &DbConnection) -> Result<Questions, DieselError> {
     use crate::schema::questions::dsl::{id, questions};
 
     let result: Questions = questions.filter(id.eq(question_id)).first(connection)?;
@@ -29,16 +80,103 @@ fn get_question(question_id: i32, connection: &MysqlConnection) -> Result<Questi
     Ok(result)
 }
 
+/// Whether a question takes answers at `now`.
+pub fn is_open(question: &Questions, now: NaiveDateTime) -> bool {
+    match question.opened_at {
+        Some(opened_at) => {
+            opened_at <= now
+                && question.closed_at.is_none()
+                && question.closes_at.map_or(true, |closes_at| now < closes_at)
+        }
+        None => false,
+    }
+}
+
+/// Returns the voting duration of the presentation of a question, in seconds.
+pub fn get_voting_duration(
+    question_id: i32,
+    connection: &DbConnection,
+) -> Result<Option<i32>, DieselError> {
+    use crate::schema::{presentations, questions};
+
+    questions::table
+        .inner_join(presentations::table)
+        .filter(questions::id.eq(question_id))
+        .select(presentations::voting_duration)
+        .first(connection)
+}
+
+/// Opens a question for answers, until it is closed or `duration` has passed, and returns it.
+pub fn open_question(
+    question_id: i32,
+    duration: Option<Duration>,
+    now: NaiveDateTime,
+    connection: &DbConnection,
+) -> Result<Questions, DieselError> {
+    use crate::schema::questions::dsl::{closed_at, closes_at, opened_at, questions};
+
+    diesel::update(questions.find(question_id))
+        .set((
+            opened_at.eq(now),
+            closes_at.eq(duration.map(|duration| now + duration)),
+            closed_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(connection)?;
+
+    get_question(question_id, connection)
+}
+
+/// Closes a question for answers and returns it. Questions closed already keep the time they
+/// were closed at.
+pub fn close_question(
+    question_id: i32,
+    now: NaiveDateTime,
+    connection: &DbConnection,
+) -> Result<Questions, DieselError> {
+    use crate::schema::questions::dsl::{closed_at, questions};
+
+    let question = get_question(question_id, connection)?;
+    if is_open(&question, now) {
+        diesel::update(questions.find(question_id))
+            .set(closed_at.eq(now))
+            .execute(connection)?;
+    }
+
+    get_question(question_id, connection)
+}
+
+/// Closes a question `duration` from now, and opens it unless it is open, then returns it.
+pub fn start_timer(
+    question_id: i32,
+    duration: Duration,
+    now: NaiveDateTime,
+    connection: &DbConnection,
+) -> Result<Questions, DieselError> {
+    use crate::schema::questions::dsl::{closes_at, questions};
+
+    let question = get_question(question_id, connection)?;
+    if !is_open(&question, now) {
+        return open_question(question_id, Some(duration), now, connection);
+    }
+
+    diesel::update(questions.find(question_id))
+        .set(closes_at.eq(now + duration))
+        .execute(connection)?;
+
+    get_question(question_id, connection)
+}
+
 pub fn get_question_by_presentation(
     presentation_id: i32,
-    connection: &MysqlConnection,
+    connection: &DbConnection,
 ) -> Result<Vec<Questions>, DieselError> {
     use crate::schema::questions;
-    use crate::schema::questions::dsl::{created, presentation_id as pid};
+    use crate::schema::questions::dsl::{created, id, presentation_id as pid};
 
     let questions: Vec<Questions> = questions::table
         .filter(pid.eq(presentation_id))
         .order_by(created.asc())
+        .then_order_by(id.asc())
         .load(connection)?;
 
     Ok(questions)
@@ -63,35 +201,131 @@ pub fn get_question_by_presentation(
 /// ```
 ///
 /// Response: 200 OK
+///
+/// Response (presentation of someone else): 403 Forbidden
+///
+/// Response (unknown presentation): 404 Not Found
+///
+/// Response (invalid input): 422 Unprocessable Entity
+/// ```json
+/// {
+///    "errors": [
+///        {
+///            "path": "title",
+///            "message": "must not be empty"
+///        }
+///    ]
+/// }
+/// ```
 #[post("/questions")]
 pub async fn post(
     pool: Data<DbPool>,
     data: Json<NewQuestionJson>,
-    id: Identity,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
-    if let Some(name) = id.identity() {
-        let connection = pool.get().expect("Unable to get database connection.");
-
-        let user = block(move || get_user_by_name(name, &connection))
-            .await
-            .map_err(|_| HttpResponse::InternalServerError().body("Could not find user."))?;
-
-        let now = Utc::now();
-        let input = data.into_inner();
-        let record = NewQuestion::new(input.title, now.naive_utc(), input.presentation_id, user.id);
-        // TODO: Try not to retrieve the connection again.
-        let connection = pool.get().expect("Unable to get database connection.");
-
-        block(move || new_question(record, &connection))
-            .await
-            .map_err(|_| {
-                HttpResponse::InternalServerError().body("Could not create new question.")
-            })?;
-
-        Ok(HttpResponse::Ok().finish())
-    } else {
-        return Ok(HttpResponse::BadRequest().body("could not identify the user."));
-    }
+    user.require_scope(Scope::Write)?;
+
+    let input = validate(data.into_inner())?;
+    let now = Utc::now();
+    let uid = user.id();
+    let parent = Parent::Presentation(input.presentation_id);
+    let record = NewQuestion::new(input.title, now.naive_utc(), input.presentation_id, uid);
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    block(move || {
+        connection.transaction::<_, AttachError, _>(|| {
+            parent.require_presenter(uid, &connection)?;
+
+            Ok(new_question(record, &connection)?)
+        })
+    })
+    .await
+    .map_err(attach_error_response)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Creates a question together with its options.
+///
+/// Either everything is created or nothing is.
+///
+/// `/questions-options` POST
+///
+/// Headers:
+///
+/// Content type: application/json
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Body (`is_correct` is optional):
+/// ```json
+/// {
+///    "title": "New Question",
+///    "presentation_id": 1,
+///    "options": [
+///        {
+///            "data": "Option 1",
+///            "is_correct": true
+///        },
+///        {
+///            "data": "Option 2"
+///        }
+///    ]
+/// }
+/// ```
+///
+/// Response:
+/// ```json
+/// {
+///    "question_id": 23,
+///    "option_ids": [12, 13]
+/// }
+/// ```
+///
+/// Response (presentation of someone else): 403 Forbidden
+///
+/// Response (unknown presentation): 404 Not Found
+///
+/// Response (invalid input): 422 Unprocessable Entity
+/// ```json
+/// {
+///    "errors": [
+///        {
+///            "path": "options[1].data",
+///            "message": "must not be empty"
+///        }
+///    ]
+/// }
+/// ```
+#[post("/questions-options")]
+pub async fn post_with_options(
+    pool: Data<DbPool>,
+    data: Json<NewQuestionWithOptionsJson>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(Scope::Write)?;
+
+    let input = validate(data.into_inner())?;
+    let now = Utc::now().naive_utc();
+    let uid = user.id();
+    let parent = Parent::Presentation(input.presentation_id);
+    let record = NewQuestion::new(input.title, now, input.presentation_id, uid);
+    let options = input.options;
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    let created = block(move || {
+        connection.transaction::<_, AttachError, _>(|| {
+            parent.require_presenter(uid, &connection)?;
+
+            Ok(new_question_with_options(record, options, &connection)?)
+        })
+    })
+    .await
+    .map_err(attach_error_response)?;
+
+    Ok(HttpResponse::Ok().json(created))
 }
 
 /// `/questions/{id}` GET
@@ -104,6 +338,9 @@ pub async fn post(
 ///    "created": "2019-11-01T14:30:30",
 ///    "presentation_id": 3,
 ///    "user_id": 7,
+///    "opened_at": "2019-11-01T14:35:00",
+///    "closes_at": "2019-11-01T14:35:30",
+///    "closed_at": null
 /// }
 /// ```
 #[get("/questions/{id}")]
@@ -131,6 +368,9 @@ pub async fn get(pool: Data<DbPool>, data: Path<i32>) -> Result<HttpResponse, Er
 ///         "created": "2019-11-01T14:30:30",
 ///         "presentation_id": 3,
 ///         "user_id": 7,
+///         "opened_at": "2019-11-01T14:35:00",
+///         "closes_at": "2019-11-01T14:35:30",
+///         "closed_at": null
 ///     }
 /// ]
 /// ```
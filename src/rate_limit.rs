@@ -0,0 +1,288 @@
+//! Rate limiting of requests, with a token bucket per client and route group.
+//!
+//! Logged in users are limited by their session and anonymous participants by their device token,
+//! so an audience behind a single address is not limited together. Every other request is limited
+//! by its peer address, or by the client address a trusted reverse proxy forwards.
+
+use crate::config::{RateLimit, RateLimits};
+use crate::participants::{DeviceTokens, DEVICE_TOKEN_HEADER};
+
+use actix_identity::RequestIdentity;
+use actix_service::{Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::{Error, HttpResponse};
+use futures::future::{ok, Either, Ready};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// How often buckets that filled up again are dropped.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A token bucket, holding up to `RateLimit::requests` tokens.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// Returns a full bucket.
+    pub fn new(limit: RateLimit, now: Instant) -> Self {
+        TokenBucket {
+            tokens: f64::from(limit.requests),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let rate = f64::from(limit.requests) / f64::from(limit.seconds);
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * rate).min(f64::from(limit.requests));
+        self.updated = now;
+    }
+
+    /// Takes a token, or returns how long it takes until one is available.
+    pub fn take(&mut self, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let rate = f64::from(limit.requests) / f64::from(limit.seconds);
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+
+    /// Whether the bucket is full again, so dropping it changes nothing.
+    fn is_full(&self, limit: RateLimit, now: Instant) -> bool {
+        let mut bucket = *self;
+        bucket.refill(limit, now);
+
+        bucket.tokens >= f64::from(limit.requests)
+    }
+}
+
+/// Routes sharing a rate limit.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RouteGroup {
+    /// Logging in and registering, which create users and sessions.
+    Auth,
+    /// Joining presentations, which creates participants. Joiners have no device token yet, so
+    /// they are limited by address, and a whole audience may share one.
+    Join,
+    Write,
+    Read,
+}
+
+impl RouteGroup {
+    pub fn of(method: &Method, path: &str) -> RouteGroup {
+        let auth = path.starts_with("/auth/")
+            || ["/login", "/register", "/change-password"].contains(&path);
+
+        if auth {
+            RouteGroup::Auth
+        } else if path.starts_with("/presentations/") && path.ends_with("/join") {
+            RouteGroup::Join
+        } else if method == Method::GET || method == Method::HEAD {
+            RouteGroup::Read
+        } else {
+            RouteGroup::Write
+        }
+    }
+
+    fn limit(self, limits: &RateLimits) -> Option<RateLimit> {
+        match self {
+            RouteGroup::Auth => limits.auth,
+            RouteGroup::Join => limits.join,
+            RouteGroup::Write => limits.write,
+            RouteGroup::Read => limits.read,
+        }
+    }
+}
+
+/// Who a request is counted against.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Client {
+    /// The session token of the auth cookie.
+    Session(String),
+    Participant(i32),
+    Address(IpAddr),
+    /// Requests without a peer address, which only happens in tests.
+    Unknown,
+}
+
+struct Buckets {
+    buckets: HashMap<(RouteGroup, Client), TokenBucket>,
+    pruned: Instant,
+}
+
+/// Middleware answering 429 Too Many Requests, with a `Retry-After` header, once a client used
+/// up the requests of a route group.
+///
+/// The buckets are shared by the clones, so one limiter created before the server keeps the
+/// counts of every worker. It must be wrapped inside the `IdentityService`, which makes the
+/// session of a request known.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    trust_forwarded_for: bool,
+    device_tokens: DeviceTokens,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// With `trust_forwarded_for`, anonymous clients are told apart by the address a reverse proxy
+    /// puts in `Forwarded` or `X-Forwarded-For`. Only set it when the proxy overwrites that header,
+    /// otherwise clients pick their own address.
+    pub fn new(limits: RateLimits, trust_forwarded_for: bool, device_tokens: DeviceTokens) -> Self {
+        RateLimiter {
+            limits,
+            trust_forwarded_for,
+            device_tokens,
+            buckets: Arc::new(Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                pruned: Instant::now(),
+            })),
+        }
+    }
+
+    fn client(&self, request: &ServiceRequest) -> Client {
+        if let Some(session) = request.get_identity() {
+            return Client::Session(session);
+        }
+
+        let participant = request
+            .headers()
+            .get(DEVICE_TOKEN_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|token| self.device_tokens.verify(token));
+        if let Some(participant_id) = participant {
+            return Client::Participant(participant_id);
+        }
+
+        let forwarded = if self.trust_forwarded_for {
+            request
+                .connection_info()
+                .realip_remote_addr()
+                .and_then(parse_address)
+        } else {
+            None
+        };
+
+        match forwarded.or_else(|| request.peer_addr().map(|address| address.ip())) {
+            Some(address) => Client::Address(address),
+            None => Client::Unknown,
+        }
+    }
+
+    /// Takes a token of the client's bucket for the route group of the request, or returns how
+    /// long the client has to wait.
+    fn check(&self, request: &ServiceRequest) -> Result<(), Duration> {
+        let group = RouteGroup::of(request.method(), request.path());
+        let limit = match group.limit(&self.limits) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let client = self.client(request);
+        let now = Instant::now();
+
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("The rate limit lock is poisoned.");
+        if now.saturating_duration_since(buckets.pruned) >= PRUNE_INTERVAL {
+            let limits = self.limits;
+            buckets.buckets.retain(|(group, _), bucket| {
+                group
+                    .limit(&limits)
+                    .map_or(false, |limit| !bucket.is_full(limit, now))
+            });
+            buckets.pruned = now;
+        }
+
+        buckets
+            .buckets
+            .entry((group, client))
+            .or_insert_with(|| TokenBucket::new(limit, now))
+            .take(limit, now)
+    }
+}
+
+/// Parses a forwarded client address, which may carry a port.
+fn parse_address(address: &str) -> Option<IpAddr> {
+    address
+        .parse::<IpAddr>()
+        .or_else(|_| address.parse::<SocketAddr>().map(|address| address.ip()))
+        .ok()
+}
+
+/// Rounds a wait up to whole seconds, for `Retry-After`.
+pub fn retry_after_seconds(wait: Duration) -> u64 {
+    let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+
+    seconds.max(1)
+}
+
+/// The 429 Too Many Requests response, asking to retry after `wait`.
+pub fn too_many_requests(wait: Duration) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .header(header::RETRY_AFTER, retry_after_seconds(wait).to_string())
+        .body("Too many requests, try again later.")
+}
+
+impl<S, B> Transform<S> for RateLimiter
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RateLimitMiddleware {
+            service,
+            limiter: self.clone(),
+        })
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Service for RateLimitMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: ServiceRequest) -> Self::Future {
+        match self.limiter.check(&request) {
+            Ok(()) => Either::Left(self.service.call(request)),
+            Err(wait) => Either::Right(ok(
+                request.into_response(too_many_requests(wait).into_body())
+            )),
+        }
+    }
+}
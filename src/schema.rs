@@ -1,9 +1,23 @@
 table! {
     answers (id) {
         id -> Integer,
-        user_id -> Integer,
+        user_id -> Nullable<Integer>,
         created -> Timestamp,
         option_id -> Integer,
+        participant_id -> Nullable<Integer>,
+        question_id -> Integer,
+    }
+}
+
+table! {
+    api_tokens (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Varchar,
+        token_hash -> Varchar,
+        scopes -> Varchar,
+        created -> Timestamp,
+        last_used -> Nullable<Timestamp>,
     }
 }
 
@@ -14,6 +28,15 @@ table! {
         user_id -> Integer,
         question_id -> Integer,
         created -> Timestamp,
+        is_correct -> Bool,
+    }
+}
+
+table! {
+    participants (id) {
+        id -> Integer,
+        presentation_id -> Integer,
+        created -> Timestamp,
     }
 }
 
@@ -23,6 +46,17 @@ table! {
         title -> Varchar,
         user_id -> Integer,
         created -> Timestamp,
+        voting_duration -> Nullable<Integer>,
+    }
+}
+
+table! {
+    presenter_accounts (user_id) {
+        user_id -> Integer,
+        password_hash -> Varchar,
+        failed_logins -> Integer,
+        locked_until -> Nullable<Timestamp>,
+        created -> Timestamp,
     }
 }
 
@@ -33,6 +67,29 @@ table! {
         created -> Timestamp,
         presentation_id -> Integer,
         user_id -> Integer,
+        opened_at -> Nullable<Timestamp>,
+        closes_at -> Nullable<Timestamp>,
+        closed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Integer,
+        user_id -> Integer,
+        token_hash -> Varchar,
+        created -> Timestamp,
+        last_seen -> Timestamp,
+    }
+}
+
+table! {
+    user_identities (id) {
+        id -> Integer,
+        user_id -> Integer,
+        provider -> Varchar,
+        subject -> Varchar,
+        created -> Timestamp,
     }
 }
 
@@ -44,10 +101,29 @@ table! {
     }
 }
 
+joinable!(answers -> options (option_id));
+joinable!(answers -> participants (participant_id));
+joinable!(answers -> users (user_id));
+joinable!(api_tokens -> users (user_id));
+joinable!(options -> questions (question_id));
+joinable!(options -> users (user_id));
+joinable!(participants -> presentations (presentation_id));
+joinable!(presentations -> users (user_id));
+joinable!(presenter_accounts -> users (user_id));
+joinable!(questions -> presentations (presentation_id));
+joinable!(questions -> users (user_id));
+joinable!(sessions -> users (user_id));
+joinable!(user_identities -> users (user_id));
+
 allow_tables_to_appear_in_same_query!(
     answers,
+    api_tokens,
     options,
+    participants,
     presentations,
+    presenter_accounts,
     questions,
+    sessions,
+    user_identities,
     users,
 );
@@ -1,33 +1,213 @@
-use crate::models::User;
-use crate::DbPool;
+use crate::accounts::{authenticate, login_response, LoginOutcome};
+use crate::auth::{hash_api_token, AuthenticatedUser, Scope};
+use crate::config::{Config, SessionTimeouts};
+use crate::models::{FieldError, NewSession, PresentationRole, Session, User};
+use crate::presentations::get_presentation_roles;
+use crate::validation::{validate, Validate, SHORT_TEXT};
+use crate::{DbConnection, DbPool};
 use actix_identity::Identity;
 use actix_web::post;
 use actix_web::web::{block, Data, Json};
 use actix_web::{get, HttpResponse};
-use chrono::Utc;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use diesel::result::Error as DieselError;
-use diesel::MysqlConnection;
-use serde::Deserialize;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Number of random characters of a session token.
+const SESSION_TOKEN_LENGTH: usize = 40;
 
 #[derive(Deserialize)]
 pub struct UserLogin {
     name: String,
+    /// Required for presenter accounts, must be left out by audience members.
+    #[serde(default)]
+    password: Option<String>,
+}
+
+impl Validate for UserLogin {
+    fn check(&self, errors: &mut Vec<FieldError>) {
+        SHORT_TEXT.check("name", &self.name, errors);
+    }
 }
 
-/// Logs out the user.
+/// Whether a session has ended, because it was idle or has been open for too long.
+pub fn is_expired(session: &Session, timeouts: SessionTimeouts, now: NaiveDateTime) -> bool {
+    now - session.last_seen >= Duration::minutes(timeouts.idle.into())
+        || now - session.created >= Duration::minutes(timeouts.absolute.into())
+}
+
+/// Starts a session of a user, and returns its token to keep in the auth cookie.
+///
+/// Expired sessions of every user are deleted on the way.
+pub fn start_session(
+    uid: i32,
+    timeouts: SessionTimeouts,
+    connection: &DbConnection,
+) -> Result<String, DieselError> {
+    use crate::schema::sessions::dsl::{created, last_seen, sessions};
+
+    let now = Utc::now().naive_utc();
+    diesel::delete(
+        sessions.filter(
+            last_seen
+                .le(now - Duration::minutes(timeouts.idle.into()))
+                .or(created.le(now - Duration::minutes(timeouts.absolute.into()))),
+        ),
+    )
+    .execute(connection)?;
+
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SESSION_TOKEN_LENGTH)
+        .collect();
+    let record = NewSession {
+        user_id: uid,
+        token_hash: hash_api_token(&token),
+        created: now,
+        last_seen: now,
+    };
+    diesel::insert_into(sessions)
+        .values(&record)
+        .execute(connection)?;
+
+    Ok(token)
+}
+
+/// Returns the user of a session that has not expired, recording that it was used. An expired
+/// session is deleted.
+pub fn resume_session(
+    token: &str,
+    timeouts: SessionTimeouts,
+    connection: &DbConnection,
+) -> Result<Option<i32>, DieselError> {
+    use crate::schema::sessions::dsl::{last_seen, sessions, token_hash};
+
+    let session = sessions
+        .filter(token_hash.eq(hash_api_token(token)))
+        .first::<Session>(connection)
+        .optional()?;
+    let now = Utc::now().naive_utc();
+
+    match session {
+        Some(session) if !is_expired(&session, timeouts, now) => {
+            diesel::update(sessions.find(session.id))
+                .set(last_seen.eq(now))
+                .execute(connection)?;
+
+            Ok(Some(session.user_id))
+        }
+        Some(session) => {
+            diesel::delete(sessions.find(session.id)).execute(connection)?;
+
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Ends the session of a token.
+pub fn end_session(token: &str, connection: &DbConnection) -> Result<(), DieselError> {
+    use crate::schema::sessions::dsl::{sessions, token_hash};
+
+    diesel::delete(sessions.filter(token_hash.eq(hash_api_token(token)))).execute(connection)?;
+
+    Ok(())
+}
+
+/// Ends every session of a user, and returns how many there were.
+pub fn end_user_sessions(uid: i32, connection: &DbConnection) -> Result<usize, DieselError> {
+    use crate::schema::sessions::dsl::{sessions, user_id};
+
+    diesel::delete(sessions.filter(user_id.eq(uid))).execute(connection)
+}
+
+/// Returns the user of the session in the auth cookie, if there is one and it is still valid.
+pub async fn session_user_id(
+    identity: Option<String>,
+    pool: &DbPool,
+    timeouts: SessionTimeouts,
+) -> Result<Option<i32>, actix_web::Error> {
+    let token = match identity {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    let connection = pool.get().expect("Unable to get database connection.");
+
+    let uid = block(move || resume_session(&token, timeouts, &connection))
+        .await
+        .map_err(|_| HttpResponse::InternalServerError().finish())?;
+
+    Ok(uid)
+}
+
+/// Logs out the user, ending the session of the auth cookie.
 ///
 /// `/logout` GET
 ///
 /// Response: 200 OK
 #[get("/logout")]
-pub fn logout(id: Identity) -> HttpResponse {
+pub async fn logout(id: Identity, pool: Data<DbPool>) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(token) = id.identity() {
+        let connection = pool.get().expect("Unable to get database connection.");
+
+        block(move || end_session(&token, &connection))
+            .await
+            .map_err(|_| HttpResponse::InternalServerError().finish())?;
+    }
+
+    id.forget();
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Serialize)]
+pub struct LogoutEverywhereResponse {
+    /// Number of sessions ended, including the current one.
+    pub sessions: usize,
+}
+
+/// Logs out the user on every device, ending all of their sessions. API tokens stay valid.
+///
+/// `/logout-everywhere` POST
+///
+/// Cookies:
+///
+/// auth-cookie: <cookie_value>
+///
+/// Response:
+/// ```json
+/// {
+///    "sessions": 3
+/// }
+/// ```
+///
+/// Response (API token without the `admin` scope): 403 Forbidden
+#[post("/logout-everywhere")]
+pub async fn logout_everywhere(
+    id: Identity,
+    pool: Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, actix_web::Error> {
+    user.require_scope(Scope::Admin)?;
+
+    let connection = pool.get().expect("Unable to get database connection.");
+    let uid = user.id();
+
+    let ended = block(move || end_user_sessions(uid, &connection))
+        .await
+        .map_err(|_| HttpResponse::InternalServerError().finish())?;
+
     id.forget();
-    HttpResponse::Ok().finish()
+    Ok(HttpResponse::Ok().json(LogoutEverywhereResponse { sessions: ended }))
 }
 
 /// Logs in a user.
 ///
+/// Audience members log in with just their name, and are created on their first login. Presenter
+/// accounts need their password, and are locked for a while after too many failed logins.
+///
 /// `/login` POST
 ///
 /// Headers:
@@ -39,6 +219,7 @@ pub fn logout(id: Identity) -> HttpResponse {
 /// ```json
 /// {
 ///    "name": "agent 42",
+///    "password": "correct horse battery staple"
 /// }
 /// ```
 ///
@@ -50,28 +231,151 @@ pub fn logout(id: Identity) -> HttpResponse {
 ///     "created": "2020-11-18T03:30:22"
 /// }
 /// ```
+///
+/// Response (wrong password): 401 Unauthorized
+///
+/// Response (locked account): 429 Too Many Requests, with a `Retry-After` header
+///
+/// Response (invalid input): 422 Unprocessable Entity
+/// ```json
+/// {
+///    "errors": [
+///        {
+///            "path": "name",
+///            "message": "must not start or end with whitespace"
+///        }
+///    ]
+/// }
+/// ```
 #[post("/login")]
 pub async fn login(
     data: Json<UserLogin>,
     pool: Data<DbPool>,
+    config: Data<Config>,
     id: Identity,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let input = data.into_inner();
+    let input = validate(data.into_inner())?;
     let connection = pool.get().expect("Could not get database connection");
-    let user_name = input.name.clone();
+    let timeouts = config.session_timeouts;
+
+    let (outcome, session_token) = block(move || {
+        let outcome = authenticate(
+            input.name,
+            input.password,
+            Utc::now().naive_utc(),
+            &connection,
+        )?;
+        let session_token = match &outcome {
+            LoginOutcome::LoggedIn(user) => Some(start_session(user.id, timeouts, &connection)?),
+            _ => None,
+        };
+
+        Ok((outcome, session_token))
+    })
+    .await
+    .map_err(|_: actix_web::error::BlockingError<DieselError>| {
+        HttpResponse::InternalServerError().body("Something went wrong during login.")
+    })?;
+
+    Ok(login_response(outcome, session_token, &id))
+}
+
+/// Who made a request, as returned by `/me`.
+#[derive(Serialize, Debug)]
+pub struct MeResponse {
+    pub authenticated: bool,
+    pub user: Option<User>,
+    pub roles: Vec<PresentationRole>,
+}
+
+/// The response of `/is-logged-in`, which has a `result` field besides those of `/me`.
+#[derive(Serialize, Debug)]
+pub struct IsLoggedInResponse {
+    pub result: bool,
+    #[serde(flatten)]
+    pub me: MeResponse,
+}
 
-    let user: User = block(move || get_user_by_name(user_name, &connection))
+async fn me_response(
+    user: Option<AuthenticatedUser>,
+    pool: Data<DbPool>,
+) -> Result<MeResponse, actix_web::Error> {
+    let user = match user {
+        Some(user) => user.user,
+        None => {
+            return Ok(MeResponse {
+                authenticated: false,
+                user: None,
+                roles: vec![],
+            })
+        }
+    };
+    let connection = pool.get().expect("Unable to get database connection.");
+    let uid = user.id;
+
+    let roles = block(move || get_presentation_roles(uid, &connection))
         .await
-        .map_err(|_| HttpResponse::InternalServerError().body("Something went wrong during login."))
-        .expect("Could not locate user by name during login.");
+        .map_err(|_| HttpResponse::InternalServerError().finish())?;
 
-    id.remember(user.id.to_string());
-    Ok(HttpResponse::Ok().json(user))
+    Ok(MeResponse {
+        authenticated: true,
+        user: Some(user),
+        roles,
+    })
 }
 
-/// Checks if the request is authenticated.
+/// Returns the user the request is authenticated as, by the auth cookie or an API token, and
+/// their roles in presentations.
+///
+/// `/me` GET
 ///
-/// If the authenticating cookie is not passed, or is not valid, then it will return false.
+/// Cookies (optional):
+///
+/// auth-cookie: <cookie_value>
+///
+/// Response:
+/// ```json
+/// {
+///    "authenticated": true,
+///    "user": {
+///        "id": 7,
+///        "name": "agent 42",
+///        "created": "2019-11-01T14:30:30"
+///    },
+///    "roles": [
+///        {
+///            "presentation_id": 3,
+///            "role": "presenter"
+///        },
+///        {
+///            "presentation_id": 5,
+///            "role": "audience"
+///        }
+///    ]
+/// }
+/// ```
+///
+/// Response (not authenticated):
+/// ```json
+/// {
+///    "authenticated": false,
+///    "user": null,
+///    "roles": []
+/// }
+/// ```
+#[get("/me")]
+pub async fn me(
+    user: Option<AuthenticatedUser>,
+    pool: Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    Ok(HttpResponse::Ok().json(me_response(user, pool).await?))
+}
+
+/// Checks if the request is authenticated. Kept for older clients, `/me` returns the same with
+/// `authenticated` instead of `result`.
+///
+/// If the authenticating cookie is not passed, is not valid, or its session has expired or was
+/// logged out, then it will return false.
 ///
 /// `/is-logged-in` GET
 ///
@@ -79,25 +383,30 @@ pub async fn login(
 ///
 /// auth-cookie: <cookie_value>
 ///
-/// Body:
+/// Response:
 ///
 /// ```json
 /// {
 ///    "result": false,
+///    "authenticated": false,
+///    "user": null,
+///    "roles": []
 /// }
 /// ```
-///
-/// Response: 200 OK
 #[get("/is-logged-in")]
-pub async fn is_logged_in(id: Identity) -> Result<HttpResponse, actix_web::Error> {
-    return if let Some(_) = id.identity() {
-        Ok(HttpResponse::Ok().json(r#"{"result": true}"#))
-    } else {
-        Ok(HttpResponse::Ok().json(r#"{"result": false}"#))
-    };
+pub async fn is_logged_in(
+    user: Option<AuthenticatedUser>,
+    pool: Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let response = me_response(user, pool).await?;
+
+    Ok(HttpResponse::Ok().json(IsLoggedInResponse {
+        result: response.authenticated,
+        me: response,
+    }))
 }
 
-pub fn get_user_by_name(name: String, connection: &MysqlConnection) -> Result<User, DieselError> {
+pub fn get_user_by_name(name: String, connection: &DbConnection) -> Result<User, DieselError> {
     use crate::schema::users::dsl::{name as user_name, users};
 
     let result = users.filter(user_name.eq(name.clone())).first(connection);
@@ -109,28 +418,57 @@ pub fn get_user_by_name(name: String, connection: &MysqlConnection) -> Result<Us
     Ok(result.expect("Could not locate user by name."))
 }
 
-pub fn create_user(name: String, connection: &MysqlConnection) -> Result<User, DieselError> {
+/// Returns the user with the given name, if there is one.
+pub fn find_user_by_name(
+    name: &str,
+    connection: &DbConnection,
+) -> Result<Option<User>, DieselError> {
+    use crate::schema::users::dsl::{name as user_name, users};
+
+    users
+        .filter(user_name.eq(name))
+        .first(connection)
+        .optional()
+}
+
+pub fn create_user(name: String, connection: &DbConnection) -> Result<User, DieselError> {
     use crate::schema::users::dsl::{created, name as user_name, users};
 
     let now = Utc::now().naive_utc();
+    let values = (user_name.eq(&name), created.eq(now));
 
-    diesel::insert_into(users)
-        .values((user_name.eq(&name), created.eq(now)))
-        .execute(connection)
-        .expect("Error creating new user.");
+    #[cfg(feature = "postgres")]
+    let new_user = diesel::insert_into(users)
+        .values(values)
+        .get_result(connection)?;
 
-    let new_user = users
-        .filter(user_name.eq(&name))
-        .first(connection)
-        .expect("Error retrieving the new user.");
+    #[cfg(not(feature = "postgres"))]
+    let new_user = {
+        let user_id = insert_returning_id!(users, values, connection)?;
+        users.find(user_id).first(connection)?
+    };
 
     Ok(new_user)
 }
 
-pub fn load_user_by_id(uid: i32, connection: &MysqlConnection) -> Result<User, DieselError> {
+pub fn load_user_by_id(uid: i32, connection: &DbConnection) -> Result<User, DieselError> {
     use crate::schema::users::dsl::users;
 
     let result = users.find(uid).first::<User>(connection);
 
     result
 }
+
+/// Returns all users, ordered by id.
+pub fn get_users(connection: &DbConnection) -> Result<Vec<User>, DieselError> {
+    use crate::schema::users::dsl::{id, users};
+
+    users.order_by(id.asc()).load(connection)
+}
+
+/// Deletes a user, along with everything they created, and returns the number of deleted users.
+pub fn delete_user(uid: i32, connection: &DbConnection) -> Result<usize, DieselError> {
+    use crate::schema::users::dsl::users;
+
+    diesel::delete(users.find(uid)).execute(connection)
+}
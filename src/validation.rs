@@ -0,0 +1,76 @@
+//! Declarative validation of request bodies.
+//!
+//! Input structs implement `Validate` by listing the rules of their fields, and handlers run
+//! `validate` before touching the database, answering 422 Unprocessable Entity with the errors
+//! of every field. Whether referenced rows exist is checked by `parents`.
+
+use crate::models::{FieldError, FieldErrors};
+
+use actix_web::HttpResponse;
+
+/// Length of the `VARCHAR(255)` columns storing titles, option texts and names.
+pub const MAX_TEXT_LENGTH: usize = 255;
+
+/// Rules for a text field.
+#[derive(Clone, Copy, Debug)]
+pub struct Text {
+    /// Refuses empty values, and values made of whitespace only.
+    pub non_empty: bool,
+    /// Refuses values starting or ending with whitespace.
+    pub trimmed: bool,
+    /// Maximum length in characters.
+    pub max_length: usize,
+}
+
+/// Titles, option texts and names.
+pub const SHORT_TEXT: Text = Text {
+    non_empty: true,
+    trimmed: true,
+    max_length: MAX_TEXT_LENGTH,
+};
+
+impl Text {
+    /// Pushes an error for the first rule `value` breaks.
+    pub fn check(&self, path: &str, value: &str, errors: &mut Vec<FieldError>) {
+        let message = if self.non_empty && value.trim().is_empty() {
+            String::from("must not be empty")
+        } else if self.trimmed && value.trim() != value {
+            String::from("must not start or end with whitespace")
+        } else if value.chars().count() > self.max_length {
+            format!("must be at most {} characters long", self.max_length)
+        } else {
+            return;
+        };
+
+        errors.push(FieldError {
+            path: String::from(path),
+            message,
+        });
+    }
+}
+
+/// A request body with rules for its fields.
+pub trait Validate {
+    /// Pushes an error for every field breaking a rule.
+    fn check(&self, errors: &mut Vec<FieldError>);
+
+    /// The errors of the fields.
+    fn errors(&self) -> Vec<FieldError> {
+        let mut errors = vec![];
+        self.check(&mut errors);
+
+        errors
+    }
+}
+
+/// Checks a request body, and returns it back if it is valid, or the 422 Unprocessable Entity
+/// response listing the invalid fields.
+pub fn validate<T: Validate>(input: T) -> Result<T, HttpResponse> {
+    let errors = input.errors();
+
+    if errors.is_empty() {
+        Ok(input)
+    } else {
+        Err(HttpResponse::UnprocessableEntity().json(FieldErrors { errors }))
+    }
+}
@@ -1,27 +1,38 @@
-use crate::answers::new_answer;
-use crate::questions::get_question_by_presentation;
+use crate::answers::{new_answer, new_participant_answer, ParticipantAnswer};
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::config::{Config, RateLimit};
+use crate::csrf::CsrfProtection;
+use crate::models::Questions;
+use crate::parents::{AttachError, Parent};
+use crate::participants::DeviceTokens;
+use crate::questions::{
+    close_question, get_question_by_presentation, get_voting_duration, open_question, start_timer,
+};
+use crate::rate_limit::{retry_after_seconds, TokenBucket};
+use crate::web_socket_server::CancelCountdown;
 use crate::web_socket_server::JoinSession;
 use crate::web_socket_server::Message;
 use crate::web_socket_server::RemoveSession;
 use crate::web_socket_server::SendMessage;
+use crate::web_socket_server::StartCountdown;
 use crate::web_socket_server::WebSocketServer;
-use crate::DbPool;
+use crate::{DbConnection, DbPool};
 use actix::prelude::*;
 use actix_broker::BrokerIssue;
 use actix_http::ws::ProtocolError;
-use actix_web::web::{Data, Payload};
+use actix_web::web::{Data, Payload, Query};
 use actix_web::HttpRequest;
 use actix_web::{get, HttpResponse};
 use actix_web_actors::ws;
 use actix_web_actors::ws::WebsocketContext;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::PooledConnection;
-use diesel::MysqlConnection;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::time::Instant;
 
-type PooledDatabaseConnection = PooledConnection<ConnectionManager<MysqlConnection>>;
+type PooledDatabaseConnection = PooledConnection<ConnectionManager<DbConnection>>;
 
 #[derive(Deserialize)]
 enum Direction {
@@ -33,6 +44,27 @@ enum Direction {
 enum Event {
     Navigate,
     AnswersCreate,
+    /// Sent by the presenter to open a question for answers.
+    VotingOpen,
+    /// Sent by the presenter to close a question for answers.
+    VotingClose,
+    /// Broadcast when a question is opened for answers.
+    #[serde(skip_deserializing)]
+    VotingOpened,
+    /// Broadcast when a question is closed, by the presenter or once its duration passed.
+    #[serde(skip_deserializing)]
+    VotingClosed,
+    /// Sent by the presenter to close a question after a countdown, opening it unless it is open.
+    TimerStart,
+    /// Broadcast when a countdown starts.
+    #[serde(skip_deserializing)]
+    TimerStarted,
+    /// Broadcast every second of a countdown.
+    #[serde(skip_deserializing)]
+    TimerTick,
+    /// Sent instead of handling a message when the connection sends too many.
+    #[serde(skip_deserializing)]
+    RateLimited,
 }
 
 struct WebSocketSession {
@@ -40,6 +72,21 @@ struct WebSocketSession {
     name: String,
     heart_beat: Instant,
     db_connection: PooledDatabaseConnection,
+    device_tokens: DeviceTokens,
+    /// Limit of the messages received, with the bucket of the connection.
+    rate_limit: Option<(RateLimit, TokenBucket)>,
+    /// The logged in user of the connection, who can open and close the questions of their
+    /// presentations.
+    user_id: Option<i32>,
+    /// The presentation whose room the connection joined.
+    presentation_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct WebSocketQuery {
+    /// Joins the room of the presentation, which gets its countdowns and voting windows only.
+    #[serde(default)]
+    presentation_id: Option<i32>,
 }
 
 trait HandleWebSocketTx<Req, Rd>
@@ -60,10 +107,49 @@ struct NavigateEventRequest {
     direction: Direction,
 }
 
+/// Answers as an anonymous participant with `device_token`, or else as the logged in user of the
+/// connection.
 #[derive(Deserialize)]
 struct AnswersCreateEventRequest {
     option_id: i32,
-    user_id: i32,
+    #[serde(default)]
+    device_token: Option<String>,
+    /// The logged in user of the connection, set by the session.
+    #[serde(skip)]
+    user_id: Option<i32>,
+    /// The participant of a valid `device_token`, set by the session.
+    #[serde(skip)]
+    participant_id: Option<i32>,
+}
+
+/// Opens a question for answers, for `duration` seconds if given, or else for the voting duration
+/// of its presentation.
+#[derive(Deserialize)]
+struct VotingOpenEventRequest {
+    question_id: i32,
+    #[serde(default)]
+    duration: Option<u32>,
+    /// The logged in user of the connection, set by the session.
+    #[serde(skip)]
+    user_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct VotingCloseEventRequest {
+    question_id: i32,
+    /// The logged in user of the connection, set by the session.
+    #[serde(skip)]
+    user_id: Option<i32>,
+}
+
+/// Closes a question once `duration` seconds passed.
+#[derive(Deserialize)]
+struct TimerStartEventRequest {
+    question_id: i32,
+    duration: u32,
+    /// The logged in user of the connection, set by the session.
+    #[serde(skip)]
+    user_id: Option<i32>,
 }
 
 #[derive(Serialize)]
@@ -72,7 +158,95 @@ struct NavigateEventResponse {
 }
 
 #[derive(Serialize)]
-struct AnswersCreateEventResponse;
+struct AnswersCreateEventResponse {
+    accepted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl AnswersCreateEventResponse {
+    fn accepted() -> Self {
+        AnswersCreateEventResponse {
+            accepted: true,
+            reason: None,
+        }
+    }
+
+    fn rejected(reason: &str) -> Self {
+        AnswersCreateEventResponse {
+            accepted: false,
+            reason: Some(reason.to_owned()),
+        }
+    }
+}
+
+/// The voting window of a question, broadcast with `VotingOpened` and `VotingClosed`.
+///
+/// The times are sent in UTC with the `Z` suffix, so clients in other time zones count down to
+/// the same deadline.
+#[derive(Serialize, Clone, Copy)]
+struct VotingWindow {
+    question_id: i32,
+    presentation_id: i32,
+    opened_at: Option<DateTime<Utc>>,
+    closes_at: Option<DateTime<Utc>>,
+    closed_at: Option<DateTime<Utc>>,
+}
+
+fn utc(time: Option<NaiveDateTime>) -> Option<DateTime<Utc>> {
+    time.map(|time| DateTime::from_utc(time, Utc))
+}
+
+impl VotingWindow {
+    fn opened(question: &Questions) -> Self {
+        VotingWindow {
+            question_id: question.id,
+            presentation_id: question.presentation_id,
+            opened_at: utc(question.opened_at),
+            closes_at: utc(question.closes_at),
+            closed_at: None,
+        }
+    }
+
+    /// The window of a closed question, which closed on its own if the presenter did not close
+    /// it.
+    fn closed(question: &Questions) -> Self {
+        VotingWindow {
+            closed_at: utc(question.closed_at.or(question.closes_at)),
+            ..VotingWindow::opened(question)
+        }
+    }
+}
+
+/// Sent back to the presenter when a question could not be opened or closed.
+#[derive(Serialize)]
+struct VotingRejectedEventResponse {
+    question_id: i32,
+    reason: String,
+}
+
+/// The voting window of a question counting down to `closes_at`, broadcast with `TimerStarted`
+/// and `TimerTick`.
+#[derive(Serialize)]
+struct CountdownEventResponse {
+    #[serde(flatten)]
+    window: VotingWindow,
+    seconds_left: i64,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum VotingEventResponse {
+    Window(VotingWindow),
+    Countdown(CountdownEventResponse),
+    Rejected(VotingRejectedEventResponse),
+}
+
+#[derive(Serialize)]
+struct RateLimitedEventResponse {
+    /// Seconds until messages are accepted again.
+    retry_after: u64,
+}
 
 #[derive(Serialize)]
 struct WebSocketResponse<T> {
@@ -131,23 +305,235 @@ impl HandleWebSocketTx<AnswersCreateEventRequest, AnswersCreateEventResponse>
         &self,
         connection: &PooledDatabaseConnection,
     ) -> WebSocketResponse<AnswersCreateEventResponse> {
-        new_answer(self.option_id, self.user_id, connection)
-            .expect("Unable to create a new answer. Check logs for more details.");
+        let data = match (self.participant_id, &self.device_token, self.user_id) {
+            (Some(participant_id), _, _) => {
+                match new_participant_answer(self.option_id, participant_id, connection) {
+                    Ok(ParticipantAnswer::Created) => AnswersCreateEventResponse::accepted(),
+                    Ok(ParticipantAnswer::AlreadyAnswered) => {
+                        AnswersCreateEventResponse::rejected("The question is already answered.")
+                    }
+                    Err(error) => AnswersCreateEventResponse::rejected(&error.message()),
+                }
+            }
+            (None, Some(_), _) => AnswersCreateEventResponse::rejected("Invalid device token."),
+            (None, None, Some(user_id)) => match new_answer(self.option_id, user_id, connection) {
+                Ok(()) => AnswersCreateEventResponse::accepted(),
+                Err(error) => AnswersCreateEventResponse::rejected(&error.message()),
+            },
+            (None, None, None) => {
+                AnswersCreateEventResponse::rejected("Log in or join the presentation to answer.")
+            }
+        };
 
         WebSocketResponse {
             event: Event::AnswersCreate,
-            data: AnswersCreateEventResponse,
+            data,
+        }
+    }
+}
+
+/// Fails with the reason to send back unless the user presents the question, which only the
+/// presenter opens and closes.
+pub fn require_presenter(
+    question_id: i32,
+    user_id: Option<i32>,
+    connection: &DbConnection,
+) -> Result<(), String> {
+    let user_id = user_id.ok_or_else(|| String::from("Log in to open or close questions."))?;
+
+    Parent::Question(question_id)
+        .require_presenter(user_id, connection)
+        .map_err(|error| match error {
+            AttachError::Forbidden(_) => {
+                String::from("Only the presenter can open or close the question.")
+            }
+            error => error.message(),
+        })
+}
+
+impl HandleWebSocketTx<VotingOpenEventRequest, VotingEventResponse> for VotingOpenEventRequest {
+    fn get_response(
+        &self,
+        connection: &PooledDatabaseConnection,
+    ) -> WebSocketResponse<VotingEventResponse> {
+        let opened = require_presenter(self.question_id, self.user_id, connection).and_then(|()| {
+            if self.duration == Some(0) {
+                return Err(String::from("The duration must be at least one second."));
+            }
+
+            let seconds = match self.duration {
+                Some(seconds) => Some(i64::from(seconds)),
+                None => get_voting_duration(self.question_id, connection)
+                    .map_err(|_| String::from("Could not open the question."))?
+                    .map(i64::from),
+            };
+            let duration = seconds.map(Duration::seconds);
+            open_question(
+                self.question_id,
+                duration,
+                Utc::now().naive_utc(),
+                connection,
+            )
+            .map_err(|_| String::from("Could not open the question."))
+        });
+
+        match opened {
+            Ok(question) => WebSocketResponse {
+                event: Event::VotingOpened,
+                data: VotingEventResponse::Window(VotingWindow::opened(&question)),
+            },
+            Err(reason) => WebSocketResponse {
+                event: Event::VotingOpen,
+                data: VotingEventResponse::Rejected(VotingRejectedEventResponse {
+                    question_id: self.question_id,
+                    reason,
+                }),
+            },
+        }
+    }
+}
+
+impl HandleWebSocketTx<VotingCloseEventRequest, VotingEventResponse> for VotingCloseEventRequest {
+    fn get_response(
+        &self,
+        connection: &PooledDatabaseConnection,
+    ) -> WebSocketResponse<VotingEventResponse> {
+        let closed = require_presenter(self.question_id, self.user_id, connection).and_then(|()| {
+            close_question(self.question_id, Utc::now().naive_utc(), connection)
+                .map_err(|_| String::from("Could not close the question."))
+        });
+
+        match closed {
+            Ok(question) => WebSocketResponse {
+                event: Event::VotingClosed,
+                data: VotingEventResponse::Window(VotingWindow::closed(&question)),
+            },
+            Err(reason) => WebSocketResponse {
+                event: Event::VotingClose,
+                data: VotingEventResponse::Rejected(VotingRejectedEventResponse {
+                    question_id: self.question_id,
+                    reason,
+                }),
+            },
+        }
+    }
+}
+
+impl HandleWebSocketTx<TimerStartEventRequest, VotingEventResponse> for TimerStartEventRequest {
+    fn get_response(
+        &self,
+        connection: &PooledDatabaseConnection,
+    ) -> WebSocketResponse<VotingEventResponse> {
+        let now = Utc::now().naive_utc();
+        let started =
+            require_presenter(self.question_id, self.user_id, connection).and_then(|()| {
+                if self.duration == 0 {
+                    return Err(String::from("The duration must be at least one second."));
+                }
+
+                let duration = Duration::seconds(self.duration.into());
+                start_timer(self.question_id, duration, now, connection)
+                    .map_err(|_| String::from("Could not start the timer."))
+            });
+
+        match started {
+            Ok(question) => WebSocketResponse {
+                event: Event::TimerStarted,
+                data: VotingEventResponse::Countdown(CountdownEventResponse {
+                    window: VotingWindow::opened(&question),
+                    seconds_left: self.duration.into(),
+                }),
+            },
+            Err(reason) => WebSocketResponse {
+                event: Event::TimerStart,
+                data: VotingEventResponse::Rejected(VotingRejectedEventResponse {
+                    question_id: self.question_id,
+                    reason,
+                }),
+            },
         }
     }
 }
 
 impl WebSocketSession {
-    pub fn new(db_connection: PooledDatabaseConnection) -> Self {
+    pub fn new(
+        db_connection: PooledDatabaseConnection,
+        device_tokens: DeviceTokens,
+        rate_limit: Option<RateLimit>,
+        user_id: Option<i32>,
+        presentation_id: Option<i32>,
+    ) -> Self {
         Self {
             id: 0,
             name: "Main".to_owned(),
             heart_beat: Instant::now(),
             db_connection,
+            device_tokens,
+            rate_limit: rate_limit.map(|limit| (limit, TokenBucket::new(limit, Instant::now()))),
+            user_id,
+            presentation_id,
+        }
+    }
+
+    /// Broadcasts a changed voting window to the room of its presentation, and starts or stops
+    /// the countdown of the question. Rejections only go back to the presenter.
+    fn send_voting_response(
+        &self,
+        response: WebSocketResponse<VotingEventResponse>,
+        ctx: &mut WebsocketContext<Self>,
+    ) {
+        let text = serde_json::to_string(&response).expect("Unable to parse voting response");
+
+        let window = match response.data {
+            VotingEventResponse::Window(window) => window,
+            VotingEventResponse::Countdown(countdown) => countdown.window,
+            VotingEventResponse::Rejected(_) => return ctx.text(text),
+        };
+
+        let server = WebSocketServer::from_registry();
+        match (window.closes_at, window.closed_at) {
+            (Some(deadline), None) => {
+                let tick = move |seconds_left| {
+                    let response = WebSocketResponse {
+                        event: Event::TimerTick,
+                        data: CountdownEventResponse {
+                            window,
+                            seconds_left,
+                        },
+                    };
+
+                    serde_json::to_string(&response).expect("Unable to parse timer tick response")
+                };
+                let closed = WebSocketResponse {
+                    event: Event::VotingClosed,
+                    data: VotingWindow {
+                        closed_at: Some(deadline),
+                        ..window
+                    },
+                };
+
+                server.do_send(StartCountdown {
+                    question_id: window.question_id,
+                    presentation_id: window.presentation_id,
+                    deadline: deadline.naive_utc(),
+                    tick: Box::new(tick),
+                    closed: serde_json::to_string(&closed)
+                        .expect("Unable to parse voting closed response"),
+                });
+            }
+            _ => server.do_send(CancelCountdown(window.question_id)),
+        }
+
+        self.send_room_msg(window.presentation_id, text);
+    }
+
+    /// Takes a token for a received message, or returns the seconds until one is available.
+    fn take_message_token(&mut self) -> Result<(), u64> {
+        match &mut self.rate_limit {
+            Some((limit, bucket)) => bucket
+                .take(*limit, Instant::now())
+                .map_err(retry_after_seconds),
+            None => Ok(()),
         }
     }
 
@@ -156,6 +542,19 @@ impl WebSocketSession {
             name: self.name.clone(),
             id: self.id,
             content: msg,
+            presentation_id: None,
+        };
+
+        self.issue_system_async(msg);
+    }
+
+    /// Sends a message to the room of a presentation, and to the connections outside of a room.
+    pub fn send_room_msg(&self, presentation_id: i32, msg: String) {
+        let msg = SendMessage {
+            name: self.name.clone(),
+            id: self.id,
+            content: msg,
+            presentation_id: Some(presentation_id),
         };
 
         self.issue_system_async(msg);
@@ -166,7 +565,10 @@ impl Actor for WebSocketSession {
     type Context = WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        let join_session = JoinSession(ctx.address().recipient());
+        let join_session = JoinSession {
+            recipient: ctx.address().recipient(),
+            presentation_id: self.presentation_id,
+        };
         WebSocketServer::from_registry()
             .send(join_session)
             .into_actor(self)
@@ -206,6 +608,18 @@ impl StreamHandler<Result<ws::Message, ProtocolError>> for WebSocketSession {
                 self.heart_beat = Instant::now();
             }
             Ok(ws::Message::Text(text)) => {
+                if let Err(retry_after) = self.take_message_token() {
+                    let response = WebSocketResponse {
+                        event: Event::RateLimited,
+                        data: RateLimitedEventResponse { retry_after },
+                    };
+                    ctx.text(
+                        serde_json::to_string(&response)
+                            .expect("Unable to parse rate limited response"),
+                    );
+                    return;
+                }
+
                 let connection = &self.db_connection;
 
                 let message: WebSocketRequest = serde_json::from_str(&text)
@@ -220,13 +634,51 @@ impl StreamHandler<Result<ws::Message, ProtocolError>> for WebSocketSession {
                         );
                     }
                     Event::AnswersCreate => {
-                        let request_data = AnswersCreateEventRequest::parse_request(&message.data);
+                        let mut request_data =
+                            AnswersCreateEventRequest::parse_request(&message.data);
+                        request_data.user_id = self.user_id;
+                        request_data.participant_id = request_data
+                            .device_token
+                            .as_ref()
+                            .and_then(|token| self.device_tokens.verify(token));
                         let response = request_data.get_response(connection);
-                        self.send_msg(
-                            serde_json::to_string(&response)
-                                .expect("Unable to parse answers create response"),
-                        );
+                        let text = serde_json::to_string(&response)
+                            .expect("Unable to parse answers create response");
+
+                        // Only accepted answers are broadcast, rejections go back to the sender.
+                        if response.data.accepted {
+                            self.send_msg(text);
+                        } else {
+                            ctx.text(text);
+                        }
+                    }
+                    Event::VotingOpen => {
+                        let mut request_data = VotingOpenEventRequest::parse_request(&message.data);
+                        request_data.user_id = self.user_id;
+                        let response = request_data.get_response(connection);
+
+                        self.send_voting_response(response, ctx);
+                    }
+                    Event::VotingClose => {
+                        let mut request_data =
+                            VotingCloseEventRequest::parse_request(&message.data);
+                        request_data.user_id = self.user_id;
+                        let response = request_data.get_response(connection);
+
+                        self.send_voting_response(response, ctx);
+                    }
+                    Event::TimerStart => {
+                        let mut request_data = TimerStartEventRequest::parse_request(&message.data);
+                        request_data.user_id = self.user_id;
+                        let response = request_data.get_response(connection);
+
+                        self.send_voting_response(response, ctx);
                     }
+                    Event::VotingOpened
+                    | Event::VotingClosed
+                    | Event::TimerStarted
+                    | Event::TimerTick
+                    | Event::RateLimited => {}
                 }
             }
             Ok(ws::Message::Binary(_)) => println!("Unexpected binary"),
@@ -240,8 +692,33 @@ pub async fn index(
     request: HttpRequest,
     stream: Payload,
     pool: Data<DbPool>,
+    device_tokens: Data<DeviceTokens>,
+    user: Option<AuthenticatedUser>,
+    query: Query<WebSocketQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let config = request
+        .app_data::<Data<Config>>()
+        .expect("The configuration is not registered.");
+    let csrf_protection = request
+        .app_data::<Data<CsrfProtection>>()
+        .expect("The CSRF protection is not registered.");
     let connection = pool.get().expect("unable to get database connection");
-    let response = ws::start(WebSocketSession::new(connection), &request, stream);
+    // Browsers send the auth cookie with upgrades from any site, so sockets opened by another
+    // site are anonymous.
+    let user_id = user
+        .filter(|user| user.has_scope(Scope::Write))
+        .filter(|_| csrf_protection.trusts_origin(&request))
+        .map(|user| user.id());
+    let response = ws::start(
+        WebSocketSession::new(
+            connection,
+            device_tokens.get_ref().clone(),
+            config.rate_limits.web_socket,
+            user_id,
+            query.presentation_id,
+        ),
+        &request,
+        stream,
+    );
     response
 }
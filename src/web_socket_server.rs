@@ -1,8 +1,13 @@
 use actix::prelude::*;
 use actix_broker::BrokerSubscribe;
+use chrono::{NaiveDateTime, Utc};
 use rand::prelude::ThreadRng;
 use rand::Rng;
 use std::collections::HashMap;
+use std::time::Duration;
+
+/// How often countdowns send the seconds left.
+pub const TICK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Message)]
 #[rtype(result = "()")]
@@ -14,39 +19,121 @@ pub struct SendMessage {
     pub name: String,
     pub id: usize,
     pub content: String,
+    /// The presentation whose room gets the message, every session does if there is none.
+    pub presentation_id: Option<i32>,
 }
 
 #[derive(Clone, Message)]
 #[rtype(result = "usize")]
-pub struct JoinSession(pub Recipient<Message>);
+pub struct JoinSession {
+    pub recipient: Recipient<Message>,
+    /// The presentation whose room the session joins. Sessions outside of a room get the messages
+    /// of every room.
+    pub presentation_id: Option<i32>,
+}
 
 #[derive(Clone, Message)]
 #[rtype(result = "()")]
 pub struct RemoveSession(pub usize);
 
+/// Counts down to the deadline of a question in the room of its presentation. Every
+/// `TICK_INTERVAL` the room gets `tick` of the seconds left, and `closed` once the deadline
+/// passed. Replaces the countdown of the question before.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StartCountdown {
+    pub question_id: i32,
+    pub presentation_id: i32,
+    pub deadline: NaiveDateTime,
+    pub tick: Box<dyn Fn(i64) -> String + Send>,
+    pub closed: String,
+}
+
+/// Stops the countdown of a question, which was closed or opened again.
+#[derive(Clone, Message)]
+#[rtype(result = "()")]
+pub struct CancelCountdown(pub i32);
+
+/// Whole seconds until `deadline`, rounded up so the last tick before it shows 1.
+pub fn seconds_left(deadline: NaiveDateTime, now: NaiveDateTime) -> i64 {
+    let milliseconds = (deadline - now).num_milliseconds();
+
+    ((milliseconds + 999) / 1000).max(0)
+}
+
+struct Session {
+    recipient: Recipient<Message>,
+    presentation_id: Option<i32>,
+}
+
+struct Countdown {
+    ticks: SpawnHandle,
+    close: SpawnHandle,
+}
+
 #[derive(Default)]
 pub struct WebSocketServer {
-    sessions: HashMap<usize, Recipient<Message>>,
+    sessions: HashMap<usize, Session>,
     rng: ThreadRng,
+    /// Countdowns of questions closing on their own, by question id.
+    countdowns: HashMap<i32, Countdown>,
 }
 
 impl WebSocketServer {
+    /// Sends a message to every session.
+    ///
+    /// Sessions whose socket closed before their `RemoveSession` arrived are dropped.
     pub fn send_message(&mut self, message: String) {
-        for (_id, recipient) in &self.sessions {
-            recipient
+        self.sessions.retain(|_, session| {
+            session
+                .recipient
                 .do_send(Message(message.to_owned()))
-                .expect("Could not send message to the client.");
-        }
+                .is_ok()
+        });
     }
 
-    pub fn add_session(&mut self, client: Recipient<Message>) -> usize {
+    /// Sends a message to the room of a presentation, and to the sessions outside of a room.
+    ///
+    /// Sessions whose socket closed before their `RemoveSession` arrived are dropped.
+    pub fn send_room_message(&mut self, presentation_id: i32, message: String) {
+        self.sessions.retain(|_, session| {
+            let in_room = session
+                .presentation_id
+                .map_or(true, |room| room == presentation_id);
+
+            !in_room
+                || session
+                    .recipient
+                    .do_send(Message(message.to_owned()))
+                    .is_ok()
+        });
+    }
+
+    pub fn add_session(
+        &mut self,
+        client: Recipient<Message>,
+        presentation_id: Option<i32>,
+    ) -> usize {
         let id: usize = self.rng.gen();
 
-        self.sessions.insert(id, client);
+        self.sessions.insert(
+            id,
+            Session {
+                recipient: client,
+                presentation_id,
+            },
+        );
 
         id
     }
 
+    fn cancel_countdown(&mut self, question_id: i32, ctx: &mut Context<Self>) {
+        if let Some(countdown) = self.countdowns.remove(&question_id) {
+            ctx.cancel_future(countdown.ticks);
+            ctx.cancel_future(countdown.close);
+        }
+    }
+
     pub fn remove_session(&mut self, session_id: usize) {
         self.sessions.remove(&session_id);
     }
@@ -65,7 +152,10 @@ impl Handler<SendMessage> for WebSocketServer {
     type Result = ();
 
     fn handle(&mut self, msg: SendMessage, _ctx: &mut Self::Context) {
-        self.send_message(msg.content);
+        match msg.presentation_id {
+            Some(presentation_id) => self.send_room_message(presentation_id, msg.content),
+            None => self.send_message(msg.content),
+        }
     }
 }
 
@@ -73,9 +163,12 @@ impl Handler<JoinSession> for WebSocketServer {
     type Result = MessageResult<JoinSession>;
 
     fn handle(&mut self, msg: JoinSession, _ctx: &mut Self::Context) -> Self::Result {
-        let JoinSession(client) = msg;
+        let JoinSession {
+            recipient,
+            presentation_id,
+        } = msg;
 
-        let id = self.add_session(client);
+        let id = self.add_session(recipient, presentation_id);
 
         MessageResult(id)
     }
@@ -93,5 +186,54 @@ impl Handler<RemoveSession> for WebSocketServer {
     }
 }
 
+impl Handler<StartCountdown> for WebSocketServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: StartCountdown, ctx: &mut Self::Context) {
+        let StartCountdown {
+            question_id,
+            presentation_id,
+            deadline,
+            tick,
+            closed,
+        } = msg;
+
+        self.cancel_countdown(question_id, ctx);
+
+        let ticks = ctx.run_interval(TICK_INTERVAL, move |act, _ctx| {
+            let left = seconds_left(deadline, Utc::now().naive_utc());
+
+            // The deadline itself is announced by `closed`.
+            if left > 0 {
+                act.send_room_message(presentation_id, tick(left));
+            }
+        });
+
+        let after = (deadline - Utc::now().naive_utc())
+            .to_std()
+            .unwrap_or_default();
+        let close = ctx.run_later(after, move |act, ctx| {
+            if let Some(countdown) = act.countdowns.remove(&question_id) {
+                ctx.cancel_future(countdown.ticks);
+            }
+
+            act.send_room_message(presentation_id, closed);
+        });
+
+        self.countdowns
+            .insert(question_id, Countdown { ticks, close });
+    }
+}
+
+impl Handler<CancelCountdown> for WebSocketServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: CancelCountdown, ctx: &mut Self::Context) {
+        let CancelCountdown(question_id) = msg;
+
+        self.cancel_countdown(question_id, ctx);
+    }
+}
+
 impl SystemService for WebSocketServer {}
 impl Supervised for WebSocketServer {}
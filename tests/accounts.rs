@@ -0,0 +1,185 @@
+extern crate chrono;
+extern crate questionnaire_rs;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use questionnaire_rs::accounts::{hash_password, verify_password};
+
+#[test]
+fn password_hashes_verify() {
+    let hash = hash_password("correct horse battery staple").unwrap();
+
+    assert!(hash.starts_with("$argon2id$"));
+    assert!(verify_password(&hash, "correct horse battery staple"));
+    assert!(!verify_password(&hash, "battery staple correct horse"));
+    assert!(!verify_password(
+        "not a hash",
+        "correct horse battery staple"
+    ));
+}
+
+#[test]
+fn password_hashes_are_salted() {
+    assert_ne!(
+        hash_password("correct horse battery staple").unwrap(),
+        hash_password("correct horse battery staple").unwrap()
+    );
+}
+
+#[cfg(feature = "sqlite")]
+mod login {
+    use super::common;
+    use chrono::{Duration, NaiveDateTime};
+    use questionnaire_rs::accounts::{
+        authenticate, hash_password, is_presenter, link_identity, register_presenter, LoginOutcome,
+        LOCKOUT_SECONDS, MAX_FAILED_LOGINS,
+    };
+    use questionnaire_rs::DbConnection;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn login(
+        name: &str,
+        password: Option<&str>,
+        now: NaiveDateTime,
+        connection: &DbConnection,
+    ) -> LoginOutcome {
+        authenticate(
+            String::from(name),
+            password.map(String::from),
+            now,
+            connection,
+        )
+        .unwrap()
+    }
+
+    fn logged_in_as(outcome: LoginOutcome) -> Option<i32> {
+        match outcome {
+            LoginOutcome::LoggedIn(user) => Some(user.id),
+            _ => None,
+        }
+    }
+
+    fn presenter(connection: &DbConnection) -> i32 {
+        let password_hash = hash_password(PASSWORD).unwrap();
+
+        register_presenter(String::from("presenter"), password_hash, connection)
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn presenters_need_their_password() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let uid = presenter(&connection);
+        let now = common::creation_time();
+
+        assert_eq!(
+            logged_in_as(login("presenter", Some(PASSWORD), now, &connection)),
+            Some(uid)
+        );
+        assert_eq!(
+            logged_in_as(login("presenter", None, now, &connection)),
+            None
+        );
+        assert_eq!(
+            logged_in_as(login("presenter", Some("wrong password"), now, &connection)),
+            None
+        );
+    }
+
+    #[test]
+    fn audience_members_log_in_by_name() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let now = common::creation_time();
+
+        let uid = logged_in_as(login("alice", None, now, &connection)).unwrap();
+        assert_eq!(
+            logged_in_as(login("alice", None, now, &connection)),
+            Some(uid)
+        );
+        assert_eq!(
+            logged_in_as(login("alice", Some(PASSWORD), now, &connection)),
+            None
+        );
+        assert!(!is_presenter(uid, &connection).unwrap());
+    }
+
+    #[test]
+    fn linked_users_log_in_through_their_provider() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let user = link_identity("github", "583231", "octocat", None, &connection).unwrap();
+
+        assert!(is_presenter(user.id, &connection).unwrap());
+        assert_eq!(
+            logged_in_as(login("octocat", None, common::creation_time(), &connection)),
+            None
+        );
+    }
+
+    #[test]
+    fn failed_logins_lock_the_account() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let uid = presenter(&connection);
+        let now = common::creation_time();
+
+        for _ in 0..MAX_FAILED_LOGINS {
+            match login("presenter", Some("wrong password"), now, &connection) {
+                LoginOutcome::InvalidCredentials => {}
+                _ => panic!("Expected invalid credentials"),
+            }
+        }
+
+        // Even the right password is refused while the account is locked.
+        let unlock = now + Duration::seconds(LOCKOUT_SECONDS);
+        match login("presenter", Some(PASSWORD), now, &connection) {
+            LoginOutcome::Locked(until) => assert_eq!(until, unlock),
+            _ => panic!("Expected a locked account"),
+        }
+        match login(
+            "presenter",
+            Some(PASSWORD),
+            unlock - Duration::seconds(1),
+            &connection,
+        ) {
+            LoginOutcome::Locked(_) => {}
+            _ => panic!("Expected a locked account"),
+        }
+
+        assert_eq!(
+            logged_in_as(login("presenter", Some(PASSWORD), unlock, &connection)),
+            Some(uid)
+        );
+    }
+
+    #[test]
+    fn successful_logins_reset_the_failures() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let uid = presenter(&connection);
+        let now = common::creation_time();
+
+        for _ in 1..MAX_FAILED_LOGINS {
+            login("presenter", Some("wrong password"), now, &connection);
+        }
+        assert_eq!(
+            logged_in_as(login("presenter", Some(PASSWORD), now, &connection)),
+            Some(uid)
+        );
+        assert!(is_presenter(uid, &connection).unwrap());
+
+        // Without the reset, the first of these would lock the account.
+        for _ in 1..MAX_FAILED_LOGINS {
+            login("presenter", Some("wrong password"), now, &connection);
+        }
+        assert_eq!(
+            logged_in_as(login("presenter", Some(PASSWORD), now, &connection)),
+            Some(uid)
+        );
+    }
+}
@@ -0,0 +1,268 @@
+extern crate actix_http;
+extern crate actix_identity;
+extern crate actix_rt;
+extern crate actix_web;
+extern crate chrono;
+extern crate questionnaire_rs;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use chrono::NaiveDate;
+use questionnaire_rs::auth::{
+    format_scopes, generate_api_token, hash_api_token, parse_scopes, AuthenticatedUser,
+    Credentials, Scope,
+};
+use questionnaire_rs::models::User;
+
+fn authenticated(scopes: Vec<Scope>) -> AuthenticatedUser {
+    AuthenticatedUser {
+        user: User {
+            id: 7,
+            name: String::from("agent 42"),
+            created: NaiveDate::from_ymd(2020, 11, 18).and_hms(9, 0, 0),
+        },
+        credentials: Credentials::ApiToken(3),
+        scopes,
+    }
+}
+
+#[test]
+fn api_tokens() {
+    let token = generate_api_token();
+
+    assert!(token.starts_with("qat_"));
+    assert_eq!(token.len(), 44);
+    assert_ne!(token, generate_api_token());
+}
+
+#[test]
+fn api_token_hash() {
+    let hash = hash_api_token("qat_example");
+
+    assert_eq!(hash.len(), 64);
+    assert!(hash
+        .chars()
+        .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+    assert_eq!(hash, hash_api_token("qat_example"));
+    assert_ne!(hash, hash_api_token("qat_examplf"));
+}
+
+#[test]
+fn scopes() {
+    let stored = format_scopes(&[Scope::Admin, Scope::Read, Scope::Admin]);
+
+    assert_eq!(stored, "read,admin");
+    assert_eq!(parse_scopes(&stored), vec![Scope::Read, Scope::Admin]);
+    assert_eq!(parse_scopes("write,unknown"), vec![Scope::Write]);
+    assert!(Scope::Admin > Scope::Write && Scope::Write > Scope::Read);
+}
+
+#[test]
+fn scopes_include_the_ones_before_them() {
+    let read = authenticated(vec![Scope::Read]);
+    assert!(read.has_scope(Scope::Read));
+    assert!(!read.has_scope(Scope::Write));
+    assert!(!read.has_scope(Scope::Admin));
+
+    let write = authenticated(vec![Scope::Write]);
+    assert!(write.has_scope(Scope::Read));
+    assert!(write.has_scope(Scope::Write));
+    assert!(!write.has_scope(Scope::Admin));
+
+    let admin = authenticated(vec![Scope::Admin]);
+    assert!(admin.has_scope(Scope::Read) && admin.has_scope(Scope::Write));
+
+    assert!(!authenticated(vec![]).has_scope(Scope::Read));
+}
+
+#[actix_rt::test]
+async fn require_scope_refuses_with_403() {
+    use actix_web::http::StatusCode;
+    use actix_web::test::read_body;
+    use actix_web::test::TestRequest;
+
+    let user = authenticated(vec![Scope::Read]);
+    assert!(user.require_scope(Scope::Read).is_ok());
+
+    let response = user.require_scope(Scope::Write).unwrap_err();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = read_body(TestRequest::default().to_srv_response(response)).await;
+    assert_eq!(body, "The API token lacks the `write` scope.");
+}
+
+#[cfg(feature = "sqlite")]
+mod extraction {
+    use super::common;
+    use actix_http::Request;
+    use actix_identity::{CookieIdentityPolicy, Identity, IdentityService};
+    use actix_web::cookie::Cookie;
+    use actix_web::dev::{MessageBody, Service, ServiceResponse};
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::web::{self, Data, Path};
+    use actix_web::{App, Error, HttpResponse};
+    use questionnaire_rs::api_tokens::{create_api_token, revoke_api_token};
+    use questionnaire_rs::auth::{AuthenticatedUser, Credentials, Scope};
+    use questionnaire_rs::config::{Config, ConfigFile};
+    use questionnaire_rs::session::start_session;
+    use questionnaire_rs::DbPool;
+
+    fn config() -> Config {
+        Config::from_sources(ConfigFile::default(), |key| match key {
+            "ADDRESS" => Some(String::from("127.0.0.1")),
+            "DATABASE_URL" => Some(String::from(":memory:")),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    /// Logs in as the user of the path, setting the auth cookie.
+    async fn log_in(
+        uid: Path<i32>,
+        pool: Data<DbPool>,
+        config: Data<Config>,
+        id: Identity,
+    ) -> HttpResponse {
+        let connection = pool.get().unwrap();
+        let session_token =
+            start_session(uid.into_inner(), config.session_timeouts, &connection).unwrap();
+        id.remember(session_token);
+
+        HttpResponse::Ok().finish()
+    }
+
+    /// Responds with the id of the user, and of the API token if one was used.
+    async fn whoami(user: AuthenticatedUser) -> HttpResponse {
+        let token_id = match user.credentials {
+            Credentials::ApiToken(token_id) => token_id.to_string(),
+            Credentials::Cookie => String::from("cookie"),
+        };
+
+        HttpResponse::Ok().body(format!("{} {}", user.id(), token_id))
+    }
+
+    async fn service(
+        pool: &DbPool,
+    ) -> impl Service<Request = Request, Response = ServiceResponse<impl MessageBody>, Error = Error>
+    {
+        init_service(
+            App::new()
+                .data(pool.clone())
+                .data(config())
+                .wrap(IdentityService::new(
+                    CookieIdentityPolicy::new(&[0; 32])
+                        .name("auth-cookie")
+                        .secure(false),
+                ))
+                .route("/log-in/{uid}", web::post().to(log_in))
+                .route("/whoami", web::get().to(whoami)),
+        )
+        .await
+    }
+
+    async fn log_in_cookie<S, B>(service: &mut S, uid: i32) -> Cookie<'static>
+    where
+        S: Service<Request = Request, Response = ServiceResponse<B>, Error = Error>,
+    {
+        let request = TestRequest::post()
+            .uri(&format!("/log-in/{}", uid))
+            .to_request();
+        let response = call_service(service, request).await;
+
+        response
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == "auth-cookie")
+            .unwrap()
+            .into_owned()
+    }
+
+    async fn whoami_with<S, B>(service: &mut S, request: TestRequest) -> (StatusCode, String)
+    where
+        S: Service<Request = Request, Response = ServiceResponse<B>, Error = Error>,
+        B: MessageBody + Unpin,
+    {
+        let response = call_service(service, request.uri("/whoami").to_request()).await;
+        let status = response.status();
+        let body = read_body(response).await;
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn cookie_sessions_have_every_scope() {
+        let pool = common::test_pool();
+        let alice = common::user("alice", &pool.get().unwrap());
+        let mut service = service(&pool).await;
+        let cookie = log_in_cookie(&mut service, alice).await;
+
+        let (status, body) = whoami_with(&mut service, TestRequest::get().cookie(cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, format!("{} cookie", alice));
+
+        let (status, body) = whoami_with(&mut service, TestRequest::get()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, "Could not identify the user.");
+    }
+
+    #[actix_rt::test]
+    async fn bearer_token_is_preferred_over_the_cookie() {
+        let pool = common::test_pool();
+        let (alice, bob, token_id, token) = {
+            let connection = pool.get().unwrap();
+            let alice = common::user("alice", &connection);
+            let bob = common::user("bob", &connection);
+            let (api_token, token) =
+                create_api_token(bob, String::from("CI"), &[Scope::Read], &connection).unwrap();
+
+            (alice, bob, api_token.id, token)
+        };
+        let mut service = service(&pool).await;
+        let cookie = log_in_cookie(&mut service, alice).await;
+
+        let request = TestRequest::get()
+            .cookie(cookie)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token));
+        let (status, body) = whoami_with(&mut service, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, format!("{} {}", bob, token_id));
+    }
+
+    #[actix_rt::test]
+    async fn unknown_and_revoked_tokens_are_refused() {
+        let pool = common::test_pool();
+        let (alice, token_id, token) = {
+            let connection = pool.get().unwrap();
+            let alice = common::user("alice", &connection);
+            let (api_token, token) =
+                create_api_token(alice, String::from("CI"), &[Scope::Write], &connection).unwrap();
+
+            (alice, api_token.id, token)
+        };
+        let mut service = service(&pool).await;
+        let cookie = log_in_cookie(&mut service, alice).await;
+        let bearer = |token: &str| {
+            TestRequest::get()
+                .cookie(cookie.clone())
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        };
+
+        let (status, _) = whoami_with(&mut service, bearer(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // An invalid token is refused, not replaced by the cookie.
+        let request = bearer("qat_unknown");
+        let response = call_service(&mut service, request.uri("/whoami").to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            r#"Bearer error="invalid_token""#
+        );
+
+        assert!(revoke_api_token(alice, token_id, &pool.get().unwrap()).unwrap());
+        let (status, body) = whoami_with(&mut service, bearer(&token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body, "Invalid API token.");
+    }
+}
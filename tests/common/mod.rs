@@ -0,0 +1,74 @@
+//! Helpers of the tests running against a database.
+//!
+//! They use an in-memory SQLite database, so the tests including them only run with
+//! `--no-default-features --features sqlite`.
+
+#![allow(dead_code)]
+
+use chrono::{NaiveDate, NaiveDateTime};
+use questionnaire_rs::accounts::{hash_password, register_presenter};
+use questionnaire_rs::models::{NewOption, NewPresentation, NewQuestion};
+use questionnaire_rs::options::new_option;
+use questionnaire_rs::presentations::new_presentation;
+use questionnaire_rs::questions::new_question;
+use questionnaire_rs::session::create_user;
+use questionnaire_rs::{db, migrations, DbConnection, DbPool};
+
+/// Returns the pool of a new in-memory database, with every migration applied.
+///
+/// An in-memory database lives as long as its connection, so the pool holds a single one. Code
+/// under test has to give it back before asking for another.
+pub fn test_pool() -> DbPool {
+    let pool = db::build_pool(":memory:", 1).expect("Failed to create pool.");
+    let connection = pool.get().expect("Unable to get database connection.");
+    migrations::run_pending_migrations(&connection, &mut std::io::sink())
+        .expect("Unable to run migrations.");
+
+    pool
+}
+
+pub fn creation_time() -> NaiveDateTime {
+    NaiveDate::from_ymd(2020, 11, 18).and_hms(9, 0, 0)
+}
+
+pub fn user(name: &str, connection: &DbConnection) -> i32 {
+    create_user(String::from(name), connection).unwrap().id
+}
+
+/// Creates a user with a presenter account, who may change their presentations.
+pub fn presenter(name: &str, connection: &DbConnection) -> i32 {
+    let password_hash = hash_password("correct horse battery staple").unwrap();
+
+    register_presenter(String::from(name), password_hash, connection)
+        .unwrap()
+        .id
+}
+
+pub fn presentation(user_id: i32, connection: &DbConnection) -> i32 {
+    let record = NewPresentation::new(String::from("Colours"), user_id, creation_time(), None);
+
+    new_presentation(record, connection).unwrap()
+}
+
+pub fn question(presentation_id: i32, user_id: i32, connection: &DbConnection) -> i32 {
+    let record = NewQuestion::new(
+        String::from("What is your favourite colour?"),
+        creation_time(),
+        presentation_id,
+        user_id,
+    );
+
+    new_question(record, connection).unwrap()
+}
+
+pub fn option(data: &str, question_id: i32, user_id: i32, connection: &DbConnection) -> i32 {
+    let record = NewOption::new(
+        String::from(data),
+        user_id,
+        question_id,
+        creation_time(),
+        false,
+    );
+
+    new_option(record, connection).unwrap()
+}
@@ -0,0 +1,312 @@
+extern crate questionnaire_rs;
+
+use questionnaire_rs::config::{Config, ConfigFile, RateLimit};
+use std::collections::HashMap;
+
+fn load(file: ConfigFile, vars: &[(&str, &str)]) -> Result<Config, Vec<String>> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+    Config::from_sources(file, |key| vars.get(key).cloned())
+        .map_err(|error| error.errors.into_iter().map(|error| error.path).collect())
+}
+
+#[test]
+fn defaults() {
+    let config = load(
+        ConfigFile::default(),
+        &[
+            ("ADDRESS", "127.0.0.1"),
+            ("DATABASE_URL", "questionnaire.db"),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(config.bind_address(), "127.0.0.1:8088");
+    assert_eq!(config.max_database_pool_size, 10);
+    assert_eq!(config.front_end_base_url, None);
+    assert!(config.run_migrations);
+    assert!(!config.trust_forwarded_for);
+}
+
+#[test]
+fn environment_overrides_file() {
+    let file: ConfigFile = toml::from_str(
+        r#"
+address = "0.0.0.0"
+port = 3000
+database_url = "questionnaire.db"
+run_migrations = false
+"#,
+    )
+    .unwrap();
+    let config = load(file, &[("PORT", "4000")]).unwrap();
+
+    assert_eq!(config.bind_address(), "0.0.0.0:4000");
+    assert!(!config.run_migrations);
+}
+
+#[test]
+fn reports_every_error() {
+    let errors = load(
+        ConfigFile::default(),
+        &[
+            ("PORT", "http"),
+            ("MAX_DATABASE_POOL_SIZE", "0"),
+            ("FRONT_END_BASE_URL", "http://localhost:3000/"),
+        ],
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        errors,
+        vec![
+            "PORT",
+            "ADDRESS",
+            "DATABASE_URL",
+            "MAX_DATABASE_POOL_SIZE",
+            "FRONT_END_BASE_URL"
+        ]
+    );
+}
+
+#[test]
+fn cookie_secret() {
+    let short = "too short";
+    let long = "0123456789abcdef0123456789abcdef";
+
+    let errors = load(
+        ConfigFile::default(),
+        &[
+            ("ADDRESS", "127.0.0.1"),
+            ("DATABASE_URL", "questionnaire.db"),
+            ("ENVIRONMENT", "production"),
+        ],
+    )
+    .unwrap_err();
+    assert_eq!(errors, vec!["COOKIE_SECRET"]);
+
+    let errors = load(
+        ConfigFile::default(),
+        &[
+            ("ADDRESS", "127.0.0.1"),
+            ("DATABASE_URL", "questionnaire.db"),
+            ("COOKIE_SECRET", short),
+        ],
+    )
+    .unwrap_err();
+    assert_eq!(errors, vec!["COOKIE_SECRET"]);
+
+    let old_secrets = format!("{}, {}", long, long.to_uppercase());
+    let config = load(
+        ConfigFile::default(),
+        &[
+            ("ADDRESS", "127.0.0.1"),
+            ("DATABASE_URL", "questionnaire.db"),
+            ("ENVIRONMENT", "production"),
+            ("COOKIE_SECRET", long),
+            ("OLD_COOKIE_SECRETS", &old_secrets),
+        ],
+    )
+    .unwrap();
+    assert_eq!(config.cookie_secret.as_deref(), Some(long));
+    assert_eq!(config.old_cookie_secrets.len(), 2);
+}
+
+#[test]
+fn session_timeouts() {
+    let required = [
+        ("ADDRESS", "127.0.0.1"),
+        ("DATABASE_URL", "questionnaire.db"),
+    ];
+
+    let timeouts = load(ConfigFile::default(), &required)
+        .unwrap()
+        .session_timeouts;
+    assert_eq!(timeouts.idle, 120);
+    assert_eq!(timeouts.absolute, 10080);
+
+    let mut vars = required.to_vec();
+    vars.push(("SESSION_IDLE_TIMEOUT", "0"));
+    vars.push(("SESSION_ABSOLUTE_TIMEOUT", "soon"));
+    assert_eq!(
+        load(ConfigFile::default(), &vars).unwrap_err(),
+        vec!["SESSION_ABSOLUTE_TIMEOUT", "SESSION_IDLE_TIMEOUT"]
+    );
+
+    let file: ConfigFile = toml::from_str("session_idle_timeout = 30").unwrap();
+    let mut vars = required.to_vec();
+    vars.push(("SESSION_ABSOLUTE_TIMEOUT", "20"));
+    assert_eq!(
+        load(file, &vars).unwrap_err(),
+        vec!["SESSION_ABSOLUTE_TIMEOUT"]
+    );
+}
+
+#[test]
+fn rate_limits() {
+    let required = [
+        ("ADDRESS", "127.0.0.1"),
+        ("DATABASE_URL", "questionnaire.db"),
+    ];
+
+    let limits = load(ConfigFile::default(), &required).unwrap().rate_limits;
+    assert_eq!(
+        limits.auth,
+        Some(RateLimit {
+            requests: 10,
+            seconds: 60
+        })
+    );
+    assert_eq!(
+        limits.join,
+        Some(RateLimit {
+            requests: 300,
+            seconds: 60
+        })
+    );
+
+    let file: ConfigFile = toml::from_str(r#"rate_limit_read = "off""#).unwrap();
+    let mut vars = required.to_vec();
+    vars.push(("RATE_LIMIT_WRITE", "30/10"));
+    vars.push(("TRUST_FORWARDED_FOR", "true"));
+    let config = load(file, &vars).unwrap();
+    assert!(config.trust_forwarded_for);
+    let limits = config.rate_limits;
+    assert_eq!(limits.read, None);
+    assert_eq!(
+        limits.write,
+        Some(RateLimit {
+            requests: 30,
+            seconds: 10
+        })
+    );
+
+    let mut vars = required.to_vec();
+    vars.push(("RATE_LIMIT_AUTH", "10"));
+    vars.push(("RATE_LIMIT_WEB_SOCKET", "5/0"));
+    assert_eq!(
+        load(ConfigFile::default(), &vars).unwrap_err(),
+        vec!["RATE_LIMIT_AUTH", "RATE_LIMIT_WEB_SOCKET"]
+    );
+}
+
+#[test]
+fn github() {
+    let required = [
+        ("ADDRESS", "127.0.0.1"),
+        ("DATABASE_URL", "questionnaire.db"),
+    ];
+
+    let config = load(ConfigFile::default(), &required).unwrap();
+    assert!(config.github.is_none());
+
+    let mut vars = required.to_vec();
+    vars.push(("GITHUB_CLIENT_ID", "client"));
+    assert_eq!(
+        load(ConfigFile::default(), &vars).unwrap_err(),
+        vec!["GITHUB_CLIENT_SECRET"]
+    );
+
+    vars.push(("GITHUB_CLIENT_SECRET", "secret"));
+    vars.push(("GITHUB_API_URL", "http://localhost:9000/api/"));
+    let github = load(ConfigFile::default(), &vars).unwrap().github.unwrap();
+    assert_eq!(github.api_url, "http://localhost:9000/api");
+    assert_eq!(
+        github.token_url,
+        "https://github.com/login/oauth/access_token"
+    );
+}
+
+#[test]
+fn oidc() {
+    let required = [
+        ("ADDRESS", "127.0.0.1"),
+        ("DATABASE_URL", "questionnaire.db"),
+    ];
+
+    let config = load(ConfigFile::default(), &required).unwrap();
+    assert!(config.oidc.is_none());
+
+    let mut vars = required.to_vec();
+    vars.push(("OIDC_ISSUER_URL", "https://accounts.example.com/"));
+    vars.push(("OIDC_SCOPES", "profile"));
+    assert_eq!(
+        load(ConfigFile::default(), &vars).unwrap_err(),
+        vec![
+            "OIDC_CLIENT_ID",
+            "OIDC_CLIENT_SECRET",
+            "OIDC_REDIRECT_URL",
+            "OIDC_SCOPES"
+        ]
+    );
+
+    vars.pop();
+    vars.push(("OIDC_CLIENT_ID", "client"));
+    vars.push(("OIDC_CLIENT_SECRET", "secret"));
+    vars.push((
+        "OIDC_REDIRECT_URL",
+        "https://questionnaire.example.com/auth/oidc/callback",
+    ));
+    let oidc = load(ConfigFile::default(), &vars).unwrap().oidc.unwrap();
+    assert_eq!(oidc.issuer_url, "https://accounts.example.com");
+    assert_eq!(oidc.scopes, "openid profile email");
+}
+
+#[test]
+fn debug_output_redacts_secrets() {
+    let vars = [
+        ("ADDRESS", "127.0.0.1"),
+        ("DATABASE_URL", "questionnaire.db"),
+        ("COOKIE_SECRET", "current-cookie-secret-0123456789abcdef"),
+        ("OLD_COOKIE_SECRETS", "retired-cookie-secret-0123456789abcdef"),
+        ("GITHUB_CLIENT_ID", "github-client"),
+        ("GITHUB_CLIENT_SECRET", "github-client-secret"),
+        ("OIDC_ISSUER_URL", "https://accounts.example.com/"),
+        ("OIDC_CLIENT_ID", "oidc-client"),
+        ("OIDC_CLIENT_SECRET", "oidc-client-secret"),
+        (
+            "OIDC_REDIRECT_URL",
+            "https://questionnaire.example.com/auth/oidc/callback",
+        ),
+    ];
+    let config = load(ConfigFile::default(), &vars).unwrap();
+    let file: ConfigFile = toml::from_str(
+        r#"
+cookie_secret = "current-cookie-secret-0123456789abcdef"
+old_cookie_secrets = ["retired-cookie-secret-0123456789abcdef"]
+github_client_secret = "github-client-secret"
+oidc_client_secret = "oidc-client-secret"
+"#,
+    )
+    .unwrap();
+
+    for output in &[format!("{:?}", config), format!("{:?}", file)] {
+        assert!(!output.contains("cookie-secret"), "{}", output);
+        assert!(!output.contains("client-secret"), "{}", output);
+        assert!(output.contains("<redacted>"));
+    }
+    assert!(format!("{:?}", config).contains("github-client"));
+}
+
+#[test]
+fn database_url_alone() {
+    let vars: HashMap<&str, &str> = vec![
+        ("DATABASE_URL", "questionnaire.db"),
+        ("ENVIRONMENT", "production"),
+    ]
+    .into_iter()
+    .collect();
+
+    // Neither `ADDRESS` nor a production `COOKIE_SECRET` is needed.
+    let database_url = Config::database_url_from_sources(ConfigFile::default(), |key| {
+        vars.get(key).map(|value| value.to_string())
+    })
+    .unwrap();
+    assert_eq!(database_url, "questionnaire.db");
+
+    let error = Config::database_url_from_sources(ConfigFile::default(), |_| None).unwrap_err();
+    assert_eq!(error.errors[0].path, "DATABASE_URL");
+}
@@ -0,0 +1,103 @@
+extern crate actix_web;
+extern crate questionnaire_rs;
+
+use actix_web::http::header;
+use actix_web::test::TestRequest;
+use questionnaire_rs::csrf::CsrfProtection;
+
+fn protection() -> CsrfProtection {
+    CsrfProtection::new(vec![String::from("https://questionnaire.example")])
+}
+
+fn allows(request: TestRequest) -> bool {
+    protection().allows(&request.header(header::HOST, "api.example").to_srv_request())
+}
+
+#[test]
+fn allows_safe_methods() {
+    assert!(allows(
+        TestRequest::get().header(header::ORIGIN, "https://evil.example")
+    ));
+}
+
+#[test]
+fn checks_origin() {
+    assert!(allows(
+        TestRequest::post().header(header::ORIGIN, "https://questionnaire.example")
+    ));
+    assert!(allows(
+        TestRequest::post().header(header::ORIGIN, "https://api.example")
+    ));
+    assert!(!allows(
+        TestRequest::post().header(header::ORIGIN, "https://evil.example")
+    ));
+    assert!(!allows(TestRequest::post().header(header::ORIGIN, "null")));
+    // The origin decides, even with a trusted referer.
+    assert!(!allows(
+        TestRequest::post()
+            .header(header::ORIGIN, "https://evil.example")
+            .header(header::REFERER, "https://questionnaire.example/")
+    ));
+}
+
+#[test]
+fn falls_back_to_referer() {
+    assert!(allows(
+        TestRequest::post().header(header::REFERER, "https://questionnaire.example/p/3")
+    ));
+    assert!(!allows(
+        TestRequest::post().header(header::REFERER, "https://evil.example/questionnaire")
+    ));
+    assert!(allows(TestRequest::post()));
+}
+
+#[test]
+fn exempts_token_authentication() {
+    assert!(allows(
+        TestRequest::post()
+            .header(header::ORIGIN, "https://evil.example")
+            .header(header::AUTHORIZATION, "Bearer qat_example")
+    ));
+    assert!(allows(
+        TestRequest::post()
+            .uri("/answers")
+            .header(header::ORIGIN, "https://evil.example")
+            .header("X-Device-Token", "5.abc")
+    ));
+    // Other routes ignore the device token and use the auth cookie.
+    assert!(!allows(
+        TestRequest::post()
+            .uri("/presentations")
+            .header(header::ORIGIN, "https://evil.example")
+            .header("X-Device-Token", "5.abc")
+    ));
+    assert!(!allows(
+        TestRequest::post()
+            .header(header::ORIGIN, "https://evil.example")
+            .header(header::AUTHORIZATION, "Basic dXNlcjpwYXNz")
+    ));
+}
+
+#[test]
+fn trusts_origin_of_web_socket_upgrades() {
+    let trusts = |request: TestRequest| {
+        protection().trusts_origin(
+            &request
+                .header(header::HOST, "api.example")
+                .to_http_request(),
+        )
+    };
+
+    assert!(trusts(
+        TestRequest::get().header(header::ORIGIN, "https://questionnaire.example")
+    ));
+    assert!(trusts(
+        TestRequest::get().header(header::ORIGIN, "https://api.example")
+    ));
+    assert!(trusts(TestRequest::get()));
+    // Unlike `allows`, safe methods are checked as well.
+    assert!(!trusts(
+        TestRequest::get().header(header::ORIGIN, "https://evil.example")
+    ));
+    assert!(!trusts(TestRequest::get().header(header::ORIGIN, "null")));
+}
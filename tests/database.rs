@@ -0,0 +1,43 @@
+#![cfg(feature = "sqlite")]
+
+extern crate diesel;
+extern crate questionnaire_rs;
+
+mod common;
+
+use common::test_pool;
+use diesel::result::Error as DieselError;
+use questionnaire_rs::migrations::pending_versions;
+use questionnaire_rs::models::NewQuestion;
+use questionnaire_rs::questions::new_question;
+use questionnaire_rs::session::get_users;
+
+#[test]
+fn migrations_apply_to_a_new_database() {
+    let pool = test_pool();
+    let connection = pool.get().unwrap();
+
+    assert!(pending_versions(&connection).unwrap().is_empty());
+    assert!(get_users(&connection).unwrap().is_empty());
+}
+
+#[test]
+fn foreign_keys_are_enforced() {
+    let pool = test_pool();
+    let connection = pool.get().unwrap();
+    let user_id = common::user("alice", &connection);
+
+    let record = NewQuestion::new(
+        String::from("What is your favourite colour?"),
+        common::creation_time(),
+        42,
+        user_id,
+    );
+
+    match new_question(record, &connection) {
+        Err(DieselError::DatabaseError(_, info)) => {
+            assert_eq!(info.message(), "FOREIGN KEY constraint failed")
+        }
+        result => panic!("Expected a foreign key error, got {:?}", result),
+    }
+}
@@ -0,0 +1,133 @@
+extern crate questionnaire_rs;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use questionnaire_rs::documents::{parse_document, validate_document, DocumentFormat};
+use questionnaire_rs::models::PresentationSettings;
+
+#[test]
+fn parse_yaml() {
+    let input = "
+version: 1
+title: New Presentation
+questions:
+  - title: New Question
+    options:
+      - data: Option 1
+      - data: Option 2
+";
+    let document = parse_document(input, DocumentFormat::Yaml).unwrap();
+
+    assert_eq!(document.title, "New Presentation");
+    assert_eq!(document.questions.len(), 1);
+    assert_eq!(document.questions[0].options[1].data, "Option 2");
+    assert_eq!(document.settings, PresentationSettings::default());
+    assert!(validate_document(&document).is_empty());
+}
+
+#[test]
+fn parse_settings() {
+    let input = "
+version: 1
+title: New Presentation
+settings:
+  voting_duration: 30
+  shuffle_options: true
+";
+    let document = parse_document(input, DocumentFormat::Yaml).unwrap();
+
+    // Settings of newer releases are ignored.
+    assert_eq!(document.settings.voting_duration, Some(30));
+    assert!(validate_document(&document).is_empty());
+
+    let input = r#"{"version": 1, "title": "New Presentation", "settings": {}}"#;
+    let document = parse_document(input, DocumentFormat::Json).unwrap();
+
+    assert_eq!(document.settings.voting_duration, None);
+}
+
+#[test]
+fn settings_errors() {
+    let input =
+        r#"{"version": 1, "title": "New Presentation", "settings": {"voting_duration": -1}}"#;
+    let error = parse_document(input, DocumentFormat::Json).unwrap_err();
+
+    assert_eq!(error.path, "settings.voting_duration");
+
+    let input =
+        r#"{"version": 1, "title": "New Presentation", "settings": {"voting_duration": 0}}"#;
+    let document = parse_document(input, DocumentFormat::Json).unwrap();
+    let errors = validate_document(&document);
+
+    assert_eq!(errors[0].path, "settings.voting_duration");
+    assert_eq!(errors[0].message, "must be between 1 and 86400 seconds");
+}
+
+#[test]
+fn parse_error_path() {
+    let input = r#"{
+        "version": 1,
+        "title": "New Presentation",
+        "questions": [{"title": "New Question", "options": [{"data": 42}]}]
+    }"#;
+    let error = parse_document(input, DocumentFormat::Json).unwrap_err();
+
+    assert_eq!(error.path, "questions[0].options[0].data");
+}
+
+#[test]
+fn validation_errors() {
+    let input = r#"{
+        "version": 2,
+        "title": " New Presentation ",
+        "questions": [
+            {"title": "New Question", "options": [{"data": "Option 1"}, {"data": " "}]}
+        ]
+    }"#;
+    let document = parse_document(input, DocumentFormat::Json).unwrap();
+    let errors = validate_document(&document);
+    let paths: Vec<&str> = errors.iter().map(|error| error.path.as_str()).collect();
+
+    // The same rules as `/presentations`, which refuses titles starting or ending with whitespace.
+    assert_eq!(
+        paths,
+        vec!["version", "title", "questions[0].options[1].data"]
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn settings_survive_export_and_import() {
+    use questionnaire_rs::documents::{export_presentation, import_presentation};
+    use questionnaire_rs::questions::get_voting_duration;
+
+    let pool = common::test_pool();
+    let connection = pool.get().unwrap();
+    let user_id = common::user("alice", &connection);
+
+    let input = r#"{
+        "version": 1,
+        "title": "New Presentation",
+        "settings": {"voting_duration": 30},
+        "questions": [{"title": "New Question", "options": [{"data": "Option 1"}]}]
+    }"#;
+    let document = parse_document(input, DocumentFormat::Json).unwrap();
+    let presentation_id = import_presentation(&document, user_id, &connection).unwrap();
+    let exported = export_presentation(presentation_id, &connection).unwrap();
+
+    assert_eq!(exported.settings.voting_duration, Some(30));
+    assert_eq!(exported.questions[0].options[0].data, "Option 1");
+
+    let question_id = common::question(presentation_id, user_id, &connection);
+    assert_eq!(
+        get_voting_duration(question_id, &connection).unwrap(),
+        Some(30)
+    );
+
+    let plain = common::presentation(user_id, &connection);
+    assert_eq!(
+        export_presentation(plain, &connection).unwrap().settings,
+        PresentationSettings::default()
+    );
+}
@@ -0,0 +1,118 @@
+extern crate chrono;
+extern crate questionnaire_rs;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use chrono::NaiveDate;
+use questionnaire_rs::export::{encode_rows, ExportFormat};
+use questionnaire_rs::models::ExportRow;
+
+fn row(answer_id: i32, user: Option<(i32, &str)>, participant_id: Option<i32>) -> ExportRow {
+    ExportRow {
+        answer_id,
+        question_id: 23,
+        question_title: String::from("Red, green or \"blue\"?"),
+        option_id: 12,
+        option_data: String::from("Red"),
+        user_id: user.map(|(id, _)| id),
+        user_name: user.map(|(_, name)| String::from(name)),
+        participant_id,
+        created: NaiveDate::from_ymd(2019, 11, 1).and_hms(14, 30, 30),
+    }
+}
+
+fn encode(rows: &[ExportRow], format: ExportFormat, with_header: bool) -> String {
+    String::from_utf8(encode_rows(rows, format, with_header).unwrap()).unwrap()
+}
+
+#[test]
+fn csv_rows() {
+    let rows = [row(47, Some((7, "agent 42")), None), row(48, None, Some(5))];
+
+    assert_eq!(
+        encode(&rows, ExportFormat::Csv, true),
+        "answer_id,question_id,question_title,option_id,option_data,user_id,user_name,participant_id,created\n\
+         47,23,\"Red, green or \"\"blue\"\"?\",12,Red,7,agent 42,,2019-11-01T14:30:30\n\
+         48,23,\"Red, green or \"\"blue\"\"?\",12,Red,,,5,2019-11-01T14:30:30\n"
+    );
+}
+
+#[test]
+fn csv_header_is_only_written_once() {
+    let rows = [row(49, None, Some(5))];
+
+    assert!(!encode(&rows, ExportFormat::Csv, false).starts_with("answer_id"));
+    assert_eq!(
+        encode(&[], ExportFormat::Csv, true),
+        "answer_id,question_id,question_title,option_id,option_data,user_id,user_name,participant_id,created\n"
+    );
+    assert_eq!(encode(&[], ExportFormat::Csv, false), "");
+}
+
+#[test]
+fn jsonl_rows() {
+    let rows = [row(47, Some((7, "agent 42")), None), row(48, None, Some(5))];
+    let output = encode(&rows, ExportFormat::Jsonl, true);
+    let lines: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert!(output.ends_with('\n'));
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["question_title"], "Red, green or \"blue\"?");
+    assert_eq!(lines[0]["user_name"], "agent 42");
+    assert_eq!(lines[0]["participant_id"], serde_json::Value::Null);
+    assert_eq!(lines[1]["user_id"], serde_json::Value::Null);
+    assert_eq!(lines[1]["user_name"], serde_json::Value::Null);
+    assert_eq!(lines[1]["participant_id"], 5);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn export_rows_are_loaded_in_batches() {
+    use questionnaire_rs::answers::{new_answer, new_participant_answer};
+    use questionnaire_rs::export::get_export_rows;
+    use questionnaire_rs::participants::create_participant;
+    use questionnaire_rs::questions::open_question;
+
+    let pool = common::test_pool();
+    let connection = pool.get().unwrap();
+    let presenter = common::user("presenter", &connection);
+    let alice = common::user("alice", &connection);
+    let presentation_id = common::presentation(presenter, &connection);
+    let question_id = common::question(presentation_id, presenter, &connection);
+    let red = common::option("Red", question_id, presenter, &connection);
+    open_question(question_id, None, common::creation_time(), &connection).unwrap();
+
+    // Answers of other presentations are left out.
+    let other_presentation = common::presentation(presenter, &connection);
+    let other_question = common::question(other_presentation, presenter, &connection);
+    let other_option = common::option("Blue", other_question, presenter, &connection);
+    open_question(other_question, None, common::creation_time(), &connection).unwrap();
+
+    let participant = create_participant(presentation_id, &connection).unwrap();
+    new_answer(red, alice, &connection).unwrap();
+    new_answer(other_option, alice, &connection).unwrap();
+    new_answer(red, presenter, &connection).unwrap();
+    new_participant_answer(red, participant.id, &connection).unwrap();
+
+    let first = get_export_rows(presentation_id, 0, 2, &connection).unwrap();
+    assert_eq!(first.len(), 2);
+    assert_eq!(first[0].user_name.as_ref().unwrap(), "alice");
+    assert_eq!(first[1].user_name.as_ref().unwrap(), "presenter");
+
+    let second = get_export_rows(presentation_id, first[1].answer_id, 2, &connection).unwrap();
+    assert_eq!(second.len(), 1);
+    assert_eq!(second[0].user_id, None);
+    assert_eq!(second[0].user_name, None);
+    assert_eq!(second[0].participant_id, Some(participant.id));
+    assert_eq!(second[0].option_data, "Red");
+
+    assert!(
+        get_export_rows(presentation_id, second[0].answer_id, 2, &connection)
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,55 @@
+extern crate questionnaire_rs;
+
+use questionnaire_rs::markdown::parse_markdown;
+
+#[test]
+fn parse() {
+    let input = "# New Presentation
+
+Some speaker notes.
+
+## New Question
+
+- [x] Option 1
+- [ ] Option 2
+
+## Another Question ##
+
+1. Option A
+2. Option B
+";
+    let document = parse_markdown(input).unwrap();
+
+    assert_eq!(document.title, "New Presentation");
+    assert_eq!(document.questions.len(), 2);
+    assert_eq!(document.questions[0].title, "New Question");
+    assert_eq!(document.questions[0].options[0].data, "Option 1");
+    assert!(document.questions[0].options[0].is_correct);
+    assert!(!document.questions[0].options[1].is_correct);
+    assert_eq!(document.questions[1].title, "Another Question");
+    assert_eq!(document.questions[1].options[1].data, "Option B");
+}
+
+#[test]
+fn option_outside_question() {
+    let error = parse_markdown("# New Presentation\n\n- Option 1\n").unwrap_err();
+
+    assert_eq!(error.path, "line 3");
+}
+
+#[test]
+fn missing_title() {
+    let error = parse_markdown("## New Question\n").unwrap_err();
+
+    assert_eq!(error.path, "title");
+}
+
+#[test]
+fn closing_hashes_need_whitespace_before_them() {
+    let input = "# Languages #\n\n## C#\n\n## F# ##\n\n- F#\n";
+    let document = parse_markdown(input).unwrap();
+
+    assert_eq!(document.title, "Languages");
+    assert_eq!(document.questions[0].title, "C#");
+    assert_eq!(document.questions[1].title, "F#");
+}
@@ -0,0 +1,318 @@
+extern crate jsonwebtoken;
+extern crate questionnaire_rs;
+extern crate serde_json;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use questionnaire_rs::config::OidcConfig;
+use questionnaire_rs::oauth::OAuthError;
+use questionnaire_rs::oidc::{validate_id_token, IdTokenClaims, Jwks, OidcProvider};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SIGNING_KEY: &[u8] = include_bytes!("fixtures/oidc-signing-key.pem");
+const OTHER_KEY: &[u8] = include_bytes!("fixtures/oidc-other-key.pem");
+
+/// Public modulus of `SIGNING_KEY`, as published in the JWKS.
+const SIGNING_KEY_MODULUS: &str = "043vejbeK17lgQp7N3eLMOZfHdUpCD2AP_H-OYyYTks9qVJnNaYDHLfPgF_JUKJTIUrFq9d8tjiZ169TaQotgM5BQCNRc1qd8TeLbscUIcrSq2jaqwdYStqS3EX0A40Q2Nwxavlmk2dWBeqzhgH3wAYosr4q_31w7TxdGs0H9VLCtvQfRY9RnZvF5TpWPo194NSNZEQwEdP7Nv1Wv3BLxPQwfa9AMKSl7hD_uKsDZlqf2IVh4TgtL5u7dTOTr9I15r05GeP0aqi4xVfZNbjwE9j3X-T4ots7U-sWZqWstHK29Z8HPPZQavlYeCLr0x58NXkuBg9NMP6p_0sHZCcrsw";
+
+const KID: &str = "test-key";
+const CLIENT_ID: &str = "questionnaire";
+const NONCE: &str = "n0nce";
+
+/// A local issuer serving the discovery document, the signing keys and a token endpoint, which
+/// returns `id_token` for the code `good`.
+struct StubIssuer {
+    url: String,
+    id_token: Arc<Mutex<String>>,
+}
+
+impl StubIssuer {
+    fn start() -> StubIssuer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let id_token = Arc::new(Mutex::new(String::new()));
+
+        let issuer = url.clone();
+        let token = id_token.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                respond(stream.unwrap(), &issuer, &token.lock().unwrap());
+            }
+        });
+
+        StubIssuer { url, id_token }
+    }
+
+    fn provider(&self) -> OidcProvider {
+        OidcProvider::new(OidcConfig {
+            issuer_url: self.url.clone(),
+            client_id: String::from(CLIENT_ID),
+            client_secret: String::from("secret"),
+            redirect_url: String::from("http://localhost:8088/auth/oidc/callback"),
+            scopes: String::from("openid profile"),
+        })
+    }
+
+    fn set_id_token(&self, token: String) {
+        *self.id_token.lock().unwrap() = token;
+    }
+}
+
+fn respond(stream: TcpStream, issuer: &str, id_token: &str) {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+
+    let mut content_length = 0;
+    let mut authorized = false;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end().to_lowercase();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(length) = line.strip_prefix("content-length:") {
+            content_length = length.trim().parse().unwrap();
+        }
+        if line.starts_with("authorization: basic ") {
+            authorized = true;
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    let body = String::from_utf8(body).unwrap();
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, response) = match path {
+        "/.well-known/openid-configuration" => (
+            "200 OK",
+            json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{}/authorize", issuer),
+                "token_endpoint": format!("{}/token", issuer),
+                "jwks_uri": format!("{}/jwks", issuer),
+            }),
+        ),
+        "/jwks" => (
+            "200 OK",
+            json!({"keys": [{
+                "kty": "RSA",
+                "kid": KID,
+                "alg": "RS256",
+                "use": "sig",
+                "n": SIGNING_KEY_MODULUS,
+                "e": "AQAB",
+            }]}),
+        ),
+        "/token" if authorized && body.contains("code=good") => {
+            ("200 OK", json!({ "id_token": id_token }))
+        }
+        "/token" => ("400 Bad Request", json!({"error": "invalid_grant"})),
+        _ => ("404 Not Found", json!({})),
+    };
+
+    let response = response.to_string();
+    write!(
+        reader.get_mut(),
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        response.len(),
+        response
+    )
+    .unwrap();
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn claims(issuer: &str) -> Value {
+    json!({
+        "iss": issuer,
+        "aud": CLIENT_ID,
+        "sub": "248289761001",
+        "exp": now() + 300,
+        "iat": now(),
+        "nonce": NONCE,
+        "preferred_username": "jane",
+        "email": "jane@example.com",
+    })
+}
+
+fn sign(claims: &Value, key: &[u8], kid: Option<&str>) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = kid.map(String::from);
+
+    encode(&header, claims, &EncodingKey::from_rsa_pem(key).unwrap()).unwrap()
+}
+
+fn jwks() -> Jwks {
+    serde_json::from_value(json!({"keys": [{
+        "kty": "RSA",
+        "kid": KID,
+        "n": SIGNING_KEY_MODULUS,
+        "e": "AQAB",
+    }]}))
+    .unwrap()
+}
+
+fn validate(claims: &Value, key: &[u8]) -> Result<IdTokenClaims, String> {
+    let token = sign(claims, key, Some(KID));
+
+    validate_id_token(
+        &token,
+        &jwks(),
+        "https://issuer.example.com",
+        CLIENT_ID,
+        NONCE,
+    )
+    .map_err(|error| match error {
+        OAuthError::InvalidToken(message) => message,
+        error => panic!("unexpected error: {}", error),
+    })
+}
+
+#[test]
+fn login_with_stub_issuer() {
+    let issuer = StubIssuer::start();
+    issuer.set_id_token(sign(&claims(&issuer.url), SIGNING_KEY, Some(KID)));
+    let provider = issuer.provider();
+
+    let url = provider.authorize_url("st4te", NONCE).unwrap();
+    assert!(url
+        .as_str()
+        .starts_with(&format!("{}/authorize?", issuer.url)));
+    assert!(url
+        .query_pairs()
+        .any(|(key, value)| key == "nonce" && value == NONCE));
+
+    let claims = provider.exchange_code("good", NONCE).unwrap();
+    assert_eq!(claims.sub, "248289761001");
+    assert_eq!(claims.preferred_name(), "jane");
+
+    match provider.exchange_code("expired", NONCE) {
+        Err(OAuthError::Provider(message)) => assert_eq!(message, "invalid_grant"),
+        result => panic!("unexpected result: {:?}", result.map(|claims| claims.sub)),
+    }
+}
+
+#[test]
+fn rejects_unknown_signing_key() {
+    let issuer = StubIssuer::start();
+    issuer.set_id_token(sign(&claims(&issuer.url), OTHER_KEY, Some("rotated-key")));
+
+    match issuer.provider().exchange_code("good", NONCE) {
+        Err(OAuthError::InvalidToken(message)) => assert_eq!(message, "unknown signing key"),
+        result => panic!("unexpected result: {:?}", result.map(|claims| claims.sub)),
+    }
+}
+
+#[test]
+fn validates_id_token() {
+    let valid = claims("https://issuer.example.com");
+    assert_eq!(validate(&valid, SIGNING_KEY).unwrap().sub, "248289761001");
+
+    assert_eq!(
+        validate(&valid, OTHER_KEY).unwrap_err(),
+        "signature does not match"
+    );
+
+    let mut wrong_audience = valid.clone();
+    wrong_audience["aud"] = json!(["someone-else"]);
+    assert_eq!(
+        validate(&wrong_audience, SIGNING_KEY).unwrap_err(),
+        "wrong audience"
+    );
+
+    let mut wrong_issuer = valid.clone();
+    wrong_issuer["iss"] = json!("https://evil.example.com");
+    assert_eq!(
+        validate(&wrong_issuer, SIGNING_KEY).unwrap_err(),
+        "wrong issuer"
+    );
+
+    let mut wrong_nonce = valid.clone();
+    wrong_nonce["nonce"] = json!("replayed");
+    assert_eq!(
+        validate(&wrong_nonce, SIGNING_KEY).unwrap_err(),
+        "nonce does not match"
+    );
+
+    let mut expired = valid;
+    expired["exp"] = json!(now() - 3600);
+    assert_eq!(
+        validate(&expired, SIGNING_KEY).unwrap_err(),
+        "token expired"
+    );
+}
+
+#[cfg(feature = "sqlite")]
+mod linking {
+    use super::{claims, common, sign, StubIssuer, KID, NONCE, SIGNING_KEY};
+    use questionnaire_rs::accounts::{hash_password, register_presenter};
+
+    fn issuer() -> StubIssuer {
+        let issuer = StubIssuer::start();
+        issuer.set_id_token(sign(&claims(&issuer.url), SIGNING_KEY, Some(KID)));
+
+        issuer
+    }
+
+    #[test]
+    fn first_login_creates_a_user() {
+        let provider = issuer().provider();
+        let pool = common::test_pool();
+
+        let user = provider
+            .login_with_code("good", NONCE, None, &pool)
+            .unwrap();
+        assert_eq!(user.name, "jane");
+
+        let again = provider
+            .login_with_code("good", NONCE, None, &pool)
+            .unwrap();
+        assert_eq!(again.id, user.id);
+    }
+
+    #[test]
+    fn presenters_are_linked() {
+        let provider = issuer().provider();
+        let pool = common::test_pool();
+        let presenter = {
+            let connection = pool.get().unwrap();
+            let password_hash = hash_password("correct horse battery staple").unwrap();
+            register_presenter(String::from("presenter"), password_hash, &connection)
+                .unwrap()
+                .id
+        };
+
+        let user = provider
+            .login_with_code("good", NONCE, Some(presenter), &pool)
+            .unwrap();
+        assert_eq!(user.id, presenter);
+    }
+
+    #[test]
+    fn audience_members_are_not_linked() {
+        let provider = issuer().provider();
+        let pool = common::test_pool();
+        let audience_member = common::user("jane", &pool.get().unwrap());
+
+        let user = provider
+            .login_with_code("good", NONCE, Some(audience_member), &pool)
+            .unwrap();
+        assert_ne!(user.id, audience_member);
+        assert_eq!(user.name, "jane 2");
+    }
+}
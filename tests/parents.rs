@@ -0,0 +1,117 @@
+extern crate actix_web;
+extern crate diesel;
+extern crate questionnaire_rs;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
+use diesel::result::Error as DieselError;
+use questionnaire_rs::parents::{attach_error_response, AttachError, Parent};
+
+#[test]
+fn attach_error_responses() {
+    let status = |error| attach_error_response(BlockingError::Error(error)).status();
+
+    assert_eq!(
+        status(AttachError::NotFound(Parent::Presentation(3))),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(AttachError::Forbidden(Parent::Question(4))),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        status(AttachError::Database(DieselError::RollbackTransaction)),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert_eq!(
+        attach_error_response(BlockingError::Canceled).status(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
+
+#[test]
+fn attach_error_messages() {
+    assert_eq!(
+        AttachError::NotFound(Parent::Option(5)).message(),
+        "Could not find the option."
+    );
+    assert_eq!(
+        AttachError::Forbidden(Parent::Option(5)).message(),
+        "Not allowed to add to the option."
+    );
+}
+
+#[cfg(feature = "sqlite")]
+mod presenters {
+    use super::common;
+    use questionnaire_rs::parents::{AttachError, Parent};
+
+    #[test]
+    fn presenters_own_every_parent_of_their_presentation() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::presenter("presenter", &connection);
+        let alice = common::user("alice", &connection);
+        let presentation_id = common::presentation(presenter, &connection);
+        let question_id = common::question(presentation_id, presenter, &connection);
+        let option_id = common::option("Red", question_id, presenter, &connection);
+
+        for parent in &[
+            Parent::Presentation(presentation_id),
+            Parent::Question(question_id),
+            Parent::Option(option_id),
+        ] {
+            assert_eq!(
+                parent.presentation(&connection).unwrap(),
+                Some((presentation_id, presenter))
+            );
+            assert_eq!(parent.require_exists(&connection).unwrap(), presentation_id);
+            assert!(parent.require_presenter(presenter, &connection).is_ok());
+
+            match parent.require_presenter(alice, &connection) {
+                Err(AttachError::Forbidden(forbidden)) => assert_eq!(forbidden, *parent),
+                result => panic!("Expected a forbidden parent, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn missing_parents_are_not_found() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::presenter("presenter", &connection);
+
+        for parent in &[
+            Parent::Presentation(3),
+            Parent::Question(4),
+            Parent::Option(5),
+        ] {
+            assert_eq!(parent.presentation(&connection).unwrap(), None);
+            match parent.require_presenter(presenter, &connection) {
+                Err(AttachError::NotFound(missing)) => assert_eq!(missing, *parent),
+                result => panic!("Expected a missing parent, got {:?}", result),
+            }
+            match parent.require_exists(&connection) {
+                Err(AttachError::NotFound(_)) => {}
+                result => panic!("Expected a missing parent, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn owners_without_credentials_are_forbidden() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        // Anyone could log in by this name, so owning the presentation is not enough.
+        let owner = common::user("demo", &connection);
+        let presentation_id = common::presentation(owner, &connection);
+
+        match Parent::Presentation(presentation_id).require_presenter(owner, &connection) {
+            Err(AttachError::Forbidden(_)) => {}
+            result => panic!("Expected a forbidden parent, got {:?}", result),
+        }
+    }
+}
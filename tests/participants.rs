@@ -0,0 +1,168 @@
+extern crate diesel;
+extern crate questionnaire_rs;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use questionnaire_rs::participants::DeviceTokens;
+
+fn device_tokens(keys: &[&str]) -> DeviceTokens {
+    DeviceTokens::new(keys.iter().map(|key| key.as_bytes().to_vec()).collect())
+}
+
+#[test]
+fn device_tokens_round_trip() {
+    let tokens = device_tokens(&["first secret"]);
+    let token = tokens.sign(5);
+
+    assert!(token.starts_with("5."));
+    assert_eq!(tokens.verify(&token), Some(5));
+    assert_ne!(token, tokens.sign(6));
+}
+
+#[test]
+fn rejects_tampered_device_tokens() {
+    let tokens = device_tokens(&["first secret"]);
+    let token = tokens.sign(5);
+    let signature = token.splitn(2, '.').nth(1).unwrap();
+
+    assert_eq!(tokens.verify(&format!("6.{}", signature)), None);
+    assert_eq!(tokens.verify(&token[..token.len() - 2]), None);
+    assert_eq!(tokens.verify("5"), None);
+    assert_eq!(tokens.verify("5.zz"), None);
+    assert_eq!(tokens.verify(""), None);
+    assert_eq!(device_tokens(&["other secret"]).verify(&token), None);
+}
+
+#[test]
+fn device_tokens_survive_key_rotation() {
+    let token = device_tokens(&["first secret"]).sign(5);
+    let rotated = device_tokens(&["second secret", "first secret"]);
+
+    assert_eq!(rotated.verify(&token), Some(5));
+    assert_ne!(rotated.sign(5), token);
+    assert_eq!(device_tokens(&["second secret"]).verify(&token), None);
+}
+
+#[cfg(feature = "sqlite")]
+mod answers {
+    use super::common;
+    use diesel::prelude::*;
+    use diesel::result::{DatabaseErrorKind, Error as DieselError};
+    use questionnaire_rs::answers::{new_answer, new_participant_answer, ParticipantAnswer};
+    use questionnaire_rs::models::NewAnswer;
+    use questionnaire_rs::parents::{AttachError, Parent};
+    use questionnaire_rs::participants::create_participant;
+    use questionnaire_rs::questions::open_question;
+    use questionnaire_rs::DbConnection;
+
+    /// Creates an open question with two options, and returns the ids of the options.
+    fn open_question_with_options(
+        presentation_id: i32,
+        user_id: i32,
+        connection: &DbConnection,
+    ) -> (i32, i32) {
+        let question_id = common::question(presentation_id, user_id, connection);
+        let red = common::option("Red", question_id, user_id, connection);
+        let green = common::option("Green", question_id, user_id, connection);
+        open_question(question_id, None, common::creation_time(), connection).unwrap();
+
+        (red, green)
+    }
+
+    #[test]
+    fn participants_answer_each_question_once() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::user("presenter", &connection);
+        let presentation_id = common::presentation(presenter, &connection);
+        let (red, green) = open_question_with_options(presentation_id, presenter, &connection);
+        let (yes, _) = open_question_with_options(presentation_id, presenter, &connection);
+        let participant = create_participant(presentation_id, &connection).unwrap();
+        let other = create_participant(presentation_id, &connection).unwrap();
+
+        let answer = |option_id, participant_id| {
+            new_participant_answer(option_id, participant_id, &connection).unwrap()
+        };
+
+        assert_eq!(answer(red, participant.id), ParticipantAnswer::Created);
+        assert_eq!(
+            answer(red, participant.id),
+            ParticipantAnswer::AlreadyAnswered
+        );
+        assert_eq!(
+            answer(green, participant.id),
+            ParticipantAnswer::AlreadyAnswered
+        );
+        assert_eq!(answer(yes, participant.id), ParticipantAnswer::Created);
+        assert_eq!(answer(green, other.id), ParticipantAnswer::Created);
+    }
+
+    #[test]
+    fn the_database_refuses_a_second_answer() {
+        use questionnaire_rs::schema::answers;
+
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::user("presenter", &connection);
+        let presentation_id = common::presentation(presenter, &connection);
+        let (red, green) = open_question_with_options(presentation_id, presenter, &connection);
+        let participant = create_participant(presentation_id, &connection).unwrap();
+        new_participant_answer(red, participant.id, &connection).unwrap();
+
+        // As if a concurrent request had passed the same checks.
+        let question_id = answers::table
+            .select(answers::question_id)
+            .first::<i32>(&connection)
+            .unwrap();
+        let second = NewAnswer::from_participant(
+            participant.id,
+            common::creation_time(),
+            green,
+            question_id,
+        );
+        match diesel::insert_into(answers::table)
+            .values(&second)
+            .execute(&connection)
+        {
+            Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {}
+            result => panic!("Expected a unique violation, got {:?}", result),
+        }
+
+        // Users, who have no participant, answer as often as they like.
+        new_answer(red, presenter, &connection).unwrap();
+        new_answer(green, presenter, &connection).unwrap();
+    }
+
+    #[test]
+    fn participants_only_answer_their_presentation() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::user("presenter", &connection);
+        let presentation_id = common::presentation(presenter, &connection);
+        let other_presentation = common::presentation(presenter, &connection);
+        let (red, _) = open_question_with_options(presentation_id, presenter, &connection);
+        let outsider = create_participant(other_presentation, &connection).unwrap();
+
+        match new_participant_answer(red, outsider.id, &connection) {
+            Err(AttachError::Forbidden(Parent::Option(option_id))) => assert_eq!(option_id, red),
+            result => panic!("Expected a forbidden answer, got {:?}", result),
+        }
+        match new_participant_answer(red, outsider.id + 1, &connection) {
+            Err(AttachError::Forbidden(_)) => {}
+            result => panic!("Expected a forbidden answer, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn participants_join_existing_presentations() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::user("presenter", &connection);
+        let presentation_id = common::presentation(presenter, &connection);
+
+        let participant = create_participant(presentation_id, &connection).unwrap();
+        assert_eq!(participant.presentation_id, presentation_id);
+        assert!(create_participant(presentation_id + 1, &connection).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+#![cfg(feature = "sqlite")]
+
+extern crate questionnaire_rs;
+
+mod common;
+
+use questionnaire_rs::answers::new_answer;
+use questionnaire_rs::presentations::{count_votes_by_option, get_presentation_tree};
+use questionnaire_rs::questions::open_question;
+
+#[test]
+fn tree_keeps_questions_and_options_in_order() {
+    let pool = common::test_pool();
+    let connection = pool.get().unwrap();
+    let user_id = common::user("alice", &connection);
+    let presentation_id = common::presentation(user_id, &connection);
+
+    let first = common::question(presentation_id, user_id, &connection);
+    let empty = common::question(presentation_id, user_id, &connection);
+    let third = common::question(presentation_id, user_id, &connection);
+    // Options of different questions are created interleaved.
+    let red = common::option("Red", first, user_id, &connection);
+    let yes = common::option("Yes", third, user_id, &connection);
+    let green = common::option("Green", first, user_id, &connection);
+    let no = common::option("No", third, user_id, &connection);
+
+    let tree = get_presentation_tree(presentation_id, false, &connection).unwrap();
+    let questions: Vec<(i32, Vec<i32>)> = tree
+        .questions
+        .iter()
+        .map(|tree| {
+            let options = tree.options.iter().map(|tree| tree.option.id).collect();
+            (tree.question.id, options)
+        })
+        .collect();
+
+    assert_eq!(tree.presentation.id, presentation_id);
+    assert_eq!(
+        questions,
+        vec![
+            (first, vec![red, green]),
+            (empty, vec![]),
+            (third, vec![yes, no])
+        ]
+    );
+    assert!(tree.questions[0].options[0].votes.is_none());
+}
+
+#[test]
+fn tree_counts_votes_when_asked() {
+    let pool = common::test_pool();
+    let connection = pool.get().unwrap();
+    let presenter = common::user("presenter", &connection);
+    let alice = common::user("alice", &connection);
+    let presentation_id = common::presentation(presenter, &connection);
+    let question_id = common::question(presentation_id, presenter, &connection);
+    let red = common::option("Red", question_id, presenter, &connection);
+    let green = common::option("Green", question_id, presenter, &connection);
+    open_question(question_id, None, common::creation_time(), &connection).unwrap();
+
+    new_answer(red, alice, &connection).unwrap();
+    new_answer(red, presenter, &connection).unwrap();
+
+    let tree = get_presentation_tree(presentation_id, true, &connection).unwrap();
+    let votes: Vec<Option<i64>> = tree.questions[0]
+        .options
+        .iter()
+        .map(|tree| tree.votes)
+        .collect();
+
+    assert_eq!(votes, vec![Some(2), Some(0)]);
+
+    let counts = count_votes_by_option(&[red, green], &connection).unwrap();
+    assert_eq!(counts.get(&red), Some(&2));
+    assert_eq!(counts.get(&green), None);
+}
+
+#[test]
+fn tree_of_a_presentation_without_questions() {
+    let pool = common::test_pool();
+    let connection = pool.get().unwrap();
+    let user_id = common::user("alice", &connection);
+    let presentation_id = common::presentation(user_id, &connection);
+
+    let tree = get_presentation_tree(presentation_id, true, &connection).unwrap();
+    assert!(tree.questions.is_empty());
+
+    assert!(get_presentation_tree(presentation_id + 1, false, &connection).is_err());
+}
@@ -0,0 +1,261 @@
+extern crate chrono;
+extern crate diesel;
+extern crate questionnaire_rs;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use questionnaire_rs::models::Questions;
+use questionnaire_rs::questions::is_open;
+
+fn opening_time() -> NaiveDateTime {
+    NaiveDate::from_ymd(2020, 11, 18).and_hms(9, 0, 0)
+}
+
+fn question(
+    opened_at: Option<NaiveDateTime>,
+    closes_at: Option<NaiveDateTime>,
+    closed_at: Option<NaiveDateTime>,
+) -> Questions {
+    Questions {
+        id: 1,
+        title: String::from("What is your favourite colour?"),
+        created: opening_time() - Duration::days(1),
+        presentation_id: 3,
+        user_id: 7,
+        opened_at,
+        closes_at,
+        closed_at,
+    }
+}
+
+#[test]
+fn questions_are_closed_until_opened() {
+    assert!(!is_open(&question(None, None, None), opening_time()));
+}
+
+#[test]
+fn open_questions() {
+    let open = question(Some(opening_time()), None, None);
+
+    assert!(!is_open(&open, opening_time() - Duration::seconds(1)));
+    assert!(is_open(&open, opening_time()));
+    assert!(is_open(&open, opening_time() + Duration::days(1)));
+}
+
+#[test]
+fn questions_close_once_their_duration_passed() {
+    let closes_at = opening_time() + Duration::seconds(30);
+    let timed = question(Some(opening_time()), Some(closes_at), None);
+
+    assert!(is_open(&timed, closes_at - Duration::seconds(1)));
+    assert!(!is_open(&timed, closes_at));
+}
+
+#[test]
+fn closed_questions() {
+    let closed_at = opening_time() + Duration::seconds(10);
+    let closed = question(
+        Some(opening_time()),
+        Some(opening_time() + Duration::seconds(30)),
+        Some(closed_at),
+    );
+
+    assert!(!is_open(&closed, closed_at));
+    assert!(!is_open(&closed, opening_time() + Duration::seconds(20)));
+}
+
+#[cfg(feature = "sqlite")]
+mod with_options {
+    use super::common;
+    use diesel::connection::SimpleConnection;
+    use diesel::prelude::*;
+    use questionnaire_rs::models::{NewQuestion, Option, QuestionOptionJson};
+    use questionnaire_rs::questions::{get_question_by_presentation, new_question_with_options};
+    use questionnaire_rs::schema::options;
+
+    fn options(data: &[&str]) -> Vec<QuestionOptionJson> {
+        data.iter()
+            .map(|data| QuestionOptionJson {
+                data: String::from(*data),
+                is_correct: *data == "Green",
+            })
+            .collect()
+    }
+
+    fn record(presentation_id: i32, user_id: i32) -> NewQuestion {
+        NewQuestion::new(
+            String::from("What is your favourite colour?"),
+            common::creation_time(),
+            presentation_id,
+            user_id,
+        )
+    }
+
+    #[test]
+    fn option_ids_follow_the_input_order() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let user_id = common::user("alice", &connection);
+        let presentation_id = common::presentation(user_id, &connection);
+
+        let created = new_question_with_options(
+            record(presentation_id, user_id),
+            options(&["Red", "Green", "Blue"]),
+            &connection,
+        )
+        .unwrap();
+        let stored: Vec<(String, bool)> = created
+            .option_ids
+            .iter()
+            .map(|id| {
+                let option: Option = options::table.find(id).first(&connection).unwrap();
+                assert_eq!(option.question_id, created.question_id);
+
+                (option.data, option.is_correct)
+            })
+            .collect();
+
+        assert_eq!(
+            stored,
+            vec![
+                (String::from("Red"), false),
+                (String::from("Green"), true),
+                (String::from("Blue"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn failing_option_rolls_back_the_question() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let user_id = common::user("alice", &connection);
+        let presentation_id = common::presentation(user_id, &connection);
+
+        connection
+            .batch_execute(
+                "CREATE TEMP TRIGGER refuse_option BEFORE INSERT ON options
+                 WHEN NEW.data = 'Blue'
+                 BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+            )
+            .unwrap();
+
+        let result = new_question_with_options(
+            record(presentation_id, user_id),
+            options(&["Red", "Green", "Blue"]),
+            &connection,
+        );
+
+        assert!(result.is_err());
+        assert!(get_question_by_presentation(presentation_id, &connection)
+            .unwrap()
+            .is_empty());
+        let count: i64 = options::table.count().get_result(&connection).unwrap();
+        assert_eq!(count, 0);
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod voting_windows {
+    use super::common;
+    use chrono::{Duration, Utc};
+    use questionnaire_rs::answers::{new_answer, new_participant_answer};
+    use questionnaire_rs::parents::{AttachError, Parent};
+    use questionnaire_rs::participants::create_participant;
+    use questionnaire_rs::questions::{close_question, open_question};
+    use questionnaire_rs::web_socket::require_presenter;
+
+    #[test]
+    fn closed_questions_refuse_answers() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::presenter("presenter", &connection);
+        let alice = common::user("alice", &connection);
+        let presentation_id = common::presentation(presenter, &connection);
+        let question_id = common::question(presentation_id, presenter, &connection);
+        let red = common::option("Red", question_id, presenter, &connection);
+        let participant = create_participant(presentation_id, &connection).unwrap();
+
+        let refused = |error: Option<AttachError>| match error {
+            Some(AttachError::Closed(Parent::Question(id))) => assert_eq!(id, question_id),
+            error => panic!("Expected a closed question, got {:?}", error),
+        };
+
+        // New questions are closed until opened.
+        refused(new_answer(red, alice, &connection).err());
+        refused(new_participant_answer(red, participant.id, &connection).err());
+
+        let now = Utc::now().naive_utc();
+        open_question(question_id, None, now, &connection).unwrap();
+        new_answer(red, alice, &connection).unwrap();
+
+        close_question(question_id, now, &connection).unwrap();
+        refused(new_answer(red, alice, &connection).err());
+        refused(new_participant_answer(red, participant.id, &connection).err());
+
+        // A duration that passed closes the question as well.
+        open_question(
+            question_id,
+            Some(Duration::seconds(30)),
+            now - Duration::seconds(30),
+            &connection,
+        )
+        .unwrap();
+        refused(new_answer(red, alice, &connection).err());
+    }
+
+    #[test]
+    fn only_the_presenter_opens_and_closes_questions() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::presenter("presenter", &connection);
+        let alice = common::user("alice", &connection);
+        let presentation_id = common::presentation(presenter, &connection);
+        let question_id = common::question(presentation_id, presenter, &connection);
+
+        assert_eq!(
+            require_presenter(question_id, Some(presenter), &connection),
+            Ok(())
+        );
+        assert_eq!(
+            require_presenter(question_id, Some(alice), &connection),
+            Err(String::from(
+                "Only the presenter can open or close the question."
+            ))
+        );
+        assert_eq!(
+            require_presenter(question_id, None, &connection),
+            Err(String::from("Log in to open or close questions."))
+        );
+        assert_eq!(
+            require_presenter(question_id + 1, Some(presenter), &connection),
+            Err(String::from("Could not find the question."))
+        );
+    }
+
+    #[test]
+    fn closing_keeps_the_first_closing_time() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let presenter = common::presenter("presenter", &connection);
+        let presentation_id = common::presentation(presenter, &connection);
+        let question_id = common::question(presentation_id, presenter, &connection);
+        let now = common::creation_time();
+
+        let opened = open_question(question_id, None, now, &connection).unwrap();
+        assert_eq!(opened.opened_at, Some(now));
+        assert_eq!(opened.closed_at, None);
+
+        let closed_at = now + Duration::seconds(10);
+        close_question(question_id, closed_at, &connection).unwrap();
+        let closed =
+            close_question(question_id, closed_at + Duration::seconds(5), &connection).unwrap();
+        assert_eq!(closed.closed_at, Some(closed_at));
+
+        // Opening again clears the closing time.
+        let reopened = open_question(question_id, None, closed_at, &connection).unwrap();
+        assert_eq!(reopened.closed_at, None);
+    }
+}
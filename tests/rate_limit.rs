@@ -0,0 +1,115 @@
+extern crate actix_rt;
+extern crate actix_web;
+extern crate questionnaire_rs;
+
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{web, App, HttpResponse};
+use questionnaire_rs::config::{RateLimit, RateLimits};
+use questionnaire_rs::participants::DeviceTokens;
+use questionnaire_rs::rate_limit::{retry_after_seconds, RateLimiter, RouteGroup, TokenBucket};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+const LIMIT: RateLimit = RateLimit {
+    requests: 3,
+    seconds: 6,
+};
+
+#[test]
+fn token_bucket() {
+    let start = Instant::now();
+    let mut bucket = TokenBucket::new(LIMIT, start);
+
+    for _ in 0..3 {
+        assert_eq!(bucket.take(LIMIT, start), Ok(()));
+    }
+    assert_eq!(bucket.take(LIMIT, start), Err(Duration::from_secs(2)));
+
+    // A token every two seconds.
+    let later = start + Duration::from_secs(3);
+    assert_eq!(bucket.take(LIMIT, later), Ok(()));
+    assert_eq!(bucket.take(LIMIT, later), Err(Duration::from_secs(1)));
+
+    // Never more than the limit.
+    let much_later = start + Duration::from_secs(600);
+    for _ in 0..3 {
+        assert_eq!(bucket.take(LIMIT, much_later), Ok(()));
+    }
+    assert!(bucket.take(LIMIT, much_later).is_err());
+}
+
+#[test]
+fn retry_after() {
+    assert_eq!(retry_after_seconds(Duration::from_millis(1)), 1);
+    assert_eq!(retry_after_seconds(Duration::from_secs(2)), 2);
+    assert_eq!(retry_after_seconds(Duration::from_millis(2001)), 3);
+}
+
+#[test]
+fn route_groups() {
+    assert_eq!(RouteGroup::of(&Method::POST, "/login"), RouteGroup::Auth);
+    assert_eq!(
+        RouteGroup::of(&Method::GET, "/auth/github"),
+        RouteGroup::Auth
+    );
+    assert_eq!(
+        RouteGroup::of(&Method::POST, "/presentations/3/join"),
+        RouteGroup::Join
+    );
+    assert_eq!(RouteGroup::of(&Method::POST, "/answers"), RouteGroup::Write);
+    assert_eq!(
+        RouteGroup::of(&Method::POST, "/api-tokens/3/revoke"),
+        RouteGroup::Write
+    );
+    assert_eq!(RouteGroup::of(&Method::GET, "/answers/3"), RouteGroup::Read);
+    assert_eq!(RouteGroup::of(&Method::GET, "/ws/"), RouteGroup::Read);
+}
+
+/// Joins from one address, and from two forwarded clients behind it, and returns the statuses.
+async fn join_statuses(trust_forwarded_for: bool) -> Vec<StatusCode> {
+    let limits = RateLimits {
+        auth: None,
+        join: Some(LIMIT),
+        write: None,
+        read: None,
+        web_socket: None,
+    };
+    let device_tokens = DeviceTokens::new(vec![b"device token secret".to_vec()]);
+    let mut service = init_service(
+        App::new()
+            .wrap(RateLimiter::new(limits, trust_forwarded_for, device_tokens))
+            .route("/presentations/{id}/join", web::post().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let proxy: SocketAddr = "10.0.0.1:443".parse().unwrap();
+    let mut statuses = vec![];
+    for client in &["203.0.113.7", "203.0.113.8"] {
+        for _ in 0..LIMIT.requests + 1 {
+            let request = TestRequest::post()
+                .uri("/presentations/3/join")
+                .peer_addr(proxy)
+                .header("X-Forwarded-For", *client)
+                .to_request();
+            statuses.push(call_service(&mut service, request).await.status());
+        }
+    }
+
+    statuses
+}
+
+#[actix_rt::test]
+async fn forwarded_clients_are_limited_apart_when_trusted() {
+    let limited = |status: &&StatusCode| **status == StatusCode::TOO_MANY_REQUESTS;
+
+    // Everyone behind the proxy shares its address.
+    let statuses = join_statuses(false).await;
+    assert_eq!(statuses.iter().filter(limited).count(), 5);
+
+    // Each forwarded client gets its own bucket.
+    let statuses = join_statuses(true).await;
+    assert_eq!(statuses.iter().filter(limited).count(), 2);
+    assert_eq!(statuses[3], StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(statuses[4], StatusCode::OK);
+}
@@ -0,0 +1,206 @@
+extern crate chrono;
+extern crate diesel;
+extern crate questionnaire_rs;
+extern crate serde_json;
+
+#[cfg(feature = "sqlite")]
+mod common;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use questionnaire_rs::config::SessionTimeouts;
+use questionnaire_rs::models::{PresentationRole, Role, Session, User};
+use questionnaire_rs::session::{is_expired, IsLoggedInResponse, MeResponse};
+use serde_json::json;
+
+const TIMEOUTS: SessionTimeouts = SessionTimeouts {
+    idle: 30,
+    absolute: 600,
+};
+
+fn login_time() -> NaiveDateTime {
+    NaiveDate::from_ymd(2020, 11, 18).and_hms(9, 0, 0)
+}
+
+fn session(last_seen: NaiveDateTime) -> Session {
+    Session {
+        id: 1,
+        user_id: 7,
+        token_hash: String::new(),
+        created: login_time(),
+        last_seen,
+    }
+}
+
+#[test]
+fn idle_timeout() {
+    let active = session(login_time());
+
+    assert!(!is_expired(&active, TIMEOUTS, login_time()));
+    assert!(!is_expired(
+        &active,
+        TIMEOUTS,
+        login_time() + Duration::minutes(29)
+    ));
+    assert!(is_expired(
+        &active,
+        TIMEOUTS,
+        login_time() + Duration::minutes(30)
+    ));
+}
+
+#[test]
+fn absolute_timeout() {
+    let logout_time = login_time() + Duration::minutes(600);
+    let busy = session(logout_time - Duration::minutes(1));
+
+    assert!(!is_expired(
+        &busy,
+        TIMEOUTS,
+        logout_time - Duration::seconds(1)
+    ));
+    assert!(is_expired(&busy, TIMEOUTS, logout_time));
+}
+
+#[test]
+fn is_logged_in_response() {
+    let anonymous = IsLoggedInResponse {
+        result: false,
+        me: MeResponse {
+            authenticated: false,
+            user: None,
+            roles: vec![],
+        },
+    };
+    assert_eq!(
+        serde_json::to_value(&anonymous).unwrap(),
+        json!({"result": false, "authenticated": false, "user": null, "roles": []})
+    );
+
+    let presenter = IsLoggedInResponse {
+        result: true,
+        me: MeResponse {
+            authenticated: true,
+            user: Some(User {
+                id: 7,
+                name: String::from("agent 42"),
+                created: login_time(),
+            }),
+            roles: vec![PresentationRole {
+                presentation_id: 3,
+                role: Role::Presenter,
+            }],
+        },
+    };
+    assert_eq!(
+        serde_json::to_value(&presenter).unwrap(),
+        json!({
+            "result": true,
+            "authenticated": true,
+            "user": {"id": 7, "name": "agent 42", "created": "2020-11-18T09:00:00"},
+            "roles": [{"presentation_id": 3, "role": "presenter"}],
+        })
+    );
+}
+
+#[cfg(feature = "sqlite")]
+mod sessions {
+    use super::{common, TIMEOUTS};
+    use chrono::{Duration, Utc};
+    use diesel::prelude::*;
+    use questionnaire_rs::auth::hash_api_token;
+    use questionnaire_rs::schema::sessions::dsl::{created, last_seen, sessions, token_hash};
+    use questionnaire_rs::session::{
+        end_session, end_user_sessions, resume_session, start_session,
+    };
+    use questionnaire_rs::DbConnection;
+
+    fn session_count(connection: &DbConnection) -> i64 {
+        sessions.count().get_result(connection).unwrap()
+    }
+
+    #[test]
+    fn sessions_resume_until_they_end() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let alice = common::user("alice", &connection);
+
+        let token = start_session(alice, TIMEOUTS, &connection).unwrap();
+        assert_eq!(
+            resume_session(&token, TIMEOUTS, &connection).unwrap(),
+            Some(alice)
+        );
+        assert_eq!(
+            resume_session("unknown", TIMEOUTS, &connection).unwrap(),
+            None
+        );
+
+        end_session(&token, &connection).unwrap();
+        assert_eq!(resume_session(&token, TIMEOUTS, &connection).unwrap(), None);
+        assert_eq!(session_count(&connection), 0);
+    }
+
+    #[test]
+    fn only_hashes_of_the_tokens_are_stored() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let alice = common::user("alice", &connection);
+
+        let token = start_session(alice, TIMEOUTS, &connection).unwrap();
+        let stored: Vec<String> = sessions.select(token_hash).load(&connection).unwrap();
+        assert_eq!(stored, vec![hash_api_token(&token)]);
+    }
+
+    #[test]
+    fn expired_sessions_are_deleted() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let alice = common::user("alice", &connection);
+        let idle = start_session(alice, TIMEOUTS, &connection).unwrap();
+        let old = start_session(alice, TIMEOUTS, &connection).unwrap();
+
+        let now = Utc::now().naive_utc();
+        diesel::update(sessions.filter(token_hash.eq(hash_api_token(&idle))))
+            .set(last_seen.eq(now - Duration::minutes(TIMEOUTS.idle.into())))
+            .execute(&connection)
+            .unwrap();
+        diesel::update(sessions.filter(token_hash.eq(hash_api_token(&old))))
+            .set(created.eq(now - Duration::minutes(TIMEOUTS.absolute.into())))
+            .execute(&connection)
+            .unwrap();
+
+        assert_eq!(resume_session(&idle, TIMEOUTS, &connection).unwrap(), None);
+        assert_eq!(session_count(&connection), 1);
+
+        // Starting a session cleans up the expired ones of every user.
+        let bob = common::user("bob", &connection);
+        let current = start_session(bob, TIMEOUTS, &connection).unwrap();
+        assert_eq!(session_count(&connection), 1);
+        assert_eq!(resume_session(&old, TIMEOUTS, &connection).unwrap(), None);
+        assert_eq!(
+            resume_session(&current, TIMEOUTS, &connection).unwrap(),
+            Some(bob)
+        );
+    }
+
+    #[test]
+    fn logging_out_everywhere_ends_every_session_of_the_user() {
+        let pool = common::test_pool();
+        let connection = pool.get().unwrap();
+        let alice = common::user("alice", &connection);
+        let bob = common::user("bob", &connection);
+        let laptop = start_session(alice, TIMEOUTS, &connection).unwrap();
+        let phone = start_session(alice, TIMEOUTS, &connection).unwrap();
+        let other = start_session(bob, TIMEOUTS, &connection).unwrap();
+
+        assert_eq!(end_user_sessions(alice, &connection).unwrap(), 2);
+        assert_eq!(
+            resume_session(&laptop, TIMEOUTS, &connection).unwrap(),
+            None
+        );
+        assert_eq!(resume_session(&phone, TIMEOUTS, &connection).unwrap(), None);
+        assert_eq!(
+            resume_session(&other, TIMEOUTS, &connection).unwrap(),
+            Some(bob)
+        );
+    }
+}
@@ -0,0 +1,86 @@
+extern crate questionnaire_rs;
+
+use questionnaire_rs::models::{
+    ApiTokenInput, NewOptionJson, NewQuestionWithOptionsJson, PresentationInput,
+};
+use questionnaire_rs::session::UserLogin;
+use questionnaire_rs::validation::{Text, Validate, SHORT_TEXT};
+
+fn text_errors(rule: Text, value: &str) -> Vec<String> {
+    let mut errors = vec![];
+    rule.check("title", value, &mut errors);
+
+    errors.into_iter().map(|error| error.message).collect()
+}
+
+#[test]
+fn short_text() {
+    assert!(text_errors(SHORT_TEXT, "What is your favourite colour?").is_empty());
+    assert!(text_errors(SHORT_TEXT, &"é".repeat(255)).is_empty());
+    assert_eq!(text_errors(SHORT_TEXT, ""), vec!["must not be empty"]);
+    assert_eq!(text_errors(SHORT_TEXT, " \t\n"), vec!["must not be empty"]);
+    assert_eq!(
+        text_errors(SHORT_TEXT, " Colour"),
+        vec!["must not start or end with whitespace"]
+    );
+    assert_eq!(
+        text_errors(SHORT_TEXT, "Colour\n"),
+        vec!["must not start or end with whitespace"]
+    );
+    assert_eq!(
+        text_errors(SHORT_TEXT, &"a".repeat(256)),
+        vec!["must be at most 255 characters long"]
+    );
+}
+
+#[test]
+fn text_rules_can_be_turned_off() {
+    let rule = Text {
+        non_empty: false,
+        trimmed: false,
+        max_length: 3,
+    };
+
+    assert!(text_errors(rule, "").is_empty());
+    assert!(text_errors(rule, " a ").is_empty());
+    assert_eq!(
+        text_errors(rule, "abcd"),
+        vec!["must be at most 3 characters long"]
+    );
+}
+
+#[test]
+fn input_field_paths() {
+    let question: NewQuestionWithOptionsJson = serde_json::from_str(
+        r#"{"title": "", "presentation_id": 3, "options": [{"data": "Red"}, {"data": " "}]}"#,
+    )
+    .unwrap();
+    let paths: Vec<String> = question
+        .errors()
+        .into_iter()
+        .map(|error| error.path)
+        .collect();
+
+    assert_eq!(paths, vec!["title", "options[1].data"]);
+
+    let option: NewOptionJson =
+        serde_json::from_str(r#"{"data": "Red ", "question_id": 4}"#).unwrap();
+    assert_eq!(option.errors()[0].path, "data");
+
+    let presentation = PresentationInput {
+        title: String::from("Colours"),
+    };
+    assert!(presentation.errors().is_empty());
+
+    let login: UserLogin = serde_json::from_str(r#"{"name": ""}"#).unwrap();
+    assert_eq!(login.errors()[0].path, "name");
+
+    let api_token: ApiTokenInput =
+        serde_json::from_str(r#"{"name": " CI", "scopes": []}"#).unwrap();
+    let paths: Vec<String> = api_token
+        .errors()
+        .into_iter()
+        .map(|error| error.path)
+        .collect();
+    assert_eq!(paths, vec!["name", "scopes"]);
+}
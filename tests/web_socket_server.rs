@@ -0,0 +1,177 @@
+extern crate actix;
+extern crate actix_rt;
+extern crate chrono;
+extern crate questionnaire_rs;
+
+use actix::prelude::*;
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use questionnaire_rs::web_socket_server::{
+    seconds_left, CancelCountdown, JoinSession, Message, SendMessage, StartCountdown,
+    WebSocketServer,
+};
+use std::sync::{Arc, Mutex};
+
+fn deadline() -> NaiveDateTime {
+    NaiveDate::from_ymd(2020, 11, 18).and_hms(9, 0, 30)
+}
+
+#[test]
+fn seconds_left_round_up() {
+    assert_eq!(
+        seconds_left(deadline(), deadline() - Duration::seconds(30)),
+        30
+    );
+    assert_eq!(
+        seconds_left(deadline(), deadline() - Duration::milliseconds(29_001)),
+        30
+    );
+    assert_eq!(
+        seconds_left(deadline(), deadline() - Duration::milliseconds(29_000)),
+        29
+    );
+    assert_eq!(
+        seconds_left(deadline(), deadline() - Duration::milliseconds(1)),
+        1
+    );
+}
+
+#[test]
+fn no_seconds_left_after_the_deadline() {
+    assert_eq!(seconds_left(deadline(), deadline()), 0);
+    assert_eq!(
+        seconds_left(deadline(), deadline() + Duration::seconds(5)),
+        0
+    );
+}
+
+/// A session keeping the messages it gets.
+struct Inbox(Arc<Mutex<Vec<String>>>);
+
+impl Actor for Inbox {
+    type Context = Context<Self>;
+}
+
+impl Handler<Message> for Inbox {
+    type Result = ();
+
+    fn handle(&mut self, msg: Message, _ctx: &mut Self::Context) {
+        self.0.lock().unwrap().push(msg.0);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Stop;
+
+impl Handler<Stop> for Inbox {
+    type Result = ();
+
+    fn handle(&mut self, _: Stop, ctx: &mut Self::Context) {
+        ctx.stop();
+    }
+}
+
+/// Joins a session to the room of a presentation, and returns the messages it gets.
+async fn join(server: &Addr<WebSocketServer>, presentation_id: i32) -> Arc<Mutex<Vec<String>>> {
+    let messages = Arc::new(Mutex::new(vec![]));
+    let recipient = Inbox(messages.clone()).start().recipient();
+    server
+        .send(JoinSession {
+            recipient,
+            presentation_id: Some(presentation_id),
+        })
+        .await
+        .unwrap();
+
+    messages
+}
+
+fn countdown(question_id: i32, milliseconds: i64, name: &'static str) -> StartCountdown {
+    StartCountdown {
+        question_id,
+        presentation_id: 3,
+        deadline: Utc::now().naive_utc() + Duration::milliseconds(milliseconds),
+        tick: Box::new(move |seconds_left| format!("{} {}", name, seconds_left)),
+        closed: format!("{} closed", name),
+    }
+}
+
+async fn wait(milliseconds: u64) {
+    actix_rt::time::delay_for(std::time::Duration::from_millis(milliseconds)).await;
+}
+
+#[actix_rt::test]
+async fn countdowns_tick_in_their_room_and_close_once() {
+    let server = WebSocketServer::default().start();
+    let room = join(&server, 3).await;
+    let other_room = join(&server, 4).await;
+
+    server.do_send(countdown(23, 2_500, "vote"));
+    wait(3_700).await;
+
+    assert_eq!(
+        *room.lock().unwrap(),
+        vec!["vote 2", "vote 1", "vote closed"]
+    );
+    assert!(other_room.lock().unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn cancelled_countdowns_stop() {
+    let server = WebSocketServer::default().start();
+    let room = join(&server, 3).await;
+
+    server.do_send(countdown(23, 1_500, "vote"));
+    server.do_send(countdown(24, 1_500, "other"));
+    wait(500).await;
+    server.do_send(CancelCountdown(23));
+    wait(1_700).await;
+
+    assert_eq!(*room.lock().unwrap(), vec!["other 1", "other closed"]);
+}
+
+#[actix_rt::test]
+async fn restarted_countdowns_replace_the_old_one() {
+    let server = WebSocketServer::default().start();
+    let room = join(&server, 3).await;
+
+    server.do_send(countdown(23, 10_000, "old"));
+    server.do_send(countdown(23, 1_500, "new"));
+    wait(2_700).await;
+
+    assert_eq!(*room.lock().unwrap(), vec!["new 1", "new closed"]);
+}
+
+#[actix_rt::test]
+async fn closed_sessions_are_dropped_instead_of_stopping_the_server() {
+    let server = WebSocketServer::default().start();
+    // A socket that closed before its `RemoveSession` reached the server.
+    let closed = Inbox(Arc::new(Mutex::new(vec![]))).start();
+    let recipient = closed.clone().recipient();
+    closed.send(Stop).await.unwrap();
+    server
+        .send(JoinSession {
+            recipient,
+            presentation_id: Some(3),
+        })
+        .await
+        .unwrap();
+    let room = join(&server, 3).await;
+
+    server.do_send(countdown(23, 1_500, "vote"));
+    wait(1_700).await;
+    server
+        .send(SendMessage {
+            name: String::from("Message"),
+            id: 0,
+            content: String::from("hello"),
+            presentation_id: None,
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *room.lock().unwrap(),
+        vec!["vote 1", "vote closed", "hello"]
+    );
+}